@@ -0,0 +1,74 @@
+//! Writes part of an epoch, dies without flushing, and restarts on the same directory.
+//!
+//! The restarted rotator opens the epoch after the persisted one, so the crashed file keeps
+//! whatever was flushed before the crash and is never appended to again.
+
+use std::{num::NonZeroUsize, path::PathBuf};
+
+use clap::Parser;
+use file_rotating_log::{
+    rotator::{LogRotator, RotationPolicy, RotatorError},
+    writers::csv::CsvLogWriter,
+};
+use serde::Serialize;
+
+#[derive(Debug, Parser)]
+pub struct Args {
+    #[arg(long)]
+    pub output_dir: PathBuf,
+    /// Records flushed before the crash.
+    #[arg(long, default_value_t = 3)]
+    pub flushed: usize,
+    /// Records still buffered when the process dies.
+    #[arg(long, default_value_t = 2)]
+    pub lost: usize,
+    /// Records written after the restart.
+    #[arg(long, default_value_t = 3)]
+    pub after_restart: usize,
+    #[arg(long, default_value = "100")]
+    pub max_records: NonZeroUsize,
+}
+
+#[derive(Debug, Serialize)]
+struct Record {
+    phase: &'static str,
+    n: usize,
+}
+
+pub fn run(args: Args) -> Result<(), RotatorError> {
+    let rotation = RotationPolicy {
+        max_records: Some(args.max_records),
+        repair_on_startup: true,
+        ..Default::default()
+    };
+    let write = |rotator: &mut LogRotator<CsvLogWriter>, phase, n| {
+        rotator
+            .writer()
+            .writer()
+            .serialize(Record { phase, n })
+            .expect("Failed to serialize the record");
+        rotator.try_incr_record_count().map(drop)
+    };
+
+    let mut rotator = LogRotator::<CsvLogWriter>::new(args.output_dir.clone(), rotation.clone())?;
+    for n in 0..args.flushed {
+        write(&mut rotator, "before", n)?;
+    }
+    rotator.flush()?;
+    for n in 0..args.lost {
+        write(&mut rotator, "lost", n)?;
+    }
+    // Skips every destructor like a killed process would, losing the buffered records.
+    std::mem::forget(rotator);
+
+    let mut rotator = LogRotator::<CsvLogWriter>::new(args.output_dir, rotation)?;
+    println!("{:#?}", rotator.startup_report());
+    for n in 0..args.after_restart {
+        write(&mut rotator, "after", n)?;
+    }
+    rotator.flush()
+}
+
+fn main() -> Result<(), RotatorError> {
+    run(Args::parse())
+}
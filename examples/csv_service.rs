@@ -0,0 +1,62 @@
+//! A service writing its request log to CSV files rotated daily and every `--max-records`.
+
+use std::{num::NonZeroUsize, path::PathBuf, time::Duration};
+
+use clap::Parser;
+use file_rotating_log::{
+    rotator::{BuildError, LogRotator},
+    writers::csv::CsvLogWriter,
+};
+use serde::Serialize;
+
+#[derive(Debug, Parser)]
+pub struct Args {
+    #[arg(long)]
+    pub output_dir: PathBuf,
+    /// Requests to log before exiting.
+    #[arg(long, default_value_t = 100)]
+    pub requests: usize,
+    #[arg(long)]
+    pub max_records: Option<NonZeroUsize>,
+    #[arg(long, default_value_t = 7)]
+    pub max_epochs: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct Request {
+    id: usize,
+    path: &'static str,
+    status: u16,
+}
+
+pub fn run(args: Args) -> Result<(), BuildError> {
+    let mut builder = LogRotator::<CsvLogWriter>::builder(args.output_dir)
+        .daily()
+        .max_epochs(args.max_epochs)
+        .flush_every(Duration::from_secs(1));
+    if let Some(max_records) = args.max_records {
+        builder = builder.max_records(max_records.get());
+    }
+    let (rotator, _flusher) = builder.build()?;
+
+    for id in 0..args.requests {
+        let request = Request {
+            id,
+            path: if id % 3 == 0 { "/health" } else { "/api" },
+            status: if id % 7 == 0 { 500 } else { 200 },
+        };
+        let mut rotator = rotator.lock().unwrap();
+        rotator
+            .writer()
+            .writer()
+            .serialize(&request)
+            .expect("Failed to serialize the request");
+        rotator.try_incr_record_count()?;
+    }
+    let mut rotator = rotator.lock().unwrap();
+    Ok(rotator.flush()?)
+}
+
+fn main() -> Result<(), BuildError> {
+    run(Args::parse())
+}
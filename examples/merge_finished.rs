@@ -0,0 +1,76 @@
+//! Merges the finished files of a CSV table into one file, skipping the file each instance is
+//! still writing to.
+//!
+//! With `--watch`, keeps merging files as they finish.
+
+use std::{collections::BTreeSet, path::PathBuf, time::Duration};
+
+use clap::Parser;
+use file_rotating_log::rotator::list_log_files;
+
+#[derive(Debug, Parser)]
+pub struct Args {
+    /// Output directory of the table.
+    #[arg(long)]
+    pub input_dir: PathBuf,
+    /// Destination of the merged rows.
+    #[arg(long)]
+    pub output: PathBuf,
+    #[arg(long)]
+    pub watch: bool,
+}
+
+/// Appends the files finished since `merged` to `output`.
+///
+/// Returns the number of rows appended.
+pub fn merge_once(args: &Args, merged: &mut BTreeSet<PathBuf>) -> usize {
+    let files = list_log_files(&args.input_dir, "csv", None);
+    let mut write_header = !args.output.exists();
+    let output = std::fs::File::options()
+        .create(true)
+        .append(true)
+        .open(&args.output)
+        .expect("Failed to open the output");
+    let mut writer = csv::Writer::from_writer(output);
+    let mut rows = 0;
+    for (i, file) in files.iter().enumerate() {
+        let is_current = files[i + 1..].iter().all(|f| f.instance != file.instance);
+        if is_current || merged.contains(&file.path) {
+            continue;
+        }
+        let mut reader = csv::ReaderBuilder::new()
+            .comment(Some(b'#'))
+            .from_path(&file.path)
+            .expect("Failed to open a finished file");
+        if write_header {
+            let header = reader.headers().expect("Failed to read the header");
+            writer.write_record(header).unwrap();
+            write_header = false;
+        }
+        for record in reader.records() {
+            writer
+                .write_record(&record.expect("Malformed record"))
+                .unwrap();
+            rows += 1;
+        }
+        merged.insert(file.path.clone());
+    }
+    writer.flush().unwrap();
+    rows
+}
+
+pub fn run(args: Args) {
+    let mut merged = BTreeSet::new();
+    loop {
+        let rows = merge_once(&args, &mut merged);
+        println!("Merged {rows} rows from {} files", merged.len());
+        if !args.watch {
+            return;
+        }
+        std::thread::sleep(Duration::from_secs(1));
+    }
+}
+
+fn main() {
+    run(Args::parse());
+}
@@ -0,0 +1,72 @@
+//! A distributor writing several tables, laid out by a JSON config file such as:
+//!
+//! ```json
+//! {
+//!     "output_dir": "logs",
+//!     "max_records": 1000,
+//!     "max_epochs": 4,
+//!     "tables": { "requests": 2500, "errors": 10 }
+//! }
+//! ```
+//!
+//! Each table goes to `<output_dir>/<table>/` with as many records as configured.
+
+use std::{collections::BTreeMap, num::NonZeroUsize, path::PathBuf};
+
+use clap::Parser;
+use file_rotating_log::{
+    distributor::LogDistributor,
+    rotator::{RotationPolicy, RotatorError},
+    writers::csv::CsvLogWriter,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Parser)]
+pub struct Args {
+    #[arg(long)]
+    pub config: PathBuf,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Config {
+    pub output_dir: PathBuf,
+    pub max_records: Option<NonZeroUsize>,
+    pub max_epochs: usize,
+    /// Records to write per table.
+    pub tables: BTreeMap<String, usize>,
+}
+
+#[derive(Debug, Serialize)]
+struct Record<'a> {
+    table: &'a str,
+    n: usize,
+}
+
+pub fn run(args: Args) -> Result<(), RotatorError> {
+    let config = std::fs::read_to_string(&args.config).expect("Failed to read the config file");
+    let config: Config = serde_json::from_str(&config).expect("Invalid config file");
+    let rotation = RotationPolicy {
+        max_records: config.max_records,
+        max_epochs: config.max_epochs,
+        ..Default::default()
+    };
+    let mut distributor = LogDistributor::<CsvLogWriter>::new(config.output_dir, rotation);
+
+    for (table, records) in config.tables {
+        let mut handle = distributor.table_handle(&table);
+        for n in 0..records {
+            handle
+                .write(|w| {
+                    w.writer()
+                        .serialize(Record { table: &table, n })
+                        .expect("Failed to serialize the record")
+                })
+                .expect("Failed to write the record");
+        }
+    }
+    distributor.flush()
+}
+
+fn main() -> Result<(), RotatorError> {
+    run(Args::parse())
+}
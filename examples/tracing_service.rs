@@ -0,0 +1,58 @@
+//! A service logging through `tracing` to text files rotated daily and every `--max-records`.
+
+use std::{
+    num::NonZeroUsize,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use clap::Parser;
+use file_rotating_log::{
+    rotator::{spawn_flushers, LogRotator, RotationPolicy, RotatorError},
+    time_past::{DailyContains, TimePast},
+    tracing::RotatingMakeWriter,
+    writers::text::TextLogWriter,
+};
+
+#[derive(Debug, Parser)]
+pub struct Args {
+    #[arg(long)]
+    pub output_dir: PathBuf,
+    /// Requests to log before exiting.
+    #[arg(long, default_value_t = 100)]
+    pub requests: usize,
+    #[arg(long)]
+    pub max_records: Option<NonZeroUsize>,
+    #[arg(long, default_value_t = 7)]
+    pub max_epochs: usize,
+}
+
+pub fn run(args: Args) -> Result<(), RotatorError> {
+    let rotation = RotationPolicy {
+        max_records: args.max_records,
+        time: Some(TimePast::new(Arc::new(DailyContains))),
+        max_epochs: args.max_epochs,
+        ..Default::default()
+    };
+    let rotator = LogRotator::<TextLogWriter>::new(args.output_dir, rotation)?;
+    let rotator = Arc::new(Mutex::new(rotator));
+    spawn_flushers(vec![Arc::clone(&rotator)], Duration::from_secs(1));
+    tracing_subscriber::fmt()
+        .with_writer(RotatingMakeWriter::new(Arc::clone(&rotator)))
+        .init();
+
+    for id in 0..args.requests {
+        if id % 7 == 0 {
+            tracing::warn!(id, status = 500, "request failed");
+        } else {
+            tracing::info!(id, status = 200, "request served");
+        }
+    }
+    let mut rotator = rotator.lock().unwrap();
+    rotator.flush()
+}
+
+fn main() -> Result<(), RotatorError> {
+    run(Args::parse())
+}
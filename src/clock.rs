@@ -0,0 +1,47 @@
+//! Where the rotator reads the time from, so that time-based rotation can be tested without
+//! waiting on the wall clock.
+
+use std::sync::Mutex;
+
+pub trait Clock: core::fmt::Debug + Sync + Send {
+    fn now(&self) -> jiff::Zoned;
+}
+
+/// The wall clock in the system time zone.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+impl Clock for SystemClock {
+    fn now(&self) -> jiff::Zoned {
+        jiff::Zoned::now()
+    }
+}
+
+/// Stands still until set or advanced.
+#[derive(Debug)]
+pub struct MockClock {
+    now: Mutex<jiff::Zoned>,
+}
+impl MockClock {
+    pub fn new(now: jiff::Zoned) -> Self {
+        Self {
+            now: Mutex::new(now),
+        }
+    }
+
+    pub fn set(&self, now: jiff::Zoned) {
+        *self.now.lock().unwrap() = now;
+    }
+
+    /// # Panics
+    ///
+    /// Panics if the time leaves the supported range.
+    pub fn advance(&self, duration: jiff::SignedDuration) {
+        let mut now = self.now.lock().unwrap();
+        *now = now.checked_add(duration).expect("The time is out of range");
+    }
+}
+impl Clock for MockClock {
+    fn now(&self) -> jiff::Zoned {
+        self.now.lock().unwrap().clone()
+    }
+}
@@ -0,0 +1,139 @@
+use std::{io, path::Path};
+
+/// Compresses a finished log file.
+///
+/// Compressed files are named `<log file name>.<extension>`.
+pub trait Compressor: core::fmt::Debug + Sync + Send {
+    /// `dst` may already exist and should be truncated rather than replaced, which keeps its
+    /// creation time.
+    fn compress(&self, src: &Path, dst: &Path) -> io::Result<()>;
+    fn extension(&self) -> &str;
+
+    /// Opens the file compressed at `src` to read it uncompressed.
+    ///
+    /// Fails with [`io::ErrorKind::Unsupported`] unless implemented.
+    fn decompress(&self, src: &Path) -> io::Result<Box<dyn io::Read + Send>> {
+        let msg = format!("{self:?} cannot decompress {}", src.display());
+        Err(io::Error::new(io::ErrorKind::Unsupported, msg))
+    }
+}
+
+#[cfg(feature = "gzip")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gzip {
+    pub level: u32,
+}
+#[cfg(feature = "gzip")]
+impl Default for Gzip {
+    fn default() -> Self {
+        Self { level: 6 }
+    }
+}
+#[cfg(feature = "gzip")]
+impl Compressor for Gzip {
+    fn compress(&self, src: &Path, dst: &Path) -> io::Result<()> {
+        let mut src = std::fs::File::open(src)?;
+        let dst = std::fs::File::create(dst)?;
+        let mut encoder = flate2::write::GzEncoder::new(dst, flate2::Compression::new(self.level));
+        io::copy(&mut src, &mut encoder)?;
+        encoder.finish()?.sync_all()
+    }
+
+    fn extension(&self) -> &str {
+        "gz"
+    }
+
+    fn decompress(&self, src: &Path) -> io::Result<Box<dyn io::Read + Send>> {
+        let src = std::fs::File::open(src)?;
+        Ok(Box::new(flate2::read::MultiGzDecoder::new(src)))
+    }
+}
+
+#[cfg(feature = "zstd")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Zstd {
+    pub level: i32,
+}
+#[cfg(feature = "zstd")]
+impl Default for Zstd {
+    fn default() -> Self {
+        Self {
+            level: zstd::DEFAULT_COMPRESSION_LEVEL,
+        }
+    }
+}
+#[cfg(feature = "zstd")]
+impl Compressor for Zstd {
+    fn compress(&self, src: &Path, dst: &Path) -> io::Result<()> {
+        let mut src = std::fs::File::open(src)?;
+        let dst = std::fs::File::create(dst)?;
+        let mut encoder = zstd::Encoder::new(dst, self.level)?;
+        io::copy(&mut src, &mut encoder)?;
+        encoder.finish()?.sync_all()
+    }
+
+    fn extension(&self) -> &str {
+        "zst"
+    }
+
+    fn decompress(&self, src: &Path) -> io::Result<Box<dyn io::Read + Send>> {
+        let src = std::fs::File::open(src)?;
+        Ok(Box::new(zstd::Decoder::new(src)?))
+    }
+}
+
+/// Path of the compressed counterpart of `path`.
+pub(crate) fn compressed_path(path: &Path, compressor: &dyn Compressor) -> std::path::PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(".");
+    path.push(compressor.extension());
+    path.into()
+}
+
+#[cfg(all(test, any(feature = "gzip", feature = "zstd")))]
+mod tests {
+    use std::{fs::File, io::Read};
+
+    use super::*;
+
+    fn round_trip(compressor: &dyn Compressor, decompress: impl Fn(File) -> Vec<u8>) {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("0.csv");
+        let content = "s,n\n".to_string() + &"a,0\n".repeat(1000);
+        std::fs::write(&src, &content).unwrap();
+        let dst = compressed_path(&src, compressor);
+        compressor.compress(&src, &dst).unwrap();
+        let compressed = File::open(&dst).unwrap();
+        assert!(compressed.metadata().unwrap().len() < content.len() as u64);
+        assert_eq!(decompress(compressed), content.as_bytes());
+        let mut decompressed = vec![];
+        let mut reader = compressor.decompress(&dst).unwrap();
+        reader.read_to_end(&mut decompressed).unwrap();
+        assert_eq!(decompressed, content.as_bytes());
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_gzip() {
+        round_trip(&Gzip::default(), |file| {
+            let mut buf = vec![];
+            flate2::read::GzDecoder::new(file)
+                .read_to_end(&mut buf)
+                .unwrap();
+            buf
+        });
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_zstd() {
+        round_trip(&Zstd::default(), |file| {
+            let mut buf = vec![];
+            zstd::Decoder::new(file)
+                .unwrap()
+                .read_to_end(&mut buf)
+                .unwrap();
+            buf
+        });
+    }
+}
@@ -0,0 +1,352 @@
+//! [`RotationPolicy`] as read from and written to configuration files.
+//!
+//! The clock trigger is a table tagged by its kind, e.g. in TOML:
+//!
+//! ```toml
+//! max_records = 10000
+//! time = { kind = "cron", expr = "0 3 * * *" }
+//! ```
+//!
+//! The kinds are those of [`TimeTrigger`]: `hourly`, `daily`, `monthly`, `every` with
+//! `minutes`, and `cron` with `expr`.
+
+use std::num::{NonZeroU64, NonZeroUsize};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cron::{Cron, CronParseError},
+    rotator::{Durability, FileNaming, PolicyError, RetentionOrder, RotationPolicy},
+    time_past::TimeTrigger,
+};
+
+/// The written form of a [`RotationPolicy`], with the same defaults.
+///
+/// Turning it into a policy checks it like [`crate::rotator::RotationPolicyBuilder::build`], so
+/// it needs a rotation trigger.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RotationConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_records: Option<NonZeroUsize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_bytes: Option<NonZeroU64>,
+    /// [`RotationPolicy::time`] or, for [`TimeTrigger::Cron`], [`RotationPolicy::cron`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time: Option<TimeTrigger>,
+    pub max_epochs: usize,
+    pub repair_on_startup: bool,
+    pub retention: Retention,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+    pub write_footer: bool,
+    pub write_metadata: bool,
+    pub naming: Naming,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_age: Option<jiff::Span>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_total_bytes: Option<NonZeroU64>,
+    pub resume: bool,
+    pub reopen_if_missing: bool,
+    pub current_link: bool,
+    pub durability: Durability,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extension: Option<String>,
+}
+impl Default for RotationConfig {
+    fn default() -> Self {
+        Self::try_from(&RotationPolicy::default()).unwrap()
+    }
+}
+
+/// [`RetentionOrder`] without the custom order.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Retention {
+    #[default]
+    Epoch,
+    Mtime,
+}
+
+/// [`FileNaming`] without the custom namer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Naming {
+    #[default]
+    Epoch,
+    EpochTimestamp,
+}
+
+impl TryFrom<RotationConfig> for RotationPolicy {
+    type Error = ConfigError;
+
+    fn try_from(config: RotationConfig) -> Result<Self, Self::Error> {
+        let mut builder = RotationPolicy::builder()
+            .max_epochs(config.max_epochs)
+            .repair_on_startup(config.repair_on_startup)
+            .retention(match config.retention {
+                Retention::Epoch => RetentionOrder::Epoch,
+                Retention::Mtime => RetentionOrder::Mtime,
+            })
+            .write_footer(config.write_footer)
+            .write_metadata(config.write_metadata)
+            .naming(match config.naming {
+                Naming::Epoch => FileNaming::Epoch,
+                Naming::EpochTimestamp => FileNaming::EpochTimestamp,
+            })
+            .resume(config.resume)
+            .reopen_if_missing(config.reopen_if_missing)
+            .current_link(config.current_link)
+            .durability(config.durability);
+        if let Some(max_records) = config.max_records {
+            builder = builder.max_records(max_records.get());
+        }
+        if let Some(max_bytes) = config.max_bytes {
+            builder = builder.max_bytes(max_bytes.get());
+        }
+        match config.time {
+            Some(TimeTrigger::Cron { expr }) => {
+                let cron = Cron::parse(&expr).map_err(|error| ConfigError::Cron { expr, error })?;
+                builder = builder.cron(cron);
+            }
+            Some(trigger) => builder = builder.time(trigger.time_past().unwrap()),
+            None => {}
+        }
+        if let Some(instance) = config.instance {
+            builder = builder.instance(instance);
+        }
+        if let Some(max_age) = config.max_age {
+            builder = builder.max_age(max_age);
+        }
+        if let Some(max_total_bytes) = config.max_total_bytes {
+            builder = builder.max_total_bytes(max_total_bytes.get());
+        }
+        if let Some(extension) = config.extension {
+            builder = builder.extension(extension);
+        }
+        builder.build().map_err(ConfigError::Policy)
+    }
+}
+
+impl TryFrom<&RotationPolicy> for RotationConfig {
+    type Error = ConfigError;
+
+    fn try_from(policy: &RotationPolicy) -> Result<Self, Self::Error> {
+        let unrepresentable = |part| Err(ConfigError::Unrepresentable(part));
+        if policy.compression.is_some() {
+            return unrepresentable("compression");
+        }
+        if policy.watermark.is_some() {
+            return unrepresentable("watermark");
+        }
+        if policy.clock.is_some() {
+            return unrepresentable("clock");
+        }
+        #[cfg(feature = "encryption")]
+        if policy.key_wrapper.is_some() {
+            return unrepresentable("key_wrapper");
+        }
+        let time = match (&policy.time, &policy.cron) {
+            (None, None) => None,
+            (Some(time), None) => match time.trigger() {
+                Some(trigger) => Some(trigger),
+                None => return unrepresentable("time"),
+            },
+            (None, Some(cron)) => match cron.expr() {
+                Some(expr) => Some(TimeTrigger::Cron {
+                    expr: expr.to_string(),
+                }),
+                None => return unrepresentable("cron"),
+            },
+            (Some(_), Some(_)) => return unrepresentable("time along with cron"),
+        };
+        let retention = match policy.retention {
+            RetentionOrder::Epoch => Retention::Epoch,
+            RetentionOrder::Mtime => Retention::Mtime,
+            RetentionOrder::Custom(_) => return unrepresentable("retention"),
+        };
+        let naming = match policy.naming {
+            FileNaming::Epoch => Naming::Epoch,
+            FileNaming::EpochTimestamp => Naming::EpochTimestamp,
+            FileNaming::Custom(_) => return unrepresentable("naming"),
+        };
+        Ok(Self {
+            max_records: policy.max_records,
+            max_bytes: policy.max_bytes,
+            time,
+            max_epochs: policy.max_epochs,
+            repair_on_startup: policy.repair_on_startup,
+            retention,
+            instance: policy.instance.clone(),
+            write_footer: policy.write_footer,
+            write_metadata: policy.write_metadata,
+            naming,
+            max_age: policy.max_age,
+            max_total_bytes: policy.max_total_bytes,
+            resume: policy.resume,
+            reopen_if_missing: policy.reopen_if_missing,
+            current_link: policy.current_link,
+            durability: policy.durability,
+            extension: policy.extension.clone(),
+        })
+    }
+}
+
+/// Fails for a policy with a part that has no written form, such as a compressor or a custom
+/// [`crate::time_past::TimeContains`].
+impl Serialize for RotationPolicy {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let config = RotationConfig::try_from(self).map_err(serde::ser::Error::custom)?;
+        config.serialize(serializer)
+    }
+}
+
+/// Goes through [`RotationConfig`].
+impl<'de> Deserialize<'de> for RotationPolicy {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let config = RotationConfig::deserialize(deserializer)?;
+        Self::try_from(config).map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    Cron {
+        expr: String,
+        error: CronParseError,
+    },
+    Policy(PolicyError),
+    /// The named part of the policy has no written form.
+    Unrepresentable(&'static str),
+}
+impl core::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Cron { expr, error } => write!(f, "Invalid cron expression `{expr}`: {error}"),
+            Self::Policy(e) => write!(f, "{e}"),
+            Self::Unrepresentable(part) => write!(f, "The {part} of the policy cannot be written"),
+        }
+    }
+}
+impl std::error::Error for ConfigError {}
+
+#[cfg(all(test, feature = "csv"))]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::{clock::MockClock, rotator::LogRotator, writers::csv::CsvLogWriter};
+
+    use super::*;
+
+    #[derive(Debug, Serialize)]
+    struct Record {
+        n: usize,
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let json = serde_json::json!({
+            "max_records": 3,
+            "max_epochs": 2,
+            "time": { "kind": "cron", "expr": "0 3 * * *" },
+            "durability": "fsync_on_rotate",
+            "max_age": "P7D",
+        });
+        let mut policy = serde_json::from_value::<RotationPolicy>(json.clone()).unwrap();
+        assert_eq!(policy.max_records, NonZeroUsize::new(3));
+        assert_eq!(policy.durability, Durability::FsyncOnRotate);
+        assert!(policy.time.is_none());
+        let written = serde_json::to_value(&policy).unwrap();
+        assert_eq!(written["time"], json["time"]);
+        assert_eq!(written["max_epochs"], 2);
+        assert_eq!(written["max_age"], "P7d");
+        serde_json::from_value::<RotationPolicy>(written).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let start: jiff::Zoned = "2024-01-01T02:59:00[UTC]".parse().unwrap();
+        let clock = Arc::new(MockClock::new(start));
+        policy.clock = Some(Arc::clone(&clock) as _);
+        let mut rotator = LogRotator::<CsvLogWriter>::new_or_panic(dir.path().to_owned(), policy);
+        rotator.writer().serialize(&Record { n: 0 }).unwrap();
+        rotator.try_incr_record_count().unwrap();
+        assert!(!rotator.try_rotate_file().unwrap());
+        clock.advance(jiff::SignedDuration::from_secs(60));
+        assert!(rotator.try_rotate_file().unwrap());
+        assert_eq!(rotator.metrics().epoch, 1);
+    }
+
+    #[test]
+    fn test_time_triggers() {
+        for (time, trigger) in [
+            (serde_json::json!({ "kind": "daily" }), TimeTrigger::Daily),
+            (
+                serde_json::json!({ "kind": "every", "minutes": 15 }),
+                TimeTrigger::Every {
+                    minutes: NonZeroU64::new(15).unwrap(),
+                },
+            ),
+        ] {
+            let json = serde_json::json!({ "time": time });
+            let policy = serde_json::from_value::<RotationPolicy>(json).unwrap();
+            assert_eq!(policy.time.as_ref().unwrap().trigger(), Some(trigger));
+            assert_eq!(serde_json::to_value(&policy).unwrap()["time"], time);
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = |json| {
+            serde_json::from_value::<RotationPolicy>(json)
+                .unwrap_err()
+                .to_string()
+        };
+        let unknown = err(serde_json::json!({ "time": { "kind": "weekly" } }));
+        assert!(unknown.contains("unknown variant `weekly`"), "{unknown}");
+        let cron = err(serde_json::json!({ "time": { "kind": "cron", "expr": "0 3 *" } }));
+        assert_eq!(
+            cron,
+            "Invalid cron expression `0 3 *`: Expected 5 cron fields, found 3"
+        );
+        let no_trigger = err(serde_json::json!({ "max_epochs": 3 }));
+        assert_eq!(no_trigger, "The policy never rotates");
+
+        let policy = RotationPolicy::builder()
+            .time(crate::time_past::TimePast::new(Arc::new(
+                crate::time_past::WeeklyContains::default(),
+            )))
+            .build()
+            .unwrap();
+        let written = serde_json::to_value(&policy).unwrap_err().to_string();
+        assert_eq!(written, "The time of the policy cannot be written");
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn test_key_wrapper_unrepresentable() {
+        #[derive(Debug)]
+        struct Identity;
+        impl crate::encryption::KeyWrapper for Identity {
+            fn wrap_key(&self, data_key: &[u8]) -> std::io::Result<Vec<u8>> {
+                Ok(data_key.to_vec())
+            }
+
+            fn unwrap_key(&self, wrapped_key: &[u8]) -> std::io::Result<Vec<u8>> {
+                Ok(wrapped_key.to_vec())
+            }
+        }
+
+        let policy = RotationPolicy::builder()
+            .max_records(2)
+            .key_wrapper(Arc::new(Identity))
+            .build()
+            .unwrap();
+        let written = serde_json::to_value(&policy).unwrap_err().to_string();
+        assert_eq!(written, "The key_wrapper of the policy cannot be written");
+    }
+}
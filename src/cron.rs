@@ -1,10 +1,21 @@
 use std::collections::BTreeSet;
 
+/// Fires once per minute whose fields are all allowed.
 #[derive(Debug, Clone)]
 pub struct Cron {
     slot_matcher: SlotMatcher<i16>,
+    /// A day matches on either its day of the month or its day of the week, as in crontab.
+    is_day_or: bool,
+    last_fired: Option<jiff::civil::DateTime>,
+    expr: Option<String>,
 }
 impl Cron {
+    /// Takes the allowed values of each field in the order of a cron expression.
+    ///
+    /// Fields are matched against the civil time of the polled instant, with the day of the week
+    /// in `1..=7` starting from Monday. For example, `minute` set to
+    /// `AllowedSet2::from_values([0, 30].into_iter())` and every other field to
+    /// [`AllowedSet2::Any`] fires on the hour and on the half hour.
     pub fn new(
         minute: AllowedSet2<i16>,
         hour: AllowedSet2<i16>,
@@ -13,96 +24,299 @@ impl Cron {
         day_of_week: AllowedSet2<i16>,
     ) -> Self {
         let slot_matcher = SlotMatcher::new(&[minute, hour, day_of_month, month, day_of_week]);
-        Self { slot_matcher }
+        Self {
+            slot_matcher,
+            is_day_or: false,
+            last_fired: None,
+            expr: None,
+        }
     }
 
-    pub fn edge_triggered_poll(&mut self, now: jiff::Zoned) -> bool {
-        let values = [
-            now.minute().into(),
-            now.hour().into(),
-            now.days_in_month().into(),
-            now.month().into(),
-            (now.weekday() as u8).into(),
-            now.year(),
-        ];
-        self.slot_matcher.edge_triggered_poll(&values)
+    /// Parses the five fields `minute hour day-of-month month day-of-week` of a cron expression.
+    ///
+    /// Each field is `*`, a value, a range `a-b` or a comma-separated list of them, each optionally
+    /// stepped with `/n`. A stepped value `a/n` runs up to the end of the field. The day of the week
+    /// is in `0..=7` starting from Sunday, which is both `0` and `7`. As in crontab, if neither the
+    /// day of the month nor the day of the week starts with `*`, a day matching either one fires.
+    pub fn parse(expr: &str) -> Result<Self, CronParseError> {
+        let fields = expr.split_whitespace().collect::<Vec<_>>();
+        let [minute, hour, day_of_month, month, day_of_week] = fields[..] else {
+            return Err(CronParseError::FieldCount(fields.len()));
+        };
+        let day_of_week_field = day_of_week;
+        let day_of_week = match parse_field(day_of_week, CronField::DayOfWeek)? {
+            AllowedSet2::Any => AllowedSet2::Any,
+            AllowedSet2::Selected(set) => {
+                // Sunday is 7 in `weekday_number`
+                let days = set.allowed.iter().map(|&d| if d == 0 { 7 } else { d });
+                AllowedSet2::from_values(days).unwrap()
+            }
+        };
+        let cron = Self::new(
+            parse_field(minute, CronField::Minute)?,
+            parse_field(hour, CronField::Hour)?,
+            parse_field(day_of_month, CronField::DayOfMonth)?,
+            parse_field(month, CronField::Month)?,
+            day_of_week,
+        );
+        Ok(Self {
+            is_day_or: !day_of_month.starts_with('*') && !day_of_week_field.starts_with('*'),
+            expr: Some(expr.to_string()),
+            ..cron
+        })
     }
-}
 
-#[derive(Debug, Clone)]
-pub struct SlotMatcher<T> {
-    cells: Vec<Cell<T>>,
-}
-impl<T> SlotMatcher<T>
-where
-    T: Copy + Ord,
-{
-    pub fn new(allowed: &[AllowedSet2<T>]) -> Self {
-        let extra = Cell::new(AllowedSet2::Any);
-        let cells = allowed
-            .iter()
-            .cloned()
-            .map(Cell::new)
-            .chain([extra])
-            .collect();
-        Self { cells }
+    /// The expression the schedule was parsed from, if it was.
+    pub fn expr(&self) -> Option<&str> {
+        self.expr.as_deref()
     }
 
-    pub fn edge_triggered_poll(&mut self, values: &[T]) -> bool {
-        assert_eq!(self.cells.len(), values.len());
-        let is_all_allowed = self
-            .cells
-            .iter()
-            .zip(values.iter())
-            .all(|(cell, &value)| cell.is_allowed(value));
-        if !is_all_allowed {
+    /// Returns `true` on the first poll inside a matching minute.
+    ///
+    /// Polls are edge-triggered on the civil minute, including its date, so the same minute of a
+    /// later day or year fires again while repeated polls within one minute do not.
+    pub fn edge_triggered_poll(&mut self, now: jiff::Zoned) -> bool {
+        let [minute, hour, ..] = &self.slot_matcher.allowed[..] else {
+            unreachable!("Cron has five fields");
+        };
+        let is_allowed = minute.is_allowed(now.minute().into())
+            && hour.is_allowed(now.hour().into())
+            && self.is_date_allowed(now.date());
+        if !is_allowed {
             return false;
         }
-        let has_all_set = self
-            .cells
-            .iter()
-            .zip(values.iter())
-            .all(|(cell, &value)| cell.has_set(value));
-        self.cells
-            .iter_mut()
-            .zip(values.iter())
-            .for_each(|(cell, &value)| cell.set(value));
-        if has_all_set {
+        let slot = now
+            .datetime()
+            .with()
+            .second(0)
+            .subsec_nanosecond(0)
+            .build()
+            .unwrap();
+        if self.last_fired == Some(slot) {
             return false;
         }
+        self.last_fired = Some(slot);
         true
     }
+
+    /// Start of the first matching minute after the minute of `now`, in the time zone of `now`.
+    ///
+    /// Searches the next 28 years, after which the calendar repeats, and returns [`None`] for a
+    /// schedule that never matches, e.g. on February 31st.
+    pub fn next_after(&self, now: jiff::Zoned) -> Option<jiff::Zoned> {
+        let [minute, hour, ..] = &self.slot_matcher.allowed[..] else {
+            unreachable!("Cron has five fields");
+        };
+        let start = now
+            .datetime()
+            .with()
+            .second(0)
+            .subsec_nanosecond(0)
+            .build()
+            .ok()?;
+        let start = start.checked_add(jiff::Span::new().minutes(1)).ok()?;
+        let mut date = start.date();
+        let end = date.checked_add(jiff::Span::new().years(28)).ok()?;
+        while date < end {
+            if self.is_date_allowed(date) {
+                let (mut from_hour, mut from_minute) = match date == start.date() {
+                    true => (start.hour().into(), start.minute().into()),
+                    false => (0, 0),
+                };
+                while let Some(h) = hour.first_at_or_after(from_hour, 23) {
+                    if h != from_hour {
+                        from_minute = 0;
+                    }
+                    if let Some(m) = minute.first_at_or_after(from_minute, 59) {
+                        let t = date
+                            .at(h as i8, m as i8, 0, 0)
+                            .to_zoned(now.time_zone().clone());
+                        // Skip a time repeated by a DST transition that is not after now.
+                        match t {
+                            Ok(t) if now < t => return Some(t),
+                            _ => {}
+                        }
+                        from_minute = m + 1;
+                        continue;
+                    }
+                    from_hour = h + 1;
+                    from_minute = 0;
+                }
+            }
+            date = date.tomorrow().ok()?;
+        }
+        None
+    }
+
+    fn is_date_allowed(&self, date: jiff::civil::Date) -> bool {
+        let [_, _, day_of_month, month, day_of_week] = &self.slot_matcher.allowed[..] else {
+            unreachable!("Cron has five fields");
+        };
+        let is_day_of_month_allowed = day_of_month.is_allowed(date.day().into());
+        let is_day_of_week_allowed = day_of_week.is_allowed(weekday_number(date.weekday()));
+        let is_day_allowed = match self.is_day_or {
+            true => is_day_of_month_allowed || is_day_of_week_allowed,
+            false => is_day_of_month_allowed && is_day_of_week_allowed,
+        };
+        is_day_allowed && month.is_allowed(date.month().into())
+    }
+}
+
+/// Numbers the day of the week as [`Cron::new`] takes it, `1..=7` from Monday as in ISO 8601.
+///
+/// [`Cron::parse`] reads the crontab `0..=7` from Sunday and renumbers Sunday `0` to `7`.
+pub fn weekday_number(weekday: jiff::civil::Weekday) -> i16 {
+    use jiff::civil::Weekday;
+    match weekday {
+        Weekday::Monday => 1,
+        Weekday::Tuesday => 2,
+        Weekday::Wednesday => 3,
+        Weekday::Thursday => 4,
+        Weekday::Friday => 5,
+        Weekday::Saturday => 6,
+        Weekday::Sunday => 7,
+    }
+}
+
+fn parse_field(field: &str, kind: CronField) -> Result<AllowedSet2<i16>, CronParseError> {
+    if field == "*" {
+        return Ok(AllowedSet2::Any);
+    }
+    let (min, max) = kind.range();
+    let invalid = || CronParseError::Invalid {
+        field: kind,
+        token: field.to_string(),
+    };
+    let value = |token: &str| -> Result<i16, CronParseError> {
+        let value: i16 = token.parse().map_err(|_| invalid())?;
+        if !(min..=max).contains(&value) {
+            return Err(CronParseError::OutOfRange {
+                field: kind,
+                value,
+                min,
+                max,
+            });
+        }
+        Ok(value)
+    };
+    let mut values = BTreeSet::new();
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => {
+                let step: i16 = step.parse().map_err(|_| invalid())?;
+                if step <= 0 {
+                    return Err(invalid());
+                }
+                (range, Some(step))
+            }
+            None => (item, None),
+        };
+        let (start, end) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((start, end)) => (value(start)?, value(end)?),
+            None if step.is_some() => (value(range)?, max),
+            None => {
+                let value = value(range)?;
+                (value, value)
+            }
+        };
+        if end < start {
+            return Err(invalid());
+        }
+        let step = step.unwrap_or(1) as usize;
+        values.extend((start..=end).step_by(step));
+    }
+    Ok(AllowedSet2::from_values(values.into_iter()).unwrap())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CronField {
+    Minute,
+    Hour,
+    DayOfMonth,
+    Month,
+    DayOfWeek,
+}
+impl CronField {
+    fn range(&self) -> (i16, i16) {
+        match self {
+            CronField::Minute => (0, 59),
+            CronField::Hour => (0, 23),
+            CronField::DayOfMonth => (1, 31),
+            CronField::Month => (1, 12),
+            CronField::DayOfWeek => (0, 7),
+        }
+    }
+}
+impl core::fmt::Display for CronField {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let name = match self {
+            CronField::Minute => "minute",
+            CronField::Hour => "hour",
+            CronField::DayOfMonth => "day of month",
+            CronField::Month => "month",
+            CronField::DayOfWeek => "day of week",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CronParseError {
+    /// The expression does not have exactly five fields.
+    FieldCount(usize),
+    /// A field is not made of values, ranges and positive steps.
+    Invalid { field: CronField, token: String },
+    OutOfRange {
+        field: CronField,
+        value: i16,
+        min: i16,
+        max: i16,
+    },
+}
+impl core::fmt::Display for CronParseError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            CronParseError::FieldCount(n) => write!(f, "Expected 5 cron fields, found {n}"),
+            CronParseError::Invalid { field, token } => {
+                write!(f, "Invalid {field} field `{token}`")
+            }
+            CronParseError::OutOfRange {
+                field,
+                value,
+                min,
+                max,
+            } => write!(f, "The {field} {value} is out of the range {min}-{max}"),
+        }
+    }
 }
+impl std::error::Error for CronParseError {}
 
 #[derive(Debug, Clone)]
-struct Cell<T> {
-    instr: AllowedSet2<T>,
-    prev: Option<T>,
+pub struct SlotMatcher<T> {
+    allowed: Vec<AllowedSet2<T>>,
 }
-impl<T> Cell<T>
+impl<T> SlotMatcher<T>
 where
     T: Copy + Ord,
 {
-    pub fn new(instr: AllowedSet2<T>) -> Self {
-        Self { instr, prev: None }
-    }
-
-    pub fn has_set(&self, value: T) -> bool {
-        self.prev == Some(value)
-    }
-    pub fn set(&mut self, value: T) {
-        self.prev = Some(value);
+    pub fn new(allowed: &[AllowedSet2<T>]) -> Self {
+        let allowed = allowed.to_vec();
+        Self { allowed }
     }
 
-    pub fn is_allowed(&self, value: T) -> bool {
-        self.instr.is_allowed(value)
+    pub fn is_allowed(&self, values: &[T]) -> bool {
+        assert_eq!(self.allowed.len(), values.len());
+        self.allowed
+            .iter()
+            .zip(values.iter())
+            .all(|(allowed, &value)| allowed.is_allowed(value))
     }
 }
 
 #[derive(Debug, Clone)]
 pub enum AllowedSet2<T> {
     Any,
-    #[allow(dead_code)]
     Selected(AllowedSet<T>),
 }
 impl<T> AllowedSet2<T>
@@ -116,7 +330,16 @@ where
         }
     }
 
-    pub fn from_iter(values: impl Iterator<Item = T>) -> Option<Self> {
+    /// Least allowed value in `value..=max`.
+    pub fn first_at_or_after(&self, value: T, max: T) -> Option<T> {
+        let first = match self {
+            AllowedSet2::Any => value,
+            AllowedSet2::Selected(allowed) => allowed.first_at_or_after(value)?,
+        };
+        (first <= max).then_some(first)
+    }
+
+    pub fn from_values(values: impl Iterator<Item = T>) -> Option<Self> {
         let allowed = BTreeSet::from_iter(values);
         let set = AllowedSet::new(allowed)?;
         Some(Self::Selected(set))
@@ -143,13 +366,247 @@ where
         self.allowed.binary_search(&value).is_ok()
     }
 
-    pub fn next(&self, value: T) -> T {
-        for allowed in self.allowed.windows(2) {
-            if allowed[0] != value {
-                continue;
+    /// Least allowed value not below `value`.
+    pub fn first_at_or_after(&self, value: T) -> Option<T> {
+        let i = self.allowed.partition_point(|&allowed| allowed < value);
+        self.allowed.get(i).copied()
+    }
+
+    /// Least allowed value above `value`, or the least allowed value along with `true` if it
+    /// wraps around, so that the next field up carries.
+    pub fn next(&self, value: T) -> (T, bool) {
+        let i = self.allowed.partition_point(|&allowed| allowed <= value);
+        match self.allowed.get(i) {
+            Some(&next) => (next, false),
+            None => (self.allowed[0], true),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_weekday_number() {
+        let monday = jiff::civil::date(2024, 1, 1);
+        for n in 0..7 {
+            let day = monday.checked_add(jiff::Span::new().days(n)).unwrap();
+            assert_eq!(weekday_number(day.weekday()), n as i16 + 1);
+        }
+        // Sunday is both `0` and `7` in crontab.
+        for expr in ["0 0 * * 0", "0 0 * * 7", "0 0 * * 6-7"] {
+            let mut cron = Cron::parse(expr).unwrap();
+            assert!(cron.edge_triggered_poll("2024-01-07T00:00[UTC]".parse().unwrap()));
+            assert!(!cron.edge_triggered_poll("2024-01-08T00:00[UTC]".parse().unwrap()));
+        }
+        let mut cron = Cron::parse("0 0 * * 1-5").unwrap();
+        assert!(cron.edge_triggered_poll("2024-01-05T00:00[UTC]".parse().unwrap()));
+        assert!(!cron.edge_triggered_poll("2024-01-06T00:00[UTC]".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_allowed_set_next() {
+        let set = AllowedSet::new(BTreeSet::from([10, 20, 30])).unwrap();
+        assert_eq!(set.next(10), (20, false));
+        assert_eq!(set.next(15), (20, false));
+        assert_eq!(set.next(0), (10, false));
+        assert_eq!(set.next(30), (10, true));
+        assert_eq!(set.next(35), (10, true));
+        let set = AllowedSet::new(BTreeSet::from([5])).unwrap();
+        assert_eq!(set.next(5), (5, true));
+        assert_eq!(set.next(4), (5, false));
+    }
+
+    #[test]
+    fn test_edge_triggered_poll() {
+        let at_noon = AllowedSet2::from_values([0].into_iter()).unwrap();
+        let hour = AllowedSet2::from_values([12].into_iter()).unwrap();
+        let any = || AllowedSet2::Any;
+        let mut cron = Cron::new(at_noon, hour, any(), any(), any());
+        let mut poll = |t: &str| cron.edge_triggered_poll(format!("{t}[UTC]").parse().unwrap());
+        assert!(!poll("2024-01-01T11:59:59"));
+        assert!(poll("2024-01-01T12:00:00"));
+        assert!(!poll("2024-01-01T12:00:30"));
+        assert!(!poll("2024-01-01T12:01:00"));
+        assert!(poll("2024-01-02T12:00:00"));
+        assert!(poll("2025-01-02T12:00:00"));
+    }
+
+    #[test]
+    fn test_day_of_month() {
+        let any = || AllowedSet2::Any;
+        let midnight = || AllowedSet2::from_values([0].into_iter()).unwrap();
+        let day = AllowedSet2::from_values([15, 31].into_iter()).unwrap();
+        let mut cron = Cron::new(midnight(), midnight(), day, any(), any());
+        let start: jiff::Zoned = "2024-04-01T00:00:00[UTC]".parse().unwrap();
+        let fired = (0..30)
+            .map(|d| start.checked_add(jiff::Span::new().days(d)).unwrap())
+            .filter(|t| cron.edge_triggered_poll(t.clone()))
+            .map(|t| t.day())
+            .collect::<Vec<_>>();
+        assert_eq!(fired, [15]);
+    }
+
+    #[test]
+    fn test_selected_minutes() {
+        let minute = AllowedSet2::from_values([0, 30].into_iter()).unwrap();
+        let any = || AllowedSet2::Any;
+        let mut cron = Cron::new(minute, any(), any(), any(), any());
+        let start: jiff::Zoned = "2024-01-01T12:00:00[UTC]".parse().unwrap();
+        let fired = (0..360)
+            .map(|i| {
+                start
+                    .checked_add(jiff::Span::new().seconds(i * 10))
+                    .unwrap()
+            })
+            .filter(|t| cron.edge_triggered_poll(t.clone()))
+            .map(|t| t.minute())
+            .collect::<Vec<_>>();
+        assert_eq!(fired, [0, 30]);
+    }
+
+    #[test]
+    fn test_yearly() {
+        let first = || AllowedSet2::from_values([1].into_iter()).unwrap();
+        let zero = || AllowedSet2::from_values([0].into_iter()).unwrap();
+        let mut cron = Cron::new(zero(), zero(), first(), first(), AllowedSet2::Any);
+        let mut poll = |t: &str| cron.edge_triggered_poll(format!("{t}[UTC]").parse().unwrap());
+        assert!(poll("2024-01-01T00:00:00"));
+        assert!(!poll("2024-02-01T00:00:00"));
+        assert!(poll("2025-01-01T00:00:00"));
+        assert!(poll("2026-01-01T00:00:59"));
+    }
+
+    #[test]
+    fn test_parse() {
+        let mut cron = Cron::parse("*/20 0 * * 1-5").unwrap();
+        let mut poll = |t: &str| cron.edge_triggered_poll(format!("{t}[UTC]").parse().unwrap());
+        // Monday
+        assert!(poll("2024-01-01T00:00:00"));
+        assert!(!poll("2024-01-01T00:10:00"));
+        assert!(poll("2024-01-01T00:20:00"));
+        assert!(poll("2024-01-01T00:40:00"));
+        assert!(!poll("2024-01-01T01:00:00"));
+        // Saturday
+        assert!(!poll("2024-01-06T00:00:00"));
+
+        let mut cron = Cron::parse("10-50/20,5 12 1,15 2 0").unwrap();
+        let mut poll = |t: &str| cron.edge_triggered_poll(format!("{t}[UTC]").parse().unwrap());
+        // Sunday
+        assert!(poll("2026-02-15T12:05:00"));
+        assert!(poll("2026-02-15T12:10:00"));
+        assert!(!poll("2026-02-15T12:20:00"));
+        assert!(poll("2026-02-15T12:50:00"));
+        // Any Sunday, as well as the 1st and the 15th.
+        assert!(poll("2026-02-08T12:10:00"));
+        assert!(!poll("2026-02-09T12:10:00"));
+    }
+
+    #[test]
+    fn test_day_of_month_or_week() {
+        let fired = |expr: &str| {
+            let mut cron = Cron::parse(expr).unwrap();
+            let start: jiff::Zoned = "2024-09-01T00:00:00[UTC]".parse().unwrap();
+            (0..30)
+                .map(|d| start.checked_add(jiff::Span::new().days(d)).unwrap())
+                .filter(|t| cron.edge_triggered_poll(t.clone()))
+                .map(|t| t.day())
+                .collect::<Vec<_>>()
+        };
+        // Fridays and the 13th.
+        assert_eq!(fired("0 0 13 * 5"), [6, 13, 20, 27]);
+        assert_eq!(fired("0 0 10 * 5"), [6, 10, 13, 20, 27]);
+        // A field starting with `*` leaves the other one alone.
+        assert_eq!(fired("0 0 */10 * 3"), [11]);
+        assert_eq!(fired("0 0 10 * *"), [10]);
+        let next = Cron::parse("0 0 10 * 5")
+            .unwrap()
+            .next_after("2024-09-06T00:00:00[UTC]".parse().unwrap())
+            .unwrap();
+        assert_eq!(next.day(), 10);
+    }
+
+    #[test]
+    fn test_parse_error() {
+        assert_eq!(
+            Cron::parse("* * * *").unwrap_err(),
+            CronParseError::FieldCount(4)
+        );
+        assert_eq!(
+            Cron::parse("60 * * * *").unwrap_err(),
+            CronParseError::OutOfRange {
+                field: CronField::Minute,
+                value: 60,
+                min: 0,
+                max: 59
             }
-            return allowed[1];
+        );
+        assert_eq!(
+            Cron::parse("* * 0 * *").unwrap_err().to_string(),
+            "The day of month 0 is out of the range 1-31"
+        );
+        assert_eq!(
+            Cron::parse("* * * * 8").unwrap_err().to_string(),
+            "The day of week 8 is out of the range 0-7"
+        );
+        for expr in ["*/0 * * * *", "5-1 * * * *", "a * * * *", "* * * 1, *"] {
+            assert!(matches!(
+                Cron::parse(expr).unwrap_err(),
+                CronParseError::Invalid { .. }
+            ));
         }
-        self.allowed[0]
+    }
+
+    #[test]
+    fn test_next_after() {
+        let next = |expr: &str, t: &str| {
+            let now: jiff::Zoned = format!("{t}[UTC]").parse().unwrap();
+            let next = Cron::parse(expr).unwrap().next_after(now)?;
+            Some(next.datetime().to_string())
+        };
+        let next = |expr, t| next(expr, t).unwrap_or_default();
+        assert_eq!(
+            next("*/20 * * * *", "2024-01-01T10:05:30"),
+            "2024-01-01T10:20:00"
+        );
+        assert_eq!(
+            next("*/20 * * * *", "2024-01-01T10:20:00"),
+            "2024-01-01T10:40:00"
+        );
+        assert_eq!(
+            next("*/20 * * * *", "2024-01-01T10:40:00"),
+            "2024-01-01T11:00:00"
+        );
+        assert_eq!(
+            next("50 3,15 * * *", "2024-01-01T03:50:00"),
+            "2024-01-01T15:50:00"
+        );
+        assert_eq!(
+            next("50 3,15 * * *", "2024-01-01T23:59:59"),
+            "2024-01-02T03:50:00"
+        );
+        assert_eq!(
+            next("0 0 1 * *", "2024-01-31T12:00:00"),
+            "2024-02-01T00:00:00"
+        );
+        assert_eq!(
+            next("0 0 31 * *", "2024-04-15T00:00:00"),
+            "2024-05-31T00:00:00"
+        );
+        assert_eq!(
+            next("0 0 1 1 *", "2024-12-31T23:59:00"),
+            "2025-01-01T00:00:00"
+        );
+        assert_eq!(
+            next("0 0 29 2 *", "2024-03-01T00:00:00"),
+            "2028-02-29T00:00:00"
+        );
+        // Monday
+        assert_eq!(
+            next("0 12 * * 1", "2024-01-01T12:00:00"),
+            "2024-01-08T12:00:00"
+        );
+        assert_eq!(next("0 0 31 2 *", "2024-01-01T00:00:00"), "");
     }
 }
@@ -1,90 +1,979 @@
 use std::{
     collections::HashMap,
-    path::PathBuf,
-    sync::{Arc, Mutex},
-    time::Duration,
+    num::NonZeroU64,
+    ops::{Deref, DerefMut},
+    path::{Component, Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Condvar, Mutex, MutexGuard,
+    },
+    time::{Duration, Instant},
 };
 
 use crate::{
-    rotator::{LogRotator, RotationPolicy},
+    clock::Clock,
+    cron::Cron,
+    flusher::{AdaptiveInterval, Backoff, FlushIntervalError, FlusherHandle},
+    metrics::{AdmissionMetrics, DistributorMetrics},
+    rate_limit::{Admission, RateLimit, RateLimited, TableAdmission},
+    rotator::{
+        create_dir, BuildError, CompressionGate, LogRotator, RotationPolicy, RotationPolicyBuilder,
+        RotatorError, RotatorOp, DEFAULT_FLUSH_INTERVAL,
+    },
+    time_past::TimePast,
     LogWriter,
 };
 
-pub fn spawn_flusher<W>(distributor: Arc<Mutex<LogDistributor<W>>>, flush_interval: Duration)
+/// Intervals outside of [`MIN_FLUSH_INTERVAL`]`..=`[`MAX_FLUSH_INTERVAL`] are clamped, as told by
+/// [`FlusherHandle::clamped`]; see [`try_spawn_flusher`] to reject them instead.
+///
+/// [`MIN_FLUSH_INTERVAL`]: crate::flusher::MIN_FLUSH_INTERVAL
+/// [`MAX_FLUSH_INTERVAL`]: crate::flusher::MAX_FLUSH_INTERVAL
+pub fn spawn_flusher<W>(
+    distributor: Arc<Mutex<LogDistributor<W>>>,
+    flush_interval: Duration,
+) -> FlusherHandle
 where
     W: LogWriter + Sync + Send + 'static,
 {
-    std::thread::Builder::new()
+    spawn_flusher_adaptive(distributor, AdaptiveInterval::fixed(flush_interval))
+}
+
+pub fn try_spawn_flusher<W>(
+    distributor: Arc<Mutex<LogDistributor<W>>>,
+    flush_interval: Duration,
+) -> Result<FlusherHandle, FlushIntervalError>
+where
+    W: LogWriter + Sync + Send + 'static,
+{
+    try_spawn_flusher_adaptive(distributor, AdaptiveInterval::fixed(flush_interval))
+}
+
+pub fn try_spawn_flusher_adaptive<W>(
+    distributor: Arc<Mutex<LogDistributor<W>>>,
+    flush_interval: AdaptiveInterval,
+) -> Result<FlusherHandle, FlushIntervalError>
+where
+    W: LogWriter + Sync + Send + 'static,
+{
+    flush_interval.validate()?;
+    Ok(spawn_flusher_adaptive(distributor, flush_interval))
+}
+
+/// Like [`spawn_flusher`] but backs off while none of the tables is dirty.
+pub fn spawn_flusher_adaptive<W>(
+    distributor: Arc<Mutex<LogDistributor<W>>>,
+    flush_interval: AdaptiveInterval,
+) -> FlusherHandle
+where
+    W: LogWriter + Sync + Send + 'static,
+{
+    let shutdown = Arc::new(AtomicBool::new(false));
+    let polls_time = distributor.lock().unwrap().rotation.has_time_trigger();
+    let flusher = std::thread::Builder::new()
         .name("LogDistributor::flush()".to_string())
         .spawn({
             let distributor = Arc::downgrade(&distributor);
+            let mut backoff = Backoff::new(flush_interval);
+            backoff.set_polls_time(polls_time);
+            let shutdown = Arc::clone(&shutdown);
             move || loop {
-                std::thread::sleep(flush_interval);
+                std::thread::park_timeout(backoff.timeout());
+                let is_shut_down = shutdown.load(Ordering::Relaxed);
+                let flush = is_shut_down || backoff.is_flush_due();
                 let Some(distributor) = distributor.upgrade() else {
                     return;
                 };
-                distributor.lock().unwrap().flush();
+                let mut distributor = distributor.lock().unwrap();
+                let is_dirty = flush_and_rotate(&mut distributor, flush);
+                if let (true, Some(is_dirty)) = (flush, is_dirty) {
+                    backoff.tick(is_dirty);
+                }
+                if is_shut_down {
+                    return;
+                }
             }
         })
         .expect("Failed to spawn the flushing worker thread");
+    let mut distributor = distributor.lock().unwrap();
+    distributor.flushers.push(flusher.thread().clone());
+    FlusherHandle::new(shutdown, flusher, &flush_interval)
+}
+
+/// Like [`spawn_flusher`] but runs as a Tokio task until `cancel` is cancelled or the distributor
+/// is dropped.
+///
+/// Flushing and rotation block on file IO, so they run through
+/// [`tokio::task::spawn_blocking`].
+///
+/// The interval is clamped like that of [`spawn_flusher`] but without telling; check it with
+/// [`AdaptiveInterval::validate`] beforehand.
+#[cfg(feature = "tokio")]
+pub fn spawn_flusher_async<W>(
+    distributor: Arc<Mutex<LogDistributor<W>>>,
+    flush_interval: Duration,
+    cancel: tokio_util::sync::CancellationToken,
+) -> tokio::task::JoinHandle<()>
+where
+    W: LogWriter + Sync + Send + 'static,
+{
+    let flush_interval = AdaptiveInterval::fixed(flush_interval).clamped().base;
+    let distributor = Arc::downgrade(&distributor);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(flush_interval);
+        interval.tick().await;
+        loop {
+            tokio::select! {
+                _ = cancel.cancelled() => return,
+                _ = interval.tick() => (),
+            }
+            let distributor = distributor.clone();
+            let is_alive = tokio::task::spawn_blocking(move || {
+                let Some(distributor) = distributor.upgrade() else {
+                    return false;
+                };
+                flush_and_rotate(&mut distributor.lock().unwrap(), true);
+                true
+            })
+            .await
+            .expect("The flushing task panicked");
+            if !is_alive {
+                return;
+            }
+        }
+    })
+}
+
+/// Flushes, if `flush`, and rotates the tables unless quiesced, telling if any of them was dirty.
+fn flush_and_rotate<W>(distributor: &mut LogDistributor<W>, flush: bool) -> Option<bool>
+where
+    W: LogWriter,
+{
+    if distributor.is_quiesced() {
+        return None;
+    }
+    let is_dirty = distributor.is_dirty();
+    for rotator in distributor.rotators.values() {
+        rotator.lock().unwrap().flusher_tick(flush);
+    }
+    Some(is_dirty)
+}
+
+/// Like [`crate::rotator::rotate_on_sighup`] but rotates every table of the distributor.
+///
+/// A signal that finds the distributor quiesced for longer than its [`QuiesceMode`] allows is
+/// skipped.
+#[cfg(all(unix, feature = "signal"))]
+pub fn rotate_on_sighup<W>(
+    distributor: Arc<Mutex<LogDistributor<W>>>,
+    skip_empty: bool,
+) -> std::io::Result<crate::signal::SighupHandle>
+where
+    W: LogWriter + Send + 'static,
+{
+    let distributor = Arc::downgrade(&distributor);
+    crate::signal::register(Box::new(move || {
+        let Some(distributor) = distributor.upgrade() else {
+            return;
+        };
+        let distributor = distributor.lock().unwrap();
+        // Skipped where a write would fail.
+        if distributor.quiesce.wait(distributor.quiesce_mode).is_err() {
+            return;
+        }
+        for rotator in distributor.rotators.values() {
+            let mut rotator = rotator.lock().unwrap();
+            if let Err(e) = rotator.rotate_now(skip_empty) {
+                rotator.defer_error(e);
+            }
+        }
+    }))
+}
+
+type SharedDistributor<W> = Arc<Mutex<LogDistributor<W>>>;
+
+/// Like [`crate::rotator::LogRotatorBuilder`] for a distributor.
+#[derive(Debug)]
+pub struct LogDistributorBuilder<W> {
+    output_dirs: Vec<PathBuf>,
+    policy: RotationPolicyBuilder,
+    flush_interval: Duration,
+    _writer: std::marker::PhantomData<fn() -> W>,
+}
+impl<W> LogDistributorBuilder<W>
+where
+    W: LogWriter + Sync + Send + 'static,
+{
+    /// Also spreads the tables over `output_dir`, like [`LogDistributor::sharded`].
+    pub fn shard(mut self, output_dir: impl Into<PathBuf>) -> Self {
+        self.output_dirs.push(output_dir.into());
+        self
+    }
+
+    pub fn max_records(self, max_records: usize) -> Self {
+        self.policy(|p| p.max_records(max_records))
+    }
+
+    pub fn max_bytes(self, max_bytes: u64) -> Self {
+        self.policy(|p| p.max_bytes(max_bytes))
+    }
+
+    pub fn time(self, time: TimePast) -> Self {
+        self.policy(|p| p.time(time))
+    }
+
+    /// Like [`RotationPolicyBuilder::daily`].
+    pub fn daily(self) -> Self {
+        self.policy(RotationPolicyBuilder::daily)
+    }
+
+    pub fn cron(self, cron: Cron) -> Self {
+        self.policy(|p| p.cron(cron))
+    }
+
+    pub fn max_epochs(self, max_epochs: usize) -> Self {
+        self.policy(|p| p.max_epochs(max_epochs))
+    }
+
+    /// Like [`crate::rotator::LogRotatorBuilder::clock`].
+    pub fn clock(self, clock: Arc<dyn Clock>) -> Self {
+        self.policy(|p| p.clock(clock))
+    }
+
+    /// Sets the rest of the policy of every table.
+    pub fn policy(
+        mut self,
+        f: impl FnOnce(RotationPolicyBuilder) -> RotationPolicyBuilder,
+    ) -> Self {
+        self.policy = f(self.policy);
+        self
+    }
+
+    /// [`DEFAULT_FLUSH_INTERVAL`] unless set.
+    pub fn flush_every(mut self, flush_interval: Duration) -> Self {
+        self.flush_interval = flush_interval;
+        self
+    }
+
+    /// Checks the policy and the flush interval and creates the output directories.
+    pub fn build(self) -> Result<(SharedDistributor<W>, FlusherHandle), BuildError> {
+        let rotation = self.policy.build()?;
+        let flush_interval = AdaptiveInterval::fixed(self.flush_interval);
+        flush_interval.validate()?;
+        for output_dir in &self.output_dirs {
+            create_dir(output_dir)?;
+        }
+        let distributor = LogDistributor::sharded(self.output_dirs, rotation);
+        let distributor = Arc::new(Mutex::new(distributor));
+        let flusher = spawn_flusher_adaptive(Arc::clone(&distributor), flush_interval);
+        Ok((distributor, flusher))
+    }
 }
 
+/// Flushes every table on drop.
 #[derive(Debug)]
-pub struct LogDistributor<W> {
-    output_dir: PathBuf,
-    rotators: HashMap<&'static str, LogRotator<W>>,
+pub struct LogDistributor<W>
+where
+    W: LogWriter,
+{
+    /// Shards the tables are spread over.
+    output_dirs: Vec<PathBuf>,
+    rotators: HashMap<String, Arc<Mutex<LogRotator<W>>>>,
     rotation: RotationPolicy,
+    quiesce: Arc<QuiesceState>,
+    quiesce_mode: QuiesceMode,
+    compression_gate: Arc<CompressionGate>,
+    admission: HashMap<String, TableAdmission>,
+    /// Flusher threads to wake up on drop.
+    flushers: Vec<std::thread::Thread>,
 }
-impl<W> LogDistributor<W> {
+impl<W> LogDistributor<W>
+where
+    W: LogWriter,
+{
     pub fn new(output_dir: PathBuf, rotation: RotationPolicy) -> Self {
+        Self::sharded(vec![output_dir], rotation)
+    }
+
+    /// Spreads the tables over `output_dirs`, e.g. on different disks, by a hash of their names
+    /// that is stable across runs and builds.
+    ///
+    /// The rotator of each table only looks at its own subdirectory, so discovery and retention
+    /// stay within the shard. Reordering or resizing `output_dirs` moves tables to other shards
+    /// and their previous files out of reach.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `output_dirs` is empty.
+    pub fn sharded(output_dirs: Vec<PathBuf>, rotation: RotationPolicy) -> Self {
+        assert!(!output_dirs.is_empty(), "No output directory");
         Self {
-            output_dir,
+            output_dirs,
             rotators: HashMap::new(),
             rotation,
+            quiesce: Arc::new(QuiesceState::default()),
+            quiesce_mode: QuiesceMode::default(),
+            compression_gate: Arc::default(),
+            admission: HashMap::new(),
+            flushers: vec![],
+        }
+    }
+
+    /// Builds a distributor along with its flusher, starting from [`RotationPolicy::builder`].
+    pub fn builder(output_dir: impl Into<PathBuf>) -> LogDistributorBuilder<W> {
+        LogDistributorBuilder {
+            output_dirs: vec![output_dir.into()],
+            policy: RotationPolicy::builder(),
+            flush_interval: DEFAULT_FLUSH_INTERVAL,
+            _writer: std::marker::PhantomData,
+        }
+    }
+
+    /// Limits the records written through [`TableHandle::write`].
+    pub fn set_rate_limit(&mut self, table_name: &str, rate_limit: Option<RateLimit>) {
+        self.admission
+            .entry(table_name.to_string())
+            .or_default()
+            .set_rate_limit(rate_limit);
+    }
+
+    /// Keeps only every `keep_one_in`-th record written through [`TableHandle::write`].
+    pub fn set_sampling(&mut self, table_name: &str, keep_one_in: Option<NonZeroU64>) {
+        self.admission
+            .entry(table_name.to_string())
+            .or_default()
+            .set_sampling(keep_one_in);
+    }
+
+    /// Applies the sampling and rate limit of the table to a record, counting the discarded ones
+    /// in the table's next [`crate::rotator::RotationEvent`].
+    fn admit(&mut self, table_name: &str) -> Admission {
+        let Some(admission) = self.admission.get_mut(table_name) else {
+            return Admission::Admit;
+        };
+        let admission = admission.admit();
+        if let Some(rotator) = self.rotators.get(table_name) {
+            match admission {
+                Admission::Drop => rotator.lock().unwrap().incr_dropped_count(),
+                Admission::SampleOut => rotator.lock().unwrap().incr_sampled_out_count(),
+                _ => (),
+            }
         }
+        admission
+    }
+
+    /// Like [`TableHandle::write`] but sleeps out [`crate::rate_limit::Overflow::Block`] with
+    /// `distributor` unlocked, so that other writers can get through.
+    pub fn write_blocking<T>(
+        distributor: &Mutex<Self>,
+        table_name: &str,
+        write: impl FnOnce(&mut W) -> T,
+    ) -> Result<Option<T>, TableError> {
+        loop {
+            let mut distributor = distributor.lock().unwrap();
+            let admission = distributor.admit(table_name);
+            if let Admission::Wait(wait) = admission {
+                drop(distributor);
+                std::thread::sleep(wait);
+                continue;
+            }
+            let mut handle = distributor.try_table_handle(table_name)?;
+            return handle.write_admitted(admission, write);
+        }
+    }
+
+    pub fn admission_metrics(&self, table_name: &str) -> AdmissionMetrics {
+        self.admission
+            .get(table_name)
+            .map(|a| a.metrics())
+            .unwrap_or_default()
+    }
+
+    pub fn set_quiesce_mode(&mut self, mode: QuiesceMode) {
+        self.quiesce_mode = mode;
+    }
+
+    pub fn is_quiesced(&self) -> bool {
+        *self.quiesce.depth.lock().unwrap() != 0
     }
 }
 impl<W> LogDistributor<W>
 where
     W: LogWriter,
 {
-    pub fn flush(&mut self) {
-        self.rotators.iter_mut().for_each(|(_, t)| {
-            t.flush();
-        });
+    /// Flushes every table even if some fail, returning the first failure.
+    pub fn flush(&mut self) -> Result<(), RotatorError> {
+        let mut res = Ok(());
+        for t in self.rotators.values() {
+            let flushed = t.lock().unwrap().flush();
+            if res.is_ok() {
+                res = flushed;
+            }
+        }
+        res
+    }
+
+    pub fn flush_or_panic(&mut self) {
+        self.flush().expect("Failed to flush the log files");
+    }
+
+    /// Gives every table a chance to rotate even if some fail, returning the first failure.
+    ///
+    /// See [`LogRotator::try_rotate_file`]. Waits out or fails on a quiesced distributor like
+    /// [`Self::try_writer`].
+    pub fn try_rotate(&mut self) -> Result<(), TableError> {
+        self.try_rotate_at(self.rotation.now())
+    }
+
+    /// Like [`Self::try_rotate`] but polls the time trigger at `now`.
+    pub fn try_rotate_at(&mut self, now: jiff::Zoned) -> Result<(), TableError> {
+        self.quiesce.wait(self.quiesce_mode)?;
+        let mut res = Ok(());
+        for t in self.rotators.values() {
+            let rotated = t.lock().unwrap().try_rotate_file_at(now.clone());
+            if res.is_ok() {
+                res = rotated.map(drop);
+            }
+        }
+        Ok(res?)
+    }
+
+    /// Rotates every table regardless of the triggers even if some fail, returning the first
+    /// failure.
+    ///
+    /// See [`LogRotator::rotate_now`]. Waits out or fails on a quiesced distributor like
+    /// [`Self::try_writer`].
+    pub fn rotate_now(&mut self, skip_empty: bool) -> Result<(), TableError> {
+        self.quiesce.wait(self.quiesce_mode)?;
+        let mut res = Ok(());
+        for t in self.rotators.values() {
+            let rotated = t.lock().unwrap().rotate_now(skip_empty);
+            if res.is_ok() {
+                res = rotated.map(drop);
+            }
+        }
+        Ok(res?)
+    }
+
+    /// Like [`Self::rotate_now`] but for one table, if it exists, telling if it rotated.
+    pub fn rotate_table_now(
+        &mut self,
+        table_name: &str,
+        skip_empty: bool,
+    ) -> Result<bool, TableError> {
+        self.quiesce.wait(self.quiesce_mode)?;
+        match self.rotators.get(table_name) {
+            Some(t) => Ok(t.lock().unwrap().rotate_now(skip_empty)?),
+            None => Ok(false),
+        }
+    }
+
+    /// Shuts down every table even if some fail, returning the first failure.
+    ///
+    /// See [`LogRotator::shutdown`]. Tables still shared through [`Self::table_rotator`] are only
+    /// flushed. Waits out or fails on a quiesced distributor like [`Self::try_writer`].
+    pub fn shutdown(mut self, finalize: bool) -> Result<(), TableError> {
+        self.quiesce.wait(self.quiesce_mode)?;
+        let mut res = Ok(());
+        for (_, rotator) in std::mem::take(&mut self.rotators) {
+            let r = match Arc::try_unwrap(rotator) {
+                Ok(rotator) => rotator.into_inner().unwrap().shutdown(finalize),
+                Err(rotator) => rotator.lock().unwrap().flush(),
+            };
+            if res.is_ok() {
+                res = r;
+            }
+        }
+        Ok(res?)
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.rotators.values().any(|t| t.lock().unwrap().is_dirty())
+    }
+
+    /// Flushes every table, waits for the background compressions under way, and holds off
+    /// writes, rotations, closing tables, background flushes, and compressions until the guard
+    /// drops.
+    ///
+    /// Guards nest: the distributor resumes once all of them are dropped. Dropping a guard does
+    /// not need the distributor, so it can happen while a writer blocks inside [`Self::writer`].
+    /// Dropping a table with a retired file left to compress waits for the guards, as does
+    /// [`LogRotator::wait_for_compression`].
+    ///
+    /// Fails without quiescing if a table cannot be flushed.
+    pub fn quiesce(&mut self) -> Result<QuiesceGuard, RotatorError> {
+        self.flush()?;
+        *self.quiesce.depth.lock().unwrap() += 1;
+        self.compression_gate.close();
+        Ok(QuiesceGuard {
+            state: Arc::clone(&self.quiesce),
+            compression_gate: Arc::clone(&self.compression_gate),
+        })
     }
 }
 impl<W> LogDistributor<W>
 where
     W: LogWriter,
 {
-    pub fn writer(&mut self, table_name: &'static str) -> &mut W {
-        let entry = self.rotators.entry(table_name);
-        let table = match entry {
-            std::collections::hash_map::Entry::Occupied(entry) => entry.into_mut(),
-            std::collections::hash_map::Entry::Vacant(entry) => {
-                let output_dir = self.output_dir.join(table_name);
-                let table = entry.insert(LogRotator::new(output_dir, self.rotation.clone()));
-                table
+    /// # Panics
+    ///
+    /// Panics if the distributor stays quiesced or the table cannot be set up; see
+    /// [`Self::try_writer`].
+    pub fn writer(&mut self, table_name: &str) -> TableWriter<'_, W> {
+        self.try_writer(table_name)
+            .expect("Failed to get the table writer")
+    }
+
+    /// Waits out or fails on a quiesced distributor depending on the [`QuiesceMode`].
+    pub fn try_writer(&mut self, table_name: &str) -> Result<TableWriter<'_, W>, TableError> {
+        let rotator = self.rotator(table_name)?;
+        Ok(TableWriter { rotator })
+    }
+
+    /// # Panics
+    ///
+    /// Panics if the distributor stays quiesced or the table cannot be set up; see
+    /// [`Self::try_table_rotator`].
+    pub fn table_rotator(&mut self, table_name: &str) -> Arc<Mutex<LogRotator<W>>> {
+        self.try_table_rotator(table_name)
+            .expect("Failed to get the table rotator")
+    }
+
+    /// The rotator of the table, created if needed, to be locked without the distributor.
+    ///
+    /// Waits out or fails on a quiesced distributor like [`Self::try_writer`], but later writes
+    /// through the rotator bypass quiescing, except for compression, and the table's admission.
+    pub fn try_table_rotator(
+        &mut self,
+        table_name: &str,
+    ) -> Result<Arc<Mutex<LogRotator<W>>>, TableError> {
+        self.quiesce.wait(self.quiesce_mode)?;
+        Ok(Arc::clone(self.rotator_entry(table_name)?))
+    }
+
+    /// # Panics
+    ///
+    /// Panics if the distributor stays quiesced or the table cannot be set up; see
+    /// [`Self::try_table_handle`].
+    pub fn table_handle<'a>(&'a mut self, table_name: &'a str) -> TableHandle<'a, W> {
+        self.try_table_handle(table_name)
+            .expect("Failed to get the table handle")
+    }
+
+    pub fn try_table_handle<'a>(
+        &'a mut self,
+        table_name: &'a str,
+    ) -> Result<TableHandle<'a, W>, TableError> {
+        drop(self.rotator(table_name)?);
+        Ok(TableHandle {
+            distributor: self,
+            table_name,
+        })
+    }
+
+    fn rotator(&mut self, table_name: &str) -> Result<MutexGuard<'_, LogRotator<W>>, TableError> {
+        self.quiesce.wait(self.quiesce_mode)?;
+        Ok(self.rotator_entry(table_name)?.lock().unwrap())
+    }
+
+    fn rotator_entry(
+        &mut self,
+        table_name: &str,
+    ) -> Result<&Arc<Mutex<LogRotator<W>>>, TableError> {
+        if !self.rotators.contains_key(table_name) {
+            let output_dir = table_dir(self.shard_dir(table_name), table_name)?;
+            let mut table = LogRotator::new(output_dir, self.rotation.clone())?;
+            table.set_compression_gate(Arc::clone(&self.compression_gate));
+            let table = Arc::new(Mutex::new(table));
+            self.rotators.insert(table_name.to_string(), table);
+        }
+        Ok(&self.rotators[table_name])
+    }
+
+    /// The output directory holding the subdirectory of the table.
+    pub fn shard_dir(&self, table_name: &str) -> &Path {
+        // FNV-1a, unlike the std hashers, is fixed across runs.
+        let hash = table_name
+            .bytes()
+            .fold(0xcbf2_9ce4_8422_2325_u64, |hash, b| {
+                (hash ^ u64::from(b)).wrapping_mul(0x0000_0100_0000_01b3)
+            });
+        let shard = hash % self.output_dirs.len() as u64;
+        &self.output_dirs[shard as usize]
+    }
+
+    pub fn table_names(&self) -> impl Iterator<Item = &str> {
+        self.rotators.keys().map(String::as_str)
+    }
+
+    /// See [`LogRotator::current_file_bytes`].
+    pub fn current_file_bytes(&self, table_name: &str) -> Option<u64> {
+        let mut rotator = self.rotators.get(table_name)?.lock().unwrap();
+        Some(rotator.current_file_bytes())
+    }
+
+    /// Unlike [`Self::metrics`], leaves the filesystem alone.
+    pub fn stats(&self, table_name: &str) -> Option<TableStats> {
+        let rotator = self.rotators.get(table_name)?.lock().unwrap();
+        Some(TableStats {
+            epoch: rotator.epoch(),
+            records_written: rotator.records_written(),
+            aux_records: rotator.aux_records(),
+            aux_records_total: rotator.lifetime_aux_records(),
+            path: rotator.current_path().to_owned(),
+        })
+    }
+
+    /// Flushes and drops the rotator of the table to release its file, and tells if the table
+    /// was open.
+    ///
+    /// The next write to the table opens the epoch after the persisted one. The file stays open
+    /// while the rotator is still shared through [`Self::table_rotator`]. Unlike
+    /// [`Self::remove`], keeps the rate limit and sampling set for the table.
+    ///
+    /// Waits out or fails on a quiesced distributor like [`Self::try_writer`].
+    pub fn close(&mut self, table_name: &str) -> Result<bool, TableError> {
+        self.quiesce.wait(self.quiesce_mode)?;
+        let Some(rotator) = self.rotators.remove(table_name) else {
+            return Ok(false);
+        };
+        rotator.lock().unwrap().flush()?;
+        Ok(true)
+    }
+
+    /// Like [`Self::close`] but also forgets the rate limit and sampling of the table.
+    ///
+    /// The log files and the epoch file stay on disk, where a table of the same name picks them
+    /// up again, unless `delete_files` removes the table's directory as well.
+    pub fn remove(&mut self, table_name: &str, delete_files: bool) -> Result<(), TableError> {
+        self.close(table_name)?;
+        self.admission.remove(table_name);
+        if !delete_files {
+            return Ok(());
+        }
+        let shard_dir = self.shard_dir(table_name);
+        let output_dir = table_dir(shard_dir, table_name).map_err(|e| {
+            let e = std::io::Error::new(std::io::ErrorKind::InvalidInput, e);
+            RotatorError::new(RotatorOp::RemoveFile, shard_dir, e)
+        })?;
+        match std::fs::remove_dir_all(&output_dir) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(RotatorError::new(RotatorOp::RemoveFile, output_dir, e).into())
             }
+            _ => Ok(()),
+        }
+    }
+
+    /// Like [`LogRotator::try_incr_record_count`], and false for a table without a rotator.
+    ///
+    /// Waits out or fails on a quiesced distributor like [`Self::try_writer`], since the count
+    /// may rotate the file.
+    pub fn incr_record_count(&mut self, table_name: &str) -> Result<bool, TableError> {
+        self.quiesce.wait(self.quiesce_mode)?;
+        let Some(table) = self.rotators.get(table_name) else {
+            return Ok(false);
+        };
+        Ok(table.lock().unwrap().try_incr_record_count()?)
+    }
+
+    /// Like [`LogRotator::advance_watermark`], and false for a table without a rotator.
+    ///
+    /// Waits out or fails on a quiesced distributor like [`Self::try_writer`].
+    pub fn advance_watermark(
+        &mut self,
+        table_name: &str,
+        t: jiff::Zoned,
+    ) -> Result<bool, TableError> {
+        self.quiesce.wait(self.quiesce_mode)?;
+        let Some(table) = self.rotators.get(table_name) else {
+            return Ok(false);
         };
-        table.writer()
+        Ok(table.lock().unwrap().advance_watermark(t)?)
     }
 
-    pub fn incr_record_count(&mut self, table_name: &str) {
-        let Some(table) = self.rotators.get_mut(table_name) else {
+    pub fn metrics(&self) -> DistributorMetrics {
+        let tables = self
+            .rotators
+            .iter()
+            .map(|(name, rotator)| (name.clone(), rotator.lock().unwrap().metrics()))
+            .collect();
+        let admission = self
+            .admission
+            .iter()
+            .map(|(name, admission)| (name.clone(), admission.metrics()))
+            .collect();
+        DistributorMetrics::new(tables, admission)
+    }
+
+    pub fn incr_aux_record_count(&mut self, table_name: &str) {
+        let Some(table) = self.rotators.get(table_name) else {
             return;
         };
-        table.incr_record_count();
+        table.lock().unwrap().incr_aux_record_count();
+    }
+}
+
+impl<W> Drop for LogDistributor<W>
+where
+    W: LogWriter,
+{
+    fn drop(&mut self) {
+        self.flushers.iter().for_each(|flusher| flusher.unpark());
+    }
+}
+
+/// Writer of one table, holding the lock of its rotator.
+#[derive(Debug)]
+pub struct TableWriter<'a, W>
+where
+    W: LogWriter,
+{
+    rotator: MutexGuard<'a, LogRotator<W>>,
+}
+impl<W> Deref for TableWriter<'_, W>
+where
+    W: LogWriter,
+{
+    type Target = W;
+
+    fn deref(&self) -> &W {
+        self.rotator.writer_ref()
+    }
+}
+impl<W> DerefMut for TableWriter<'_, W>
+where
+    W: LogWriter,
+{
+    fn deref_mut(&mut self) -> &mut W {
+        self.rotator.writer()
+    }
+}
+
+/// Writes to one table, counting every record exactly once.
+#[derive(Debug)]
+pub struct TableHandle<'a, W>
+where
+    W: LogWriter,
+{
+    distributor: &'a mut LogDistributor<W>,
+    table_name: &'a str,
+}
+impl<W> TableHandle<'_, W>
+where
+    W: LogWriter,
+{
+    /// Writes one data record and counts it.
+    ///
+    /// The table's sampling and rate limit are applied before `write` runs. Returns [`None`] if
+    /// the record was dropped, fails with [`TableError::RateLimited`] if it has to wait for the
+    /// rate limit, and fails like [`LogDistributor::try_table_handle`] if the table it is routed
+    /// to cannot be written. A failed rotation is returned after the record is written and
+    /// retried on the next one.
+    pub fn write<T>(&mut self, write: impl FnOnce(&mut W) -> T) -> Result<Option<T>, TableError> {
+        let admission = self.distributor.admit(self.table_name);
+        self.write_admitted(admission, write)
+    }
+
+    fn write_admitted<T>(
+        &mut self,
+        admission: Admission,
+        write: impl FnOnce(&mut W) -> T,
+    ) -> Result<Option<T>, TableError> {
+        let table_name = match &admission {
+            Admission::Admit => self.table_name,
+            Admission::Drop | Admission::SampleOut => return Ok(None),
+            Admission::Wait(wait) => return Err(RateLimited { wait: *wait }.into()),
+            Admission::RouteTo(table_name) => table_name,
+        };
+        let mut rotator = self.distributor.rotator(table_name)?;
+        let res = write(rotator.writer());
+        rotator.try_incr_record_count()?;
+        Ok(Some(res))
+    }
+
+    /// Writes one auxiliary row, e.g. a banner, and counts it as such.
+    pub fn write_aux<T>(&mut self, write: impl FnOnce(&mut W) -> T) -> Result<T, TableError> {
+        let mut rotator = self.distributor.rotator(self.table_name)?;
+        let res = write(rotator.writer());
+        rotator.incr_aux_record_count();
+        Ok(res)
+    }
+}
+
+/// What writes and the other calls that may touch the files do while the distributor is
+/// quiesced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuiesceMode {
+    /// Waits for the guards to drop.
+    ///
+    /// The waiting writer keeps holding whatever lock protects the distributor, so the wait gives
+    /// up after `timeout` rather than deadlocking a guard holder that needs that lock.
+    Block { timeout: Duration },
+    /// Fails right away.
+    FailFast,
+}
+impl Default for QuiesceMode {
+    fn default() -> Self {
+        Self::Block {
+            timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quiesced;
+impl core::fmt::Display for Quiesced {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "The distributor is quiesced")
+    }
+}
+impl std::error::Error for Quiesced {}
+
+/// Where a table is at, as of [`LogDistributor::stats`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableStats {
+    pub epoch: usize,
+    /// Data records written to the current file.
+    pub records_written: usize,
+    /// Auxiliary rows written to the current file.
+    pub aux_records: usize,
+    /// Auxiliary rows written since the table was opened, including the footers of the retired
+    /// files.
+    pub aux_records_total: u64,
+    pub path: PathBuf,
+}
+
+/// A table name that is not a single plain path component, such as `..` or `a/b`, so that its
+/// directory would not be a child of the output directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidTableName(pub String);
+impl core::fmt::Display for InvalidTableName {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Invalid table name {:?}", self.0)
+    }
+}
+impl std::error::Error for InvalidTableName {}
+
+/// The directory of the table in `output_dir`.
+fn table_dir(output_dir: &Path, table_name: &str) -> Result<PathBuf, InvalidTableName> {
+    let mut components = Path::new(table_name).components();
+    let is_plain = matches!(components.next(), Some(Component::Normal(_)))
+        && components.next().is_none()
+        && !table_name.contains(['/', '\\']);
+    match is_plain {
+        true => Ok(output_dir.join(table_name)),
+        false => Err(InvalidTableName(table_name.to_string())),
+    }
+}
+
+/// A table cannot be written to.
+#[derive(Debug)]
+pub enum TableError {
+    Quiesced(Quiesced),
+    RateLimited(RateLimited),
+    InvalidName(InvalidTableName),
+    /// The rotator of a new table failed to set up its output directory.
+    Rotator(RotatorError),
+}
+impl From<Quiesced> for TableError {
+    fn from(e: Quiesced) -> Self {
+        Self::Quiesced(e)
+    }
+}
+impl From<RateLimited> for TableError {
+    fn from(e: RateLimited) -> Self {
+        Self::RateLimited(e)
+    }
+}
+impl From<InvalidTableName> for TableError {
+    fn from(e: InvalidTableName) -> Self {
+        Self::InvalidName(e)
+    }
+}
+impl From<RotatorError> for TableError {
+    fn from(e: RotatorError) -> Self {
+        Self::Rotator(e)
+    }
+}
+impl core::fmt::Display for TableError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Quiesced(e) => e.fmt(f),
+            Self::RateLimited(e) => e.fmt(f),
+            Self::InvalidName(e) => e.fmt(f),
+            Self::Rotator(e) => e.fmt(f),
+        }
+    }
+}
+impl std::error::Error for TableError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Quiesced(e) => Some(e),
+            Self::RateLimited(e) => Some(e),
+            Self::InvalidName(e) => Some(e),
+            Self::Rotator(e) => Some(e),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct QuiesceState {
+    depth: Mutex<usize>,
+    released: Condvar,
+}
+impl QuiesceState {
+    fn wait(&self, mode: QuiesceMode) -> Result<(), Quiesced> {
+        let mut depth = self.depth.lock().unwrap();
+        let timeout = match mode {
+            QuiesceMode::Block { timeout } => timeout,
+            QuiesceMode::FailFast => Duration::ZERO,
+        };
+        let deadline = Instant::now() + timeout;
+        while *depth != 0 {
+            let Some(timeout) = deadline.checked_duration_since(Instant::now()) else {
+                return Err(Quiesced);
+            };
+            depth = self.released.wait_timeout(depth, timeout).unwrap().0;
+        }
+        Ok(())
+    }
+}
+
+#[must_use = "The distributor resumes as soon as the guard is dropped"]
+#[derive(Debug)]
+pub struct QuiesceGuard {
+    state: Arc<QuiesceState>,
+    compression_gate: Arc<CompressionGate>,
+}
+impl Drop for QuiesceGuard {
+    fn drop(&mut self) {
+        self.compression_gate.open();
+        let mut depth = self.state.depth.lock().unwrap();
+        *depth -= 1;
+        if *depth == 0 {
+            self.state.released.notify_all();
+        }
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "csv"))]
 mod tests {
-    use std::{any::type_name, io::Read, num::NonZeroUsize, path::Path};
+    use std::{
+        any::type_name,
+        io::Read,
+        num::{NonZeroU32, NonZeroUsize},
+        path::Path,
+    };
 
     use serde::Serialize;
 
+    use crate::{
+        compression::Compressor,
+        rate_limit::Overflow,
+        time_past::{DailyContains, TimePast},
+        writers::csv::CsvLogWriter,
+    };
+
     use super::*;
 
     fn log_file_path(
@@ -98,35 +987,6 @@ mod tests {
         path
     }
 
-    struct CsvLogWriter {
-        writer: csv::Writer<std::fs::File>,
-    }
-    impl CsvLogWriter {
-        pub fn writer(&mut self) -> &mut csv::Writer<std::fs::File> {
-            &mut self.writer
-        }
-    }
-    impl LogWriter for CsvLogWriter {
-        fn flush(&mut self) {
-            self.writer.flush().unwrap();
-        }
-
-        fn open(path: impl AsRef<Path>) -> Self {
-            let file = std::fs::File::options()
-                .write(true)
-                .create(true)
-                .truncate(true)
-                .open(path)
-                .expect("Cannot create a log file");
-            let writer = csv::Writer::from_writer(file);
-            Self { writer }
-        }
-
-        fn file_extension() -> &'static str {
-            "csv"
-        }
-    }
-
     struct Logger {
         distributor: Arc<Mutex<LogDistributor<CsvLogWriter>>>,
     }
@@ -139,18 +999,15 @@ mod tests {
         where
             R: Serialize,
         {
-            let table_name = type_name::<R>();
             let mut distributor = self.distributor.lock().unwrap();
             distributor
-                .writer(table_name)
-                .writer()
-                .serialize(record)
+                .table_handle(type_name::<R>())
+                .write(|w| w.writer().serialize(record).unwrap())
                 .unwrap();
-            distributor.incr_record_count(table_name);
         }
 
         pub fn flush(&self) {
-            self.distributor.lock().unwrap().flush();
+            self.distributor.lock().unwrap().flush().unwrap();
         }
     }
 
@@ -160,6 +1017,428 @@ mod tests {
         pub n: usize,
     }
 
+    #[test]
+    fn test_remove() {
+        let dir = tempfile::tempdir().unwrap();
+        let distributor = LogDistributor::new(dir.path().to_owned(), RotationPolicy::default());
+        let distributor = Arc::new(Mutex::new(distributor));
+        let logger = Logger::new(Arc::clone(&distributor));
+        logger.write(&TestRecord { s: "a", n: 0 });
+        let mut distributor = distributor.lock().unwrap();
+        distributor.writer("other");
+        let mut names = distributor.table_names().collect::<Vec<_>>();
+        names.sort_unstable();
+        assert_eq!(names, [type_name::<TestRecord>(), "other"]);
+
+        let table_name = type_name::<TestRecord>();
+        distributor.remove(table_name, false).unwrap();
+        assert_eq!(distributor.table_names().collect::<Vec<_>>(), ["other"]);
+        let path = log_file_path(dir.path(), table_name, 0, "csv");
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "s,n\na,0\n");
+
+        distributor.remove("other", true).unwrap();
+        assert_eq!(distributor.table_names().count(), 0);
+        assert!(!dir.path().join("other").exists());
+        assert!(dir.path().join(table_name).exists());
+    }
+
+    #[test]
+    fn test_table_rotator() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut distributor =
+            LogDistributor::<CsvLogWriter>::new(dir.path().to_owned(), RotationPolicy::default());
+        let a = distributor.table_rotator("a");
+        let b = distributor.table_rotator("b");
+        let mut a = a.lock().unwrap();
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                let mut b = b.lock().unwrap();
+                b.writer().serialize(&TestRecord { s: "b", n: 0 }).unwrap();
+                b.try_incr_record_count().unwrap();
+            });
+        });
+        a.writer().serialize(&TestRecord { s: "a", n: 0 }).unwrap();
+        a.try_incr_record_count().unwrap();
+        drop(a);
+        distributor.flush().unwrap();
+        assert_eq!(distributor.metrics().records_total, 2);
+        let path = log_file_path(dir.path(), "b", 0, "csv");
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "s,n\nb,0\n");
+    }
+
+    #[test]
+    fn test_try_rotate_idle_table() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy {
+            time: Some(TimePast::new(Arc::new(DailyContains))),
+            ..Default::default()
+        };
+        let mut distributor = LogDistributor::<CsvLogWriter>::new(dir.path().to_owned(), rotation);
+        distributor.writer("t");
+        let at = |t: &str| format!("{t}[UTC]").parse::<jiff::Zoned>().unwrap();
+        distributor.try_rotate_at(at("2024-01-01T23:59")).unwrap();
+        assert_eq!(distributor.metrics().tables["t"].epoch, 0);
+        distributor.try_rotate_at(at("2024-01-02T00:00")).unwrap();
+        assert_eq!(distributor.metrics().tables["t"].epoch, 1);
+        assert!(log_file_path(dir.path(), "t", 1, "csv").exists());
+    }
+
+    #[test]
+    fn test_runtime_table_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut distributor =
+            LogDistributor::<CsvLogWriter>::new(dir.path().to_owned(), RotationPolicy::default());
+        for tenant in 0..2 {
+            let table_name = format!("tenant-{tenant}");
+            distributor
+                .table_handle(&table_name)
+                .write(|w| {
+                    w.serialize(&TestRecord { s: "a", n: tenant }).unwrap();
+                })
+                .unwrap();
+        }
+        distributor.flush().unwrap();
+        for tenant in 0..2 {
+            let path = log_file_path(dir.path(), &format!("tenant-{tenant}"), 0, "csv");
+            let expected = format!("s,n\na,{tenant}\n");
+            assert_eq!(std::fs::read_to_string(path).unwrap(), expected);
+        }
+        let metrics = distributor.metrics();
+        assert_eq!(metrics.tables["tenant-1"].records_total, 1);
+    }
+
+    #[test]
+    fn test_builder() {
+        let dir = tempfile::tempdir().unwrap();
+        let shards = [dir.path().join("a"), dir.path().join("b")];
+        let (distributor, flusher) = LogDistributor::<CsvLogWriter>::builder(&shards[0])
+            .shard(&shards[1])
+            .max_records(2)
+            .flush_every(Duration::from_millis(10))
+            .build()
+            .unwrap();
+        assert!(shards.iter().all(|shard| shard.is_dir()));
+        for n in 0..3 {
+            distributor
+                .lock()
+                .unwrap()
+                .table_handle("t0")
+                .write(|w| w.serialize(&TestRecord { s: "a", n }).unwrap())
+                .unwrap();
+        }
+        let shard = distributor.lock().unwrap().shard_dir("t0").to_owned();
+        let path = log_file_path(&shard, "t0", 1, "csv");
+        let start = Instant::now();
+        while std::fs::read_to_string(&path).unwrap() != "s,n\na,2\n" {
+            assert!(start.elapsed() < Duration::from_secs(10));
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        flusher.shutdown();
+
+        let err = LogDistributor::<CsvLogWriter>::builder(dir.path())
+            .build()
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            BuildError::Policy(crate::rotator::PolicyError::NoTrigger)
+        ));
+        for (flush_interval, expected) in [
+            (Duration::ZERO, FlushIntervalError::TooShort(Duration::ZERO)),
+            (Duration::MAX, FlushIntervalError::TooLong(Duration::MAX)),
+        ] {
+            let err = LogDistributor::<CsvLogWriter>::builder(dir.path())
+                .max_records(2)
+                .flush_every(flush_interval)
+                .build()
+                .unwrap_err();
+            assert!(matches!(err, BuildError::FlushInterval(e) if e == expected));
+        }
+    }
+
+    #[test]
+    fn test_flush_interval_bounds() {
+        let dir = tempfile::tempdir().unwrap();
+        let distributor = LogDistributor::new(dir.path().to_owned(), RotationPolicy::default());
+        let distributor = Arc::new(Mutex::new(distributor));
+        let res = try_spawn_flusher(Arc::clone(&distributor), Duration::ZERO);
+        assert!(matches!(res, Err(FlushIntervalError::TooShort(_))));
+        let res = try_spawn_flusher(Arc::clone(&distributor), Duration::MAX);
+        assert!(matches!(res, Err(FlushIntervalError::TooLong(_))));
+
+        let clamped = spawn_flusher(Arc::clone(&distributor), Duration::MAX);
+        assert_eq!(
+            clamped.clamped(),
+            Some(FlushIntervalError::TooLong(Duration::MAX))
+        );
+        clamped.shutdown();
+        let flusher = spawn_flusher(Arc::clone(&distributor), Duration::ZERO);
+        assert_eq!(
+            flusher.clamped(),
+            Some(FlushIntervalError::TooShort(Duration::ZERO))
+        );
+        Logger::new(Arc::clone(&distributor)).write(&TestRecord { s: "a", n: 0 });
+        let path = log_file_path(dir.path(), type_name::<TestRecord>(), 0, "csv");
+        let start = Instant::now();
+        while std::fs::read_to_string(&path).unwrap_or_default() != "s,n\na,0\n" {
+            assert!(start.elapsed() < Duration::from_secs(10));
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        flusher.shutdown();
+    }
+
+    #[test]
+    fn test_stats_aux_records() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy {
+            max_records: NonZeroUsize::new(2),
+            write_footer: true,
+            ..Default::default()
+        };
+        let mut distributor = LogDistributor::<CsvLogWriter>::new(dir.path().to_owned(), rotation);
+        for n in 0..3 {
+            distributor
+                .table_handle("t")
+                .write(|w| {
+                    w.serialize(&TestRecord { s: "a", n }).unwrap();
+                })
+                .unwrap();
+        }
+        let stats = distributor.stats("t").unwrap();
+        assert_eq!(stats.aux_records, 0);
+        assert_eq!(stats.aux_records_total, 1);
+    }
+
+    #[test]
+    fn test_close_table() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy {
+            max_records: NonZeroUsize::new(2),
+            ..Default::default()
+        };
+        let mut distributor = LogDistributor::<CsvLogWriter>::new(dir.path().to_owned(), rotation);
+        assert_eq!(distributor.stats("t"), None);
+        for n in 0..3 {
+            distributor
+                .table_handle("t")
+                .write(|w| {
+                    w.serialize(&TestRecord { s: "a", n }).unwrap();
+                })
+                .unwrap();
+        }
+        let stats = distributor.stats("t").unwrap();
+        assert_eq!(stats.epoch, 1);
+        assert_eq!(stats.records_written, 1);
+        assert_eq!(stats.path, log_file_path(dir.path(), "t", 1, "csv"));
+
+        assert!(distributor.close("t").unwrap());
+        assert!(!distributor.close("t").unwrap());
+        assert_eq!(distributor.table_names().count(), 0);
+        let path = log_file_path(dir.path(), "t", 1, "csv");
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "s,n\na,2\n");
+
+        distributor
+            .writer("t")
+            .serialize(&TestRecord { s: "b", n: 3 })
+            .unwrap();
+        assert_eq!(distributor.stats("t").unwrap().epoch, 2);
+        distributor.flush().unwrap();
+        let path = log_file_path(dir.path(), "t", 1, "csv");
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "s,n\na,2\n");
+        let path = log_file_path(dir.path(), "t", 2, "csv");
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "s,n\nb,3\n");
+    }
+
+    #[test]
+    fn test_current_file_bytes() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut distributor =
+            LogDistributor::<CsvLogWriter>::new(dir.path().to_owned(), RotationPolicy::default());
+        assert_eq!(distributor.current_file_bytes("t"), None);
+        distributor
+            .table_handle("t")
+            .write(|w| {
+                w.serialize(&TestRecord { s: "a", n: 0 }).unwrap();
+            })
+            .unwrap();
+        let path = log_file_path(dir.path(), "t", 0, "csv");
+        // Counts the buffered record, which has not reached the file yet.
+        assert_eq!(distributor.current_file_bytes("t"), Some(8));
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 0);
+        distributor.flush().unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 8);
+        assert_eq!(distributor.current_file_bytes("t"), Some(8));
+    }
+
+    #[test]
+    fn test_sharded() {
+        let dirs = [(); 4].map(|()| tempfile::tempdir().unwrap());
+        let output_dirs = dirs.iter().map(|d| d.path().to_owned()).collect::<Vec<_>>();
+        let rotation = RotationPolicy::default();
+        let mut distributor = LogDistributor::<CsvLogWriter>::sharded(output_dirs, rotation);
+        let tables = (0..16).map(|n| format!("t{n}")).collect::<Vec<_>>();
+        for (n, table) in tables.iter().enumerate() {
+            distributor
+                .table_handle(table)
+                .write(|w| {
+                    w.serialize(&TestRecord { s: "a", n }).unwrap();
+                })
+                .unwrap();
+        }
+        distributor.flush().unwrap();
+        for (n, table) in tables.iter().enumerate() {
+            let shard_dir = distributor.shard_dir(table);
+            for dir in &dirs {
+                let path = log_file_path(dir.path(), table, 0, "csv");
+                assert_eq!(path.exists(), dir.path() == shard_dir);
+            }
+            let path = log_file_path(shard_dir, table, 0, "csv");
+            assert_eq!(
+                std::fs::read_to_string(path).unwrap(),
+                format!("s,n\na,{n}\n")
+            );
+        }
+        for dir in &dirs {
+            assert!(std::fs::read_dir(dir.path()).unwrap().next().is_some());
+        }
+        // Shards are picked the same way on every run.
+        let shard = |table: &str| {
+            let dir = distributor.shard_dir(table);
+            dirs.iter().position(|d| d.path() == dir).unwrap()
+        };
+        assert_eq!(
+            [shard("a"), shard("b"), shard("t0"), shard("t1")],
+            [0, 1, 1, 2]
+        );
+    }
+
+    #[test]
+    fn test_invalid_table_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let output_dir = dir.path().join("out");
+        let mut distributor =
+            LogDistributor::<CsvLogWriter>::new(output_dir.clone(), RotationPolicy::default());
+        for table_name in ["", ".", "..", "../escaped", "a/b", "a\\b", "/tmp"] {
+            let res = distributor.try_writer(table_name).map(drop);
+            let expected = InvalidTableName(table_name.to_string());
+            assert!(matches!(res, Err(TableError::InvalidName(e)) if e == expected));
+            assert!(distributor.remove(table_name, true).is_err());
+        }
+        assert_eq!(distributor.table_names().count(), 0);
+        let entries = std::fs::read_dir(dir.path()).unwrap();
+        let entries = entries.map(|e| e.unwrap().file_name()).collect::<Vec<_>>();
+        assert!(entries.iter().all(|e| e == "out"));
+        distributor.writer("..a");
+        assert!(output_dir.join("..a").is_dir());
+    }
+
+    #[test]
+    fn test_table_setup_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a"), "").unwrap();
+        let mut distributor =
+            LogDistributor::<CsvLogWriter>::new(dir.path().to_owned(), RotationPolicy::default());
+        let res = distributor.try_writer("a").map(drop);
+        assert!(matches!(res, Err(TableError::Rotator(_))));
+        assert!(distributor.try_writer("b").is_ok());
+        assert_eq!(distributor.table_names().collect::<Vec<_>>(), ["b"]);
+    }
+
+    #[test]
+    fn test_rotate_now() {
+        let dir = tempfile::tempdir().unwrap();
+        let distributor = LogDistributor::new(dir.path().to_owned(), RotationPolicy::default());
+        let distributor = Arc::new(Mutex::new(distributor));
+        let logger = Logger::new(Arc::clone(&distributor));
+        logger.write(&TestRecord { s: "a", n: 0 });
+        let table_name = type_name::<TestRecord>();
+        let mut guard = distributor.lock().unwrap();
+        assert!(guard.rotate_table_now(table_name, true).unwrap());
+        assert!(!guard.rotate_table_now(table_name, true).unwrap());
+        assert!(!guard.rotate_table_now("missing", false).unwrap());
+        guard.rotate_now(true).unwrap();
+        assert_eq!(guard.stats(table_name).unwrap().epoch, 1);
+        guard.rotate_now(false).unwrap();
+        drop(guard);
+        logger.write(&TestRecord { s: "b", n: 1 });
+        logger.flush();
+        let path = log_file_path(dir.path(), table_name, 2, "csv");
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "s,n\nb,1\n");
+        assert!(!dir.path().join("missing").exists());
+    }
+
+    #[test]
+    fn test_flusher_shutdown() {
+        let dir = tempfile::tempdir().unwrap();
+        let distributor = LogDistributor::new(dir.path().to_owned(), RotationPolicy::default());
+        let distributor = Arc::new(Mutex::new(distributor));
+        let flusher = spawn_flusher(Arc::clone(&distributor), Duration::from_secs(60 * 60));
+        Logger::new(Arc::clone(&distributor)).write(&TestRecord { s: "a", n: 0 });
+        let start = Instant::now();
+        flusher.shutdown();
+        assert!(start.elapsed() < Duration::from_secs(1));
+        let path = log_file_path(dir.path(), type_name::<TestRecord>(), 0, "csv");
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "s,n\na,0\n");
+    }
+
+    #[test]
+    fn test_flusher_polls_time_without_flushing() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy {
+            time: Some(TimePast::new(Arc::new(DailyContains))),
+            ..Default::default()
+        };
+        let distributor = LogDistributor::new(dir.path().to_owned(), rotation);
+        let distributor = Arc::new(Mutex::new(distributor));
+        let flusher = spawn_flusher(Arc::clone(&distributor), Duration::from_secs(60 * 60));
+        Logger::new(Arc::clone(&distributor)).write(&TestRecord { s: "a", n: 0 });
+        std::thread::sleep(crate::flusher::TIME_TRIGGER_POLL_INTERVAL + Duration::from_millis(500));
+        let path = log_file_path(dir.path(), type_name::<TestRecord>(), 0, "csv");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+        flusher.shutdown();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "s,n\na,0\n");
+    }
+
+    #[test]
+    fn test_flusher_exits_after_drop() {
+        let dir = tempfile::tempdir().unwrap();
+        let distributor =
+            LogDistributor::<CsvLogWriter>::new(dir.path().to_owned(), RotationPolicy::default());
+        let distributor = Arc::new(Mutex::new(distributor));
+        let flusher = spawn_flusher(Arc::clone(&distributor), Duration::from_secs(60 * 60));
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(!flusher.is_finished());
+        drop(distributor);
+        let start = Instant::now();
+        while !flusher.is_finished() {
+            assert!(start.elapsed() < Duration::from_secs(1));
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        flusher.shutdown();
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_spawn_flusher_async() {
+        let dir = tempfile::tempdir().unwrap();
+        let distributor = LogDistributor::new(dir.path().to_owned(), RotationPolicy::default());
+        let distributor = Arc::new(Mutex::new(distributor));
+        let cancel = tokio_util::sync::CancellationToken::new();
+        let task = spawn_flusher_async(
+            Arc::clone(&distributor),
+            Duration::from_millis(10),
+            cancel.clone(),
+        );
+        Logger::new(Arc::clone(&distributor)).write(&TestRecord { s: "a", n: 0 });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let path = log_file_path(dir.path(), type_name::<TestRecord>(), 0, "csv");
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "s,n\na,0\n");
+        cancel.cancel();
+        tokio::time::timeout(Duration::from_secs(1), task)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
     #[test]
     fn test_logger() {
         let dir = tempfile::tempdir().unwrap();
@@ -167,8 +1446,8 @@ mod tests {
             dir.path().to_owned(),
             RotationPolicy {
                 max_records: Some(NonZeroUsize::new(2).unwrap()),
-                time: None,
                 max_epochs: 2,
+                ..Default::default()
             },
         );
         let logger = Logger::new(Arc::new(Mutex::new(distributor)));
@@ -188,4 +1467,326 @@ b,1
 "#
         );
     }
+
+    fn quiesce_distributor(dir: &Path) -> LogDistributor<CsvLogWriter> {
+        LogDistributor::new(
+            dir.to_owned(),
+            RotationPolicy {
+                max_epochs: 2,
+                ..Default::default()
+            },
+        )
+    }
+
+    fn write(distributor: &mut LogDistributor<CsvLogWriter>, record: &TestRecord) {
+        let table_name = type_name::<TestRecord>();
+        let mut writer = distributor.try_writer(table_name).unwrap();
+        writer.writer().serialize(record).unwrap();
+        drop(writer);
+        distributor.incr_record_count(table_name).unwrap();
+    }
+
+    #[test]
+    fn test_quiesce_fail_fast() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut distributor = quiesce_distributor(dir.path());
+        distributor.set_quiesce_mode(QuiesceMode::FailFast);
+        write(&mut distributor, &TestRecord { s: "a", n: 0 });
+
+        let guard = distributor.quiesce().unwrap();
+        let path = log_file_path(dir.path(), type_name::<TestRecord>(), 0, "csv");
+        let snapshot = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(snapshot, "s,n\na,0\n");
+        let nested = distributor.quiesce().unwrap();
+        drop(guard);
+        assert!(distributor.is_quiesced());
+        let res = distributor.try_writer(type_name::<TestRecord>()).map(drop);
+        assert!(matches!(res, Err(TableError::Quiesced(Quiesced))));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), snapshot);
+
+        drop(nested);
+        assert!(!distributor.is_quiesced());
+        write(&mut distributor, &TestRecord { s: "b", n: 1 });
+        distributor.flush().unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "s,n\na,0\nb,1\n");
+    }
+
+    #[test]
+    fn test_quiesce_blocks_writers() {
+        let dir = tempfile::tempdir().unwrap();
+        let distributor = Arc::new(Mutex::new(quiesce_distributor(dir.path())));
+        write(
+            &mut distributor.lock().unwrap(),
+            &TestRecord { s: "a", n: 0 },
+        );
+        let guard = distributor.lock().unwrap().quiesce().unwrap();
+
+        let writer = std::thread::spawn({
+            let distributor = Arc::clone(&distributor);
+            move || {
+                let mut distributor = distributor.lock().unwrap();
+                write(&mut distributor, &TestRecord { s: "b", n: 1 });
+                distributor.flush().unwrap();
+            }
+        });
+        std::thread::sleep(Duration::from_millis(50));
+        let path = log_file_path(dir.path(), type_name::<TestRecord>(), 0, "csv");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "s,n\na,0\n");
+        assert!(!writer.is_finished());
+
+        drop(guard);
+        writer.join().unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "s,n\na,0\nb,1\n");
+    }
+
+    #[test]
+    fn test_quiesce_blocks_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let distributor = Arc::new(Mutex::new(quiesce_distributor(dir.path())));
+        write(
+            &mut distributor.lock().unwrap(),
+            &TestRecord { s: "a", n: 0 },
+        );
+        let guard = distributor.lock().unwrap().quiesce().unwrap();
+
+        let rotation = std::thread::spawn({
+            let distributor = Arc::clone(&distributor);
+            move || distributor.lock().unwrap().rotate_now(false).unwrap()
+        });
+        std::thread::sleep(Duration::from_millis(50));
+        let table_name = type_name::<TestRecord>();
+        assert!(!log_file_path(dir.path(), table_name, 1, "csv").exists());
+        assert!(!rotation.is_finished());
+
+        drop(guard);
+        rotation.join().unwrap();
+        assert!(log_file_path(dir.path(), table_name, 1, "csv").exists());
+
+        let mut distributor = distributor.lock().unwrap();
+        distributor.set_quiesce_mode(QuiesceMode::FailFast);
+        let _guard = distributor.quiesce().unwrap();
+        let is_quiesced = |res| matches!(res, Err(TableError::Quiesced(Quiesced)));
+        assert!(is_quiesced(distributor.try_rotate()));
+        assert!(is_quiesced(
+            distributor.incr_record_count(table_name).map(drop)
+        ));
+        assert!(is_quiesced(distributor.close(table_name).map(drop)));
+        assert!(is_quiesced(distributor.remove(table_name, true)));
+        assert!(log_file_path(dir.path(), table_name, 1, "csv").exists());
+        assert_eq!(distributor.table_names().count(), 1);
+    }
+
+    #[test]
+    fn test_quiesce_timeout() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut distributor = quiesce_distributor(dir.path());
+        distributor.set_quiesce_mode(QuiesceMode::Block {
+            timeout: Duration::from_millis(20),
+        });
+        let _guard = distributor.quiesce().unwrap();
+        let start = Instant::now();
+        let res = distributor.try_writer(type_name::<TestRecord>()).map(drop);
+        assert!(matches!(res, Err(TableError::Quiesced(Quiesced))));
+        assert!(Duration::from_millis(20) <= start.elapsed());
+    }
+
+    #[derive(Debug)]
+    struct SlowCompressor;
+    impl Compressor for SlowCompressor {
+        fn compress(&self, src: &Path, dst: &Path) -> std::io::Result<()> {
+            std::thread::sleep(Duration::from_millis(200));
+            std::fs::copy(src, dst).map(|_| ())
+        }
+
+        fn extension(&self) -> &str {
+            "cp"
+        }
+    }
+
+    #[test]
+    fn test_quiesce_compression() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy {
+            max_records: NonZeroUsize::new(1),
+            compression: Some(Arc::new(SlowCompressor)),
+            ..Default::default()
+        };
+        let mut distributor = LogDistributor::new(dir.path().to_owned(), rotation);
+        let table_name = type_name::<TestRecord>();
+        let rotator = distributor.table_rotator(table_name);
+        write(&mut distributor, &TestRecord { s: "a", n: 0 });
+        let guard = distributor.quiesce().unwrap();
+        assert!(!log_file_path(dir.path(), table_name, 0, "csv").exists());
+        let compressed = log_file_path(dir.path(), table_name, 0, "csv.cp");
+        assert_eq!(std::fs::read_to_string(compressed).unwrap(), "s,n\na,0\n");
+
+        let mut rotator = rotator.lock().unwrap();
+        rotator
+            .writer()
+            .serialize(&TestRecord { s: "b", n: 1 })
+            .unwrap();
+        rotator.try_incr_record_count().unwrap();
+        std::thread::sleep(Duration::from_millis(300));
+        assert!(log_file_path(dir.path(), table_name, 1, "csv").exists());
+        drop(guard);
+        rotator.wait_for_compression().unwrap();
+        assert!(!log_file_path(dir.path(), table_name, 1, "csv").exists());
+    }
+
+    #[test]
+    fn test_rate_limit_drop_and_route() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut distributor = quiesce_distributor(dir.path());
+        let limited = type_name::<TestRecord>();
+        let overflow = "overflow";
+        let rate_limit = |overflow| RateLimit {
+            records_per_second: NonZeroU32::new(10).unwrap(),
+            burst: NonZeroU32::new(5).unwrap(),
+            overflow,
+        };
+        distributor.set_rate_limit(limited, Some(rate_limit(Overflow::Drop)));
+        let start = Instant::now();
+        let written = (0..1000)
+            .filter_map(|n| {
+                distributor
+                    .table_handle(limited)
+                    .write(|w| w.writer().serialize(TestRecord { s: "a", n }).unwrap())
+                    .unwrap()
+            })
+            .count();
+        let refilled = (start.elapsed().as_secs_f64() * 10.).ceil() as usize;
+        assert!((5..=5 + refilled).contains(&written));
+        let metrics = distributor.admission_metrics(limited);
+        assert_eq!(metrics.dropped as usize, 1000 - written);
+        assert_eq!(
+            distributor.metrics().tables[limited].records_total as usize,
+            written
+        );
+
+        distributor.set_rate_limit(
+            limited,
+            Some(rate_limit(Overflow::RouteTo(overflow.into()))),
+        );
+        for n in 0..100 {
+            distributor
+                .table_handle(limited)
+                .write(|w| w.writer().serialize(TestRecord { s: "b", n }).unwrap())
+                .unwrap();
+        }
+        let metrics = distributor.admission_metrics(limited);
+        let tables = distributor.metrics().tables;
+        let routed = tables[overflow].records_total;
+        assert_eq!(metrics.routed, routed);
+        assert_eq!(tables[limited].records_total + routed, written as u64 + 100);
+        assert!(90 <= routed);
+    }
+
+    #[test]
+    fn test_route_to_invalid_table() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut distributor = quiesce_distributor(dir.path());
+        let limited = type_name::<TestRecord>();
+        distributor.set_rate_limit(
+            limited,
+            Some(RateLimit {
+                records_per_second: NonZeroU32::new(1).unwrap(),
+                burst: NonZeroU32::new(1).unwrap(),
+                overflow: Overflow::RouteTo("../overflow".into()),
+            }),
+        );
+        let mut handle = distributor.table_handle(limited);
+        let mut write =
+            |n| handle.write(|w| w.writer().serialize(TestRecord { s: "a", n }).unwrap());
+        assert!(write(0).unwrap().is_some());
+        let e = write(1).unwrap_err();
+        assert!(matches!(e, TableError::InvalidName(_)), "{e}");
+        handle
+            .write_aux(|w| w.writer().write_record(["#", "banner"]).unwrap())
+            .unwrap();
+    }
+
+    #[test]
+    fn test_rate_limit_block() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut distributor = quiesce_distributor(dir.path());
+        let limited = type_name::<TestRecord>();
+        distributor.set_rate_limit(
+            limited,
+            Some(RateLimit {
+                records_per_second: NonZeroU32::new(100).unwrap(),
+                burst: NonZeroU32::new(1).unwrap(),
+                overflow: Overflow::Block,
+            }),
+        );
+        let write = |distributor: &mut LogDistributor<CsvLogWriter>, n| {
+            distributor
+                .table_handle(limited)
+                .write(|w| w.writer().serialize(TestRecord { s: "a", n }).unwrap())
+        };
+        assert!(write(&mut distributor, 0).unwrap().is_some());
+        let e = write(&mut distributor, 1).unwrap_err();
+        assert!(matches!(e, TableError::RateLimited(_)), "{e}");
+
+        let distributor = Mutex::new(distributor);
+        let start = Instant::now();
+        for n in 1..6 {
+            let written = LogDistributor::write_blocking(&distributor, limited, |w| {
+                w.writer().serialize(TestRecord { s: "a", n }).unwrap()
+            });
+            assert!(written.unwrap().is_some());
+        }
+        assert!(Duration::from_millis(40) <= start.elapsed());
+        let distributor = distributor.into_inner().unwrap();
+        assert_eq!(distributor.admission_metrics(limited).blocked, 5);
+    }
+
+    #[test]
+    fn test_sampling() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut distributor = quiesce_distributor(dir.path());
+        let sampled = type_name::<TestRecord>();
+        distributor.set_sampling(sampled, Some(NonZeroU64::new(3).unwrap()));
+        for n in 0..7 {
+            distributor
+                .table_handle(sampled)
+                .write(|w| w.writer().serialize(TestRecord { s: "a", n }).unwrap())
+                .unwrap();
+        }
+        distributor.flush().unwrap();
+        let path = log_file_path(dir.path(), sampled, 0, "csv");
+        assert_eq!(
+            std::fs::read_to_string(path).unwrap(),
+            "s,n\na,0\na,3\na,6\n"
+        );
+        assert_eq!(distributor.admission_metrics(sampled).sampled_out, 4);
+    }
+
+    #[test]
+    fn test_sampling_in_rotation_event() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy {
+            max_records: NonZeroUsize::new(2),
+            ..Default::default()
+        };
+        let mut distributor = LogDistributor::<CsvLogWriter>::new(dir.path().to_owned(), rotation);
+        let sampled = type_name::<TestRecord>();
+        distributor.set_sampling(sampled, Some(NonZeroU64::new(2).unwrap()));
+        let rotator = distributor.table_rotator(sampled);
+        let events = Arc::new(Mutex::new(vec![]));
+        rotator.lock().unwrap().set_on_rotate(Box::new({
+            let events = Arc::clone(&events);
+            move |event| {
+                events.lock().unwrap().push(event.sampled_out);
+                false
+            }
+        }));
+        for n in 0..8 {
+            distributor
+                .table_handle(sampled)
+                .write(|w| w.writer().serialize(TestRecord { s: "a", n }).unwrap())
+                .unwrap();
+        }
+        rotator.lock().unwrap().wait_for_on_rotate();
+        assert_eq!(*events.lock().unwrap(), [1, 2]);
+    }
 }
@@ -0,0 +1,99 @@
+//! The consumer side shared by [`crate::nonblocking`] and [`crate::sharded`], which write the
+//! records queued by producers to a rotator on a thread of their own.
+
+use std::sync::mpsc;
+
+use crate::{
+    rotator::{LogRotator, RotatorError, RotatorOp},
+    LogWriter,
+};
+
+/// Most messages drained at once.
+pub(crate) const MAX_BATCH: usize = 1024;
+
+pub(crate) type WriteFn<W> = dyn FnOnce(&mut W) + Send;
+
+pub(crate) enum Message<R> {
+    /// Writes one or more records.
+    Write(R),
+    Flush(mpsc::SyncSender<Result<(), RotatorError>>),
+}
+
+/// Records that a [`Drain`] writes one at a time.
+pub(crate) trait Records<W>
+where
+    W: LogWriter,
+{
+    /// Writes each record, passing the outcome of the write to `commit` after each one.
+    fn write(self, rotator: &mut LogRotator<W>, commit: &mut CommitFn<'_, W>);
+}
+pub(crate) type CommitFn<'a, W> = dyn FnMut(&mut LogRotator<W>, std::io::Result<()>) + 'a;
+
+impl<W> Records<W> for Box<WriteFn<W>>
+where
+    W: LogWriter,
+{
+    fn write(self, rotator: &mut LogRotator<W>, commit: &mut CommitFn<'_, W>) {
+        self(rotator.writer());
+        commit(rotator, Ok(()));
+    }
+}
+
+/// Writes drained messages to a rotator.
+///
+/// Failed writes and rotations are reported by the next flush; failed rotations are retried on
+/// the next record.
+#[derive(Debug, Default)]
+pub(crate) struct Drain {
+    error: Option<RotatorError>,
+}
+impl Drain {
+    pub fn write_batch<W, R>(
+        &mut self,
+        rotator: &mut LogRotator<W>,
+        batch: impl IntoIterator<Item = Message<R>>,
+    ) where
+        W: LogWriter,
+        R: Records<W>,
+    {
+        for message in batch {
+            match message {
+                Message::Write(records) => records.write(rotator, &mut |rotator, written| {
+                    let committed = match written {
+                        Ok(()) => rotator.try_incr_record_count().map(|_| ()),
+                        Err(e) => Err(RotatorError::new(
+                            RotatorOp::Write,
+                            rotator.current_path(),
+                            e,
+                        )),
+                    };
+                    if let Err(e) = committed {
+                        self.error.get_or_insert(e);
+                    }
+                }),
+                Message::Flush(done) => {
+                    let flushed = rotator.flush();
+                    let _ = done.send(self.error.take().map_or(flushed, Err));
+                }
+            }
+        }
+    }
+
+    /// Flushes on the way out, failing like a flush.
+    pub fn finish<W>(self, rotator: &mut LogRotator<W>) -> Result<(), RotatorError>
+    where
+        W: LogWriter,
+    {
+        let flushed = rotator.flush();
+        self.error.map_or(flushed, Err)
+    }
+}
+
+impl<R> core::fmt::Debug for Message<R> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Write(_) => f.write_str("Write"),
+            Self::Flush(_) => f.write_str("Flush"),
+        }
+    }
+}
@@ -0,0 +1,534 @@
+use std::{
+    fs::File,
+    io::{self, Read, Write},
+    path::Path,
+};
+
+use aes_gcm::{
+    aead::{Aead, OsRng},
+    AeadCore, Aes256Gcm, Key, KeyInit, Nonce,
+};
+
+use crate::{
+    reader::{LogReader, LogReaderFor},
+    rotator::RotationPolicy,
+    LogWriter,
+};
+
+const MAGIC: &[u8; 4] = b"FRLE";
+const VERSION: u8 = 1;
+const MAX_CHUNK_LEN: usize = 64 * 1024;
+const TAG_LEN: usize = 16;
+const CHUNK_FLAG_LAST: u8 = 1;
+
+/// Wraps and unwraps the per-file data keys, e.g. through a KMS.
+pub trait KeyWrapper: core::fmt::Debug + Sync + Send {
+    fn wrap_key(&self, data_key: &[u8]) -> io::Result<Vec<u8>>;
+    fn unwrap_key(&self, wrapped_key: &[u8]) -> io::Result<Vec<u8>>;
+}
+
+/// A log format that writes its bytes into an [`EncryptingStream`] instead of a plain file.
+pub trait EncryptedFormat: Sized {
+    /// The writer of the same format without encryption, whose [`LogReaderFor`] impls decode
+    /// the decrypted files.
+    type Plain: LogWriter;
+
+    fn from_stream(stream: EncryptingStream) -> Self;
+    fn into_stream(self) -> io::Result<EncryptingStream>;
+    /// Pushes everything the format has buffered into the stream and flushes the stream.
+    fn flush(&mut self) -> io::Result<()>;
+    fn file_extension() -> &'static str;
+}
+
+/// Encrypts each log file with its own random data key, wrapped by
+/// [`RotationPolicy::key_wrapper`].
+#[derive(Debug)]
+pub struct EncryptedWriter<Inner> {
+    inner: Inner,
+}
+impl<Inner> EncryptedWriter<Inner>
+where
+    Inner: EncryptedFormat,
+{
+    /// Creates the file at `path`, truncating it if it exists.
+    pub fn create(path: impl AsRef<Path>, key_wrapper: &dyn KeyWrapper) -> io::Result<Self> {
+        let file = File::options()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        let stream = EncryptingStream::new(file, key_wrapper)?;
+        Ok(Self {
+            inner: Inner::from_stream(stream),
+        })
+    }
+
+    pub fn inner(&mut self) -> &mut Inner {
+        &mut self.inner
+    }
+
+    /// Writes the final chunk.
+    ///
+    /// Dropping the writer finishes it as well but swallows the errors.
+    pub fn finish(self) -> io::Result<()> {
+        self.inner.into_stream()?.finish()
+    }
+}
+impl<Inner> LogWriter for EncryptedWriter<Inner>
+where
+    Inner: EncryptedFormat,
+{
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+
+    /// Fails as there is no key wrapper; see [`Self::create`].
+    fn open(_path: impl AsRef<Path>) -> io::Result<Self> {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "An encrypted log file needs a key wrapper",
+        ))
+    }
+
+    /// Fails if [`RotationPolicy::key_wrapper`] is unset.
+    fn open_for(path: impl AsRef<Path>, rotation: &RotationPolicy) -> io::Result<Self> {
+        match &rotation.key_wrapper {
+            Some(key_wrapper) => Self::create(path, key_wrapper.as_ref()),
+            None => Self::open(path),
+        }
+    }
+
+    fn file_extension() -> &'static str {
+        Inner::file_extension()
+    }
+}
+
+/// Chunked AES-256-GCM stream.
+///
+/// Layout: `magic | version | wrapped key length (u16) | wrapped key`, followed by chunks of
+/// `ciphertext length (u32) | flags | ciphertext`. The chunk counter and the last-chunk flag are
+/// bound into the nonce, so reordered, dropped, or truncated chunks fail to decrypt.
+pub struct EncryptingStream {
+    file: File,
+    cipher: Aes256Gcm,
+    buf: Vec<u8>,
+    counter: u64,
+    finished: bool,
+}
+impl EncryptingStream {
+    pub fn new(mut file: File, key_wrapper: &dyn KeyWrapper) -> io::Result<Self> {
+        let data_key = Aes256Gcm::generate_key(OsRng);
+        let wrapped_key = key_wrapper.wrap_key(&data_key)?;
+        let wrapped_len = u16::try_from(wrapped_key.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "Wrapped key is too long"))?;
+        file.write_all(MAGIC)?;
+        file.write_all(&[VERSION])?;
+        file.write_all(&wrapped_len.to_be_bytes())?;
+        file.write_all(&wrapped_key)?;
+        Ok(Self {
+            file,
+            cipher: Aes256Gcm::new(&data_key),
+            buf: Vec::new(),
+            counter: 0,
+            finished: false,
+        })
+    }
+
+    /// Seals the buffered bytes and writes the final chunk carrying the last authentication tag.
+    pub fn finish(&mut self) -> io::Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+        self.seal_chunk(true)?;
+        self.finished = true;
+        self.file.flush()
+    }
+
+    fn seal_chunk(&mut self, last: bool) -> io::Result<()> {
+        if self.buf.is_empty() && !last {
+            return Ok(());
+        }
+        let flags = if last { CHUNK_FLAG_LAST } else { 0 };
+        let nonce = chunk_nonce(self.counter, flags);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, self.buf.as_slice())
+            .map_err(|_| io::Error::other("Failed to encrypt a chunk"))?;
+        let len = u32::try_from(ciphertext.len()).expect("Chunks are bounded by MAX_CHUNK_LEN");
+        self.file.write_all(&len.to_be_bytes())?;
+        self.file.write_all(&[flags])?;
+        self.file.write_all(&ciphertext)?;
+        self.buf.clear();
+        self.counter += 1;
+        Ok(())
+    }
+}
+impl core::fmt::Debug for EncryptingStream {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("EncryptingStream")
+            .field("file", &self.file)
+            .field("buffered", &self.buf.len())
+            .field("counter", &self.counter)
+            .field("finished", &self.finished)
+            .finish()
+    }
+}
+impl Write for EncryptingStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.finished {
+            return Err(io::Error::other("The encrypted stream is already finished"));
+        }
+        let len = buf.len().min(MAX_CHUNK_LEN - self.buf.len());
+        self.buf.extend_from_slice(&buf[..len]);
+        if self.buf.len() == MAX_CHUNK_LEN {
+            self.seal_chunk(false)?;
+        }
+        Ok(len)
+    }
+
+    /// Seals the buffered bytes into a chunk so that the file is a decryptable prefix.
+    fn flush(&mut self) -> io::Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+        self.seal_chunk(false)?;
+        self.file.flush()
+    }
+}
+impl Drop for EncryptingStream {
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}
+
+/// Reads the plaintext back from a file written by [`EncryptingStream`].
+///
+/// A file without its final chunk yields every complete chunk and then fails with
+/// [`io::ErrorKind::UnexpectedEof`].
+pub struct DecryptingReader<R> {
+    reader: R,
+    cipher: Aes256Gcm,
+    plaintext: Vec<u8>,
+    pos: usize,
+    counter: u64,
+    done: bool,
+}
+impl DecryptingReader<File> {
+    pub fn open(path: impl AsRef<Path>, key_wrapper: &dyn KeyWrapper) -> io::Result<Self> {
+        let file = File::options().read(true).open(path)?;
+        Self::new(file, key_wrapper)
+    }
+}
+impl<R> DecryptingReader<R>
+where
+    R: Read,
+{
+    pub fn new(mut reader: R, key_wrapper: &dyn KeyWrapper) -> io::Result<Self> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        let mut version = [0; 1];
+        reader.read_exact(&mut version)?;
+        if &magic != MAGIC || version[0] != VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Not an encrypted log file",
+            ));
+        }
+        let mut wrapped_len = [0; 2];
+        reader.read_exact(&mut wrapped_len)?;
+        let mut wrapped_key = vec![0; u16::from_be_bytes(wrapped_len).into()];
+        reader.read_exact(&mut wrapped_key)?;
+        let data_key = key_wrapper.unwrap_key(&wrapped_key)?;
+        if data_key.len() != 32 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Unwrapped data key has the wrong length",
+            ));
+        }
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&data_key));
+        Ok(Self {
+            reader,
+            cipher,
+            plaintext: Vec::new(),
+            pos: 0,
+            counter: 0,
+            done: false,
+        })
+    }
+
+    fn read_chunk(&mut self) -> io::Result<()> {
+        let mut header = [0; 5];
+        self.reader.read_exact(&mut header).map_err(truncated)?;
+        let len = u32::from_be_bytes(header[..4].try_into().unwrap()) as usize;
+        let flags = header[4];
+        if !(TAG_LEN..=MAX_CHUNK_LEN + TAG_LEN).contains(&len) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Invalid encrypted chunk length",
+            ));
+        }
+        let mut ciphertext = vec![0; len];
+        self.reader.read_exact(&mut ciphertext).map_err(truncated)?;
+        let nonce = chunk_nonce(self.counter, flags);
+        self.plaintext = self
+            .cipher
+            .decrypt(&nonce, ciphertext.as_slice())
+            .map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Failed to authenticate an encrypted chunk",
+                )
+            })?;
+        self.pos = 0;
+        self.counter += 1;
+        self.done = flags & CHUNK_FLAG_LAST != 0;
+        Ok(())
+    }
+}
+impl<R> core::fmt::Debug for DecryptingReader<R>
+where
+    R: core::fmt::Debug,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("DecryptingReader")
+            .field("reader", &self.reader)
+            .field("counter", &self.counter)
+            .field("done", &self.done)
+            .finish()
+    }
+}
+impl<R> Read for DecryptingReader<R>
+where
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.plaintext.len() {
+            if self.done {
+                return Ok(0);
+            }
+            self.read_chunk()?;
+        }
+        let len = buf.len().min(self.plaintext.len() - self.pos);
+        buf[..len].copy_from_slice(&self.plaintext[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+impl<Inner> LogReader<EncryptedWriter<Inner>>
+where
+    Inner: EncryptedFormat,
+{
+    /// Like [`Self::open_files`] but decrypts the files.
+    pub fn open_decrypted<'a>(
+        &'a self,
+        key_wrapper: &'a dyn KeyWrapper,
+    ) -> impl Iterator<Item = io::Result<DecryptingReader<Box<dyn Read + Send>>>> + 'a {
+        self.open_files()
+            .map(move |file| DecryptingReader::new(file?, key_wrapper))
+    }
+
+    /// Like [`Self::records`] but decrypts the files and decodes them as written by
+    /// [`EncryptedFormat::Plain`].
+    ///
+    /// The current file yields its flushed records and then
+    /// [`io::ErrorKind::UnexpectedEof`], as it is not finished yet.
+    pub fn decrypted_records<'a, R>(
+        &'a self,
+        key_wrapper: &'a dyn KeyWrapper,
+    ) -> impl Iterator<Item = io::Result<R>> + 'a
+    where
+        R: LogReaderFor<Inner::Plain> + 'a,
+    {
+        self.open_decrypted(key_wrapper).flat_map(|file| {
+            let (records, error) = match file {
+                Ok(file) => (Some(R::read_records(file)), None),
+                Err(e) => (None, Some(Err(e))),
+            };
+            records.into_iter().flatten().chain(error)
+        })
+    }
+}
+
+fn chunk_nonce(counter: u64, flags: u8) -> Nonce<<Aes256Gcm as AeadCore>::NonceSize> {
+    let mut nonce = [0; 12];
+    nonce[..8].copy_from_slice(&counter.to_be_bytes());
+    nonce[8] = flags;
+    nonce.into()
+}
+
+fn truncated(e: io::Error) -> io::Error {
+    match e.kind() {
+        io::ErrorKind::UnexpectedEof => io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "The encrypted log file is truncated",
+        ),
+        _ => e,
+    }
+}
+
+#[cfg(all(test, feature = "csv"))]
+mod tests {
+    use std::{num::NonZeroUsize, sync::Arc};
+
+    use serde::{Deserialize, Serialize};
+
+    use crate::{rotator::LogRotator, writers::csv::CsvLogWriter};
+
+    use super::*;
+
+    #[derive(Debug)]
+    struct MockKeyWrapper;
+    impl KeyWrapper for MockKeyWrapper {
+        fn wrap_key(&self, data_key: &[u8]) -> io::Result<Vec<u8>> {
+            Ok(data_key.iter().map(|b| b ^ 0x5a).collect())
+        }
+
+        fn unwrap_key(&self, wrapped_key: &[u8]) -> io::Result<Vec<u8>> {
+            Ok(wrapped_key.iter().map(|b| b ^ 0x5a).collect())
+        }
+    }
+
+    #[derive(Debug)]
+    struct CsvFormat {
+        writer: csv::Writer<EncryptingStream>,
+    }
+    impl EncryptedFormat for CsvFormat {
+        type Plain = CsvLogWriter;
+
+        fn from_stream(stream: EncryptingStream) -> Self {
+            let writer = csv::Writer::from_writer(stream);
+            Self { writer }
+        }
+
+        fn into_stream(self) -> io::Result<EncryptingStream> {
+            self.writer.into_inner().map_err(|e| e.into_error())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.writer.flush()
+        }
+
+        fn file_extension() -> &'static str {
+            "csv"
+        }
+    }
+
+    type Writer = EncryptedWriter<CsvFormat>;
+
+    fn decrypt(path: impl AsRef<Path>) -> (String, io::Result<usize>) {
+        let mut reader = DecryptingReader::open(path, &MockKeyWrapper).unwrap();
+        let mut plaintext = Vec::new();
+        let res = reader.read_to_end(&mut plaintext);
+        (String::from_utf8(plaintext).unwrap(), res)
+    }
+
+    #[test]
+    fn test_rotation_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut rotator = LogRotator::<Writer>::new_or_panic(
+            dir.path().to_owned(),
+            RotationPolicy {
+                max_records: Some(NonZeroUsize::new(2).unwrap()),
+                max_epochs: 2,
+                key_wrapper: Some(Arc::new(MockKeyWrapper)),
+                ..Default::default()
+            },
+        );
+        for (s, n) in [("a", 0), ("b", 1), ("c", 2)] {
+            rotator
+                .writer()
+                .inner()
+                .writer
+                .write_record([s, &n.to_string()])
+                .unwrap();
+            rotator.try_incr_record_count().unwrap();
+        }
+        drop(rotator);
+
+        let (csv, res) = decrypt(dir.path().join("0.csv"));
+        res.unwrap();
+        assert_eq!(csv, "a,0\nb,1\n");
+        let (csv, res) = decrypt(dir.path().join("1.csv"));
+        res.unwrap();
+        assert_eq!(csv, "c,2\n");
+
+        let raw = std::fs::read(dir.path().join("0.csv")).unwrap();
+        assert!(!raw.windows(3).any(|w| w == b"a,0"));
+    }
+
+    #[test]
+    fn test_decrypted_records() {
+        #[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+        struct Record {
+            n: usize,
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy {
+            max_records: Some(NonZeroUsize::new(2).unwrap()),
+            key_wrapper: Some(Arc::new(MockKeyWrapper)),
+            ..Default::default()
+        };
+        let mut rotator = LogRotator::<Writer>::new_or_panic(dir.path().to_owned(), rotation);
+        for n in 0..5 {
+            rotator
+                .writer()
+                .inner()
+                .writer
+                .serialize(Record { n })
+                .unwrap();
+            rotator.try_incr_record_count().unwrap();
+        }
+        rotator.flush_or_panic();
+
+        let reader = LogReader::<Writer>::new(dir.path());
+        assert_eq!(reader.files().len(), 3);
+        let mut records = reader.decrypted_records::<Record>(&MockKeyWrapper);
+        let n = records.by_ref().take(5).map(|r| r.unwrap().n);
+        assert_eq!(n.collect::<Vec<_>>(), [0, 1, 2, 3, 4]);
+        let e = records.next().unwrap().unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+        assert!(records.next().is_none());
+
+        drop(rotator);
+        let records = reader.decrypted_records::<Record>(&MockKeyWrapper);
+        let n = records.map(|r| r.unwrap().n);
+        assert_eq!(n.collect::<Vec<_>>(), [0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_missing_key_wrapper() {
+        let dir = tempfile::tempdir().unwrap();
+        let e = LogRotator::<Writer>::new(dir.path().to_owned(), RotationPolicy::default())
+            .unwrap_err();
+        assert_eq!(e.source.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_flushed_prefix_and_truncation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("0.csv");
+        let mut writer = Writer::create(&path, &MockKeyWrapper).unwrap();
+        writer.inner().writer.write_record(["a", "0"]).unwrap();
+        LogWriter::flush(&mut writer).unwrap();
+
+        let (csv, res) = decrypt(&path);
+        assert_eq!(res.unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(csv, "a,0\n");
+
+        writer.inner().writer.write_record(["b", "1"]).unwrap();
+        LogWriter::flush(&mut writer).unwrap();
+        let flushed_len = std::fs::metadata(&path).unwrap().len();
+        writer.finish().unwrap();
+        let (csv, res) = decrypt(&path);
+        res.unwrap();
+        assert_eq!(csv, "a,0\nb,1\n");
+
+        let file = File::options().write(true).open(&path).unwrap();
+        file.set_len(flushed_len - 3).unwrap();
+        let (csv, res) = decrypt(&path);
+        assert_eq!(res.unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(csv, "a,0\n");
+    }
+}
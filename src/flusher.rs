@@ -0,0 +1,283 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+/// Shortest wake interval of a flusher thread.
+pub const MIN_FLUSH_INTERVAL: Duration = Duration::from_millis(10);
+/// Longest wake interval of a flusher thread.
+pub const MAX_FLUSH_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// Longest wake interval of a flusher thread while a rotator has a time or cron trigger, so that
+/// idle rotators still rotate on time. Flushing still waits for the flush interval.
+pub const TIME_TRIGGER_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Wake interval of a flusher thread.
+///
+/// The interval doubles, up to `max`, after every `idle_ticks` consecutive ticks without a dirty
+/// rotator and snaps back to `base` as soon as one is dirty again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AdaptiveInterval {
+    pub base: Duration,
+    pub max: Duration,
+    pub idle_ticks: usize,
+}
+impl AdaptiveInterval {
+    pub fn fixed(interval: Duration) -> Self {
+        Self {
+            base: interval,
+            max: interval,
+            idle_ticks: 1,
+        }
+    }
+
+    /// Checks the intervals against [`MIN_FLUSH_INTERVAL`] and [`MAX_FLUSH_INTERVAL`].
+    pub fn validate(&self) -> Result<(), FlushIntervalError> {
+        if self.base < MIN_FLUSH_INTERVAL {
+            return Err(FlushIntervalError::TooShort(self.base));
+        }
+        if MAX_FLUSH_INTERVAL < self.max {
+            return Err(FlushIntervalError::TooLong(self.max));
+        }
+        if self.max < self.base {
+            return Err(FlushIntervalError::BaseAboveMax {
+                base: self.base,
+                max: self.max,
+            });
+        }
+        Ok(())
+    }
+
+    /// Clamps both intervals into [`MIN_FLUSH_INTERVAL`]`..=`[`MAX_FLUSH_INTERVAL`], raising `max`
+    /// to `base` if needed.
+    pub fn clamped(self) -> Self {
+        let base = self.base.clamp(MIN_FLUSH_INTERVAL, MAX_FLUSH_INTERVAL);
+        let max = self.max.clamp(base, MAX_FLUSH_INTERVAL);
+        Self { base, max, ..self }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlushIntervalError {
+    TooShort(Duration),
+    TooLong(Duration),
+    BaseAboveMax { base: Duration, max: Duration },
+}
+impl core::fmt::Display for FlushIntervalError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::TooShort(d) => write!(
+                f,
+                "Flush interval {d:?} is shorter than {MIN_FLUSH_INTERVAL:?}"
+            ),
+            Self::TooLong(d) => write!(
+                f,
+                "Flush interval {d:?} is longer than {MAX_FLUSH_INTERVAL:?}"
+            ),
+            Self::BaseAboveMax { base, max } => write!(
+                f,
+                "Base flush interval {base:?} is longer than the maximum {max:?}"
+            ),
+        }
+    }
+}
+impl std::error::Error for FlushIntervalError {}
+
+/// Stops a flusher thread.
+///
+/// Dropping the handle detaches the thread, which then runs until its rotators are dropped.
+#[derive(Debug)]
+pub struct FlusherHandle {
+    shutdown: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+    clamped: Option<FlushIntervalError>,
+}
+impl FlusherHandle {
+    /// `interval` is the one asked for, before [`AdaptiveInterval::clamped`].
+    pub(crate) fn new(
+        shutdown: Arc<AtomicBool>,
+        thread: JoinHandle<()>,
+        interval: &AdaptiveInterval,
+    ) -> Self {
+        Self {
+            shutdown,
+            thread,
+            clamped: interval.validate().err(),
+        }
+    }
+
+    /// Why the interval asked for was clamped into
+    /// [`MIN_FLUSH_INTERVAL`]`..=`[`MAX_FLUSH_INTERVAL`], if it was.
+    pub fn clamped(&self) -> Option<FlushIntervalError> {
+        self.clamped
+    }
+
+    pub(crate) fn thread(&self) -> &std::thread::Thread {
+        self.thread.thread()
+    }
+
+    /// The thread has exited, e.g. after everything it flushes was dropped.
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    /// Wakes the thread up for a final flush and waits for it to exit.
+    pub fn shutdown(self) {
+        self.shutdown.store(true, Ordering::Relaxed);
+        self.thread.thread().unpark();
+        self.thread
+            .join()
+            .expect("The flushing worker thread panicked");
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Backoff {
+    config: AdaptiveInterval,
+    interval: Duration,
+    idle_ticks: usize,
+    polls_time: bool,
+    last_flush: Instant,
+}
+impl Backoff {
+    pub fn new(config: AdaptiveInterval) -> Self {
+        let config = config.clamped();
+        Self {
+            config,
+            interval: config.base,
+            idle_ticks: 0,
+            polls_time: false,
+            last_flush: Instant::now(),
+        }
+    }
+
+    /// How long to sleep before the next flush, or at most [`TIME_TRIGGER_POLL_INTERVAL`] while
+    /// polling time triggers.
+    pub fn timeout(&self) -> Duration {
+        let timeout = self.interval.saturating_sub(self.last_flush.elapsed());
+        match self.polls_time {
+            true => timeout.min(TIME_TRIGGER_POLL_INTERVAL),
+            false => timeout,
+        }
+    }
+
+    /// Tells if the flush interval has elapsed since the last flush, starting the next one if so.
+    pub fn is_flush_due(&mut self) -> bool {
+        if self.last_flush.elapsed() < self.interval {
+            return false;
+        }
+        self.last_flush = Instant::now();
+        true
+    }
+
+    pub fn set_polls_time(&mut self, polls_time: bool) {
+        self.polls_time = polls_time;
+    }
+
+    /// Called on every flush.
+    pub fn tick(&mut self, is_dirty: bool) {
+        if is_dirty {
+            self.interval = self.config.base;
+            self.idle_ticks = 0;
+            return;
+        }
+        self.idle_ticks += 1;
+        if self.idle_ticks < self.config.idle_ticks {
+            return;
+        }
+        self.idle_ticks = 0;
+        self.interval = self
+            .interval
+            .saturating_mul(2)
+            .clamp(self.config.base, self.config.max);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        let mut backoff = Backoff::new(AdaptiveInterval {
+            base: Duration::from_millis(100),
+            max: Duration::from_millis(500),
+            idle_ticks: 2,
+        });
+        let mut intervals = vec![];
+        for is_dirty in [false, false, false, false, false, false, false, true, false] {
+            backoff.tick(is_dirty);
+            intervals.push(backoff.interval.as_millis());
+        }
+        assert_eq!(intervals, [100, 200, 200, 400, 400, 500, 500, 100, 100]);
+    }
+
+    #[test]
+    fn test_fixed() {
+        let mut backoff = Backoff::new(AdaptiveInterval::fixed(Duration::from_millis(100)));
+        for _ in 0..4 {
+            backoff.tick(false);
+            assert_eq!(backoff.interval, Duration::from_millis(100));
+        }
+    }
+
+    #[test]
+    fn test_polls_time() {
+        let mut backoff = Backoff::new(AdaptiveInterval::fixed(Duration::from_secs(60)));
+        assert!(Duration::from_secs(59) < backoff.timeout());
+        backoff.set_polls_time(true);
+        assert_eq!(backoff.timeout(), TIME_TRIGGER_POLL_INTERVAL);
+        assert!(!backoff.is_flush_due());
+        assert_eq!(backoff.interval, Duration::from_secs(60));
+
+        let mut backoff = Backoff::new(AdaptiveInterval::fixed(MIN_FLUSH_INTERVAL));
+        backoff.set_polls_time(true);
+        std::thread::sleep(backoff.timeout());
+        assert!(backoff.is_flush_due());
+        assert!(!backoff.is_flush_due());
+    }
+
+    #[test]
+    fn test_validate() {
+        let fixed = AdaptiveInterval::fixed;
+        assert!(fixed(Duration::from_millis(100)).validate().is_ok());
+        assert_eq!(
+            fixed(Duration::ZERO).validate(),
+            Err(FlushIntervalError::TooShort(Duration::ZERO))
+        );
+        assert_eq!(
+            fixed(Duration::MAX).validate(),
+            Err(FlushIntervalError::TooLong(Duration::MAX))
+        );
+        let inverted = AdaptiveInterval {
+            base: Duration::from_secs(2),
+            max: Duration::from_secs(1),
+            idle_ticks: 1,
+        };
+        assert!(matches!(
+            inverted.validate(),
+            Err(FlushIntervalError::BaseAboveMax { .. })
+        ));
+    }
+
+    #[test]
+    fn test_clamped() {
+        let backoff = Backoff::new(AdaptiveInterval::fixed(Duration::ZERO));
+        assert_eq!(backoff.interval, MIN_FLUSH_INTERVAL);
+        let mut backoff = Backoff::new(AdaptiveInterval {
+            base: Duration::from_secs(1),
+            max: Duration::MAX,
+            idle_ticks: 1,
+        });
+        for _ in 0..64 {
+            backoff.tick(false);
+        }
+        assert_eq!(backoff.interval, MAX_FLUSH_INTERVAL);
+        let clamped = AdaptiveInterval::fixed(Duration::MAX).clamped();
+        assert_eq!(clamped.base, MAX_FLUSH_INTERVAL);
+        assert!(clamped.validate().is_ok());
+    }
+}
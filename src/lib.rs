@@ -1,13 +1,113 @@
 use std::path::Path;
 
-mod cron;
+pub mod clock;
+pub mod compression;
+#[cfg(feature = "serde")]
+pub mod config;
+pub mod cron;
 pub mod distributor;
+mod drain;
+#[cfg(feature = "encryption")]
+pub mod encryption;
+pub mod flusher;
+#[cfg(feature = "log")]
+pub mod log;
+pub mod metadata;
+pub mod metrics;
+pub mod nonblocking;
+pub mod rate_limit;
+pub mod reader;
 pub mod rotator;
+pub mod sharded;
+#[cfg(all(unix, feature = "signal"))]
+pub mod signal;
 mod table;
 pub mod time_past;
+#[cfg(feature = "tracing")]
+pub mod tracing;
+pub mod writers;
 
 pub trait LogWriter: Sized {
-    fn flush(&mut self);
-    fn open(path: impl AsRef<Path>) -> Self;
+    fn flush(&mut self) -> std::io::Result<()>;
+
+    /// Creates the file at `path`, truncating it if it exists.
+    fn open(path: impl AsRef<Path>) -> std::io::Result<Self>;
+
+    /// Like [`Self::open`] but for a file of a rotator under `rotation`, which is what the
+    /// rotator calls.
+    ///
+    /// Defaults to [`Self::open`].
+    fn open_for(
+        path: impl AsRef<Path>,
+        _rotation: &rotator::RotationPolicy,
+    ) -> std::io::Result<Self> {
+        Self::open(path)
+    }
+
+    /// Reopens the file at `path`, creating it if missing, to append records after the ones
+    /// already in it, and returns the writer along with the number of those records.
+    ///
+    /// Used by [`rotator::RotationPolicy::resume`]. Unsupported by default.
+    fn try_open_append(_path: impl AsRef<Path>) -> std::io::Result<(Self, usize)> {
+        Err(std::io::ErrorKind::Unsupported.into())
+    }
+
     fn file_extension() -> &'static str;
+
+    /// Called once a file is created by the rotator, e.g. to write a preamble. Does nothing by
+    /// default.
+    ///
+    /// Returns the number of rows written, which count as auxiliary records. A failure fails the
+    /// opening of the file. Not called on files reopened by [`Self::try_open_append`].
+    fn on_open(&mut self) -> std::io::Result<usize> {
+        Ok(0)
+    }
+
+    /// Called before the rotator retires the file, after the footer, e.g. to write a trailer.
+    /// Does nothing by default.
+    ///
+    /// Returns the number of rows written, which count as auxiliary records. A failure fails the
+    /// rotation, which keeps the file current.
+    fn on_close(&mut self) -> std::io::Result<usize> {
+        Ok(0)
+    }
+
+    /// Appends a comment such as `#records=1234` that parsers of the format skip.
+    ///
+    /// Called with the final record count before the file is retired. Writes nothing by default.
+    /// A failure fails the rotation, which keeps the file current.
+    fn write_footer(&mut self, _records_written: usize) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    /// Syncs the flushed records to disk, e.g. with [`std::fs::File::sync_data`].
+    ///
+    /// Called on flush as asked by [`rotator::RotationPolicy::durability`]. Does nothing by
+    /// default.
+    fn sync(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    /// Bytes written to the current file so far, including the ones still buffered.
+    ///
+    /// [`rotator::RotationPolicy::max_bytes`] falls back to the size of the file on disk if this
+    /// returns [`None`], which it does by default.
+    fn bytes_written(&mut self) -> Option<u64> {
+        None
+    }
+
+    /// Fingerprint of the shape of the records in the current file, e.g. a hash of its CSV
+    /// header, for [`rotator::RotationEvent::schema_changed`].
+    ///
+    /// [`None`] until known, and by default.
+    fn schema(&self) -> Option<u64> {
+        None
+    }
+
+    /// [`Self::schema`] spelled out, e.g. the CSV header row, which the sidecar keeps.
+    ///
+    /// [`None`] until known, and by default.
+    fn schema_text(&self) -> Option<String> {
+        None
+    }
 }
@@ -0,0 +1,122 @@
+use std::{
+    io::Write,
+    sync::{Arc, Mutex},
+};
+
+use ::log::{LevelFilter, Log, Metadata, Record, SetLoggerError};
+
+use crate::{
+    rotator::{LogRotator, RotatorError, RotatorOp},
+    LogWriter,
+};
+
+/// Writes the records of the `log` crate to a rotator, one line per record.
+///
+/// Each line reads `<timestamp> <level> <target>: <message>`.
+/// Failures are kept for the next [`LogRotator::flush`] called directly, as the `log` crate has
+/// no way to report them.
+/// [`crate::writers::text::TextLogWriter`] fits as the log writer.
+#[derive(Debug)]
+pub struct RotatingLogger<W>
+where
+    W: LogWriter,
+{
+    rotator: Arc<Mutex<LogRotator<W>>>,
+    level: LevelFilter,
+}
+impl<W> RotatingLogger<W>
+where
+    W: LogWriter + Write + Send + 'static,
+{
+    pub fn new(rotator: Arc<Mutex<LogRotator<W>>>, level: LevelFilter) -> Self {
+        Self { rotator, level }
+    }
+
+    /// Installs the logger with [`::log::set_boxed_logger`] and sets the max level to its own.
+    pub fn init(self) -> Result<(), SetLoggerError> {
+        let level = self.level;
+        ::log::set_boxed_logger(Box::new(self))?;
+        ::log::set_max_level(level);
+        Ok(())
+    }
+}
+impl<W> Log for RotatingLogger<W>
+where
+    W: LogWriter + Write + Send,
+{
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = format!(
+            "{} {:<5} {}: {}\n",
+            jiff::Timestamp::now(),
+            record.level(),
+            record.target(),
+            record.args()
+        );
+        let mut rotator = self.rotator.lock().unwrap();
+        if let Err(e) = rotator.writer().write_all(line.as_bytes()) {
+            let e = RotatorError::new(RotatorOp::Write, rotator.current_path(), e);
+            rotator.defer_error(e);
+            return;
+        }
+        if let Err(e) = rotator.try_incr_record_count() {
+            rotator.defer_error(e);
+        }
+    }
+
+    fn flush(&self) {
+        let mut rotator = self.rotator.lock().unwrap();
+        if let Err(e) = rotator.flush() {
+            rotator.defer_error(e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroUsize;
+
+    use crate::{
+        rotator::{list_log_files, RotationPolicy},
+        writers::text::TextLogWriter,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotator = LogRotator::<TextLogWriter>::new_or_panic(
+            dir.path().to_owned(),
+            RotationPolicy {
+                max_records: Some(NonZeroUsize::new(2).unwrap()),
+                ..Default::default()
+            },
+        );
+        let rotator = Arc::new(Mutex::new(rotator));
+        RotatingLogger::new(Arc::clone(&rotator), LevelFilter::Info)
+            .init()
+            .unwrap();
+        ::log::info!("a");
+        ::log::debug!("filtered");
+        ::log::warn!("b");
+        ::log::error!("c");
+        ::log::logger().flush();
+
+        let files = list_log_files(dir.path(), "log", None);
+        assert_eq!(files.len(), 2);
+        let first = std::fs::read_to_string(&files[0].path).unwrap();
+        let lines = first.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with(" INFO  file_rotating_log::log::tests: a"));
+        assert!(lines[1].ends_with(" WARN  file_rotating_log::log::tests: b"));
+        let second = std::fs::read_to_string(&files[1].path).unwrap();
+        assert!(second.ends_with(" ERROR file_rotating_log::log::tests: c\n"));
+    }
+}
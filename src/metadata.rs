@@ -0,0 +1,187 @@
+//! Sidecar files describing each retired log file, written with
+//! [`crate::rotator::RotationPolicy::write_metadata`].
+//!
+//! The sidecar of `<file>` is `<file>.meta` next to it, named after the uncompressed file even
+//! once that one is compressed. It holds one `<key>=<value>` line per field, with the backslashes
+//! and line breaks of text values escaped as `\\`, `\n` and `\r`.
+
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+const EXTENSION: &str = "meta";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EpochMetadata {
+    /// Data records in the file.
+    pub records: usize,
+    /// Rows that are not data records, such as those written by [`crate::LogWriter::on_open`],
+    /// [`crate::LogWriter::write_footer`] and [`crate::LogWriter::on_close`].
+    pub aux_records: usize,
+    /// [`crate::LogWriter::schema`] of the file, if known.
+    pub schema: Option<u64>,
+    /// [`crate::LogWriter::schema_text`] of the file, if known.
+    pub schema_text: Option<String>,
+}
+impl EpochMetadata {
+    /// Reads the sidecar of the log file at `log_path`, compressed or not, whose compression
+    /// extension is `compression`.
+    pub fn read(log_path: impl AsRef<Path>, compression: Option<&str>) -> std::io::Result<Self> {
+        let path = metadata_path(log_path.as_ref(), compression);
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// Writes a temporary file renamed over the sidecar of the uncompressed `log_path`, so that
+    /// readers never see a partial one.
+    pub(crate) fn write(&self, log_path: &Path) -> std::io::Result<()> {
+        let path = metadata_path(log_path, None);
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        let mut file = std::fs::File::create(&tmp)?;
+        file.write_all(self.to_string().as_bytes())?;
+        drop(file);
+        std::fs::rename(&tmp, &path)
+    }
+
+    fn parse(s: &str) -> std::io::Result<Self> {
+        let invalid = |line: &str| {
+            let msg = format!("Invalid metadata line {line:?}");
+            std::io::Error::new(std::io::ErrorKind::InvalidData, msg)
+        };
+        let mut metadata = Self::default();
+        for line in s.lines() {
+            let (key, value) = line.split_once('=').ok_or_else(|| invalid(line))?;
+            match key {
+                "records" => metadata.records = value.parse().map_err(|_| invalid(line))?,
+                "aux_records" => metadata.aux_records = value.parse().map_err(|_| invalid(line))?,
+                "schema" => {
+                    let schema = u64::from_str_radix(value, 16).map_err(|_| invalid(line))?;
+                    metadata.schema = Some(schema);
+                }
+                "schema_text" => metadata.schema_text = Some(unescape(value)),
+                // Left for later versions to add fields.
+                _ => (),
+            }
+        }
+        Ok(metadata)
+    }
+}
+impl core::fmt::Display for EpochMetadata {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        writeln!(f, "records={}", self.records)?;
+        writeln!(f, "aux_records={}", self.aux_records)?;
+        if let Some(schema) = self.schema {
+            writeln!(f, "schema={schema:016x}")?;
+        }
+        if let Some(schema_text) = &self.schema_text {
+            writeln!(f, "schema_text={}", escape(schema_text))?;
+        }
+        Ok(())
+    }
+}
+
+/// Keeps a value on its line.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some(next @ ('\\' | 'n' | 'r'))) => {
+                chars.next();
+                unescaped.push(match next {
+                    'n' => '\n',
+                    'r' => '\r',
+                    _ => '\\',
+                });
+            }
+            _ => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+/// Path of the sidecar of the log file at `log_path`, compressed or not, whose compression
+/// extension is `compression`.
+pub fn metadata_path(log_path: &Path, compression: Option<&str>) -> PathBuf {
+    let uncompressed = compression.and_then(|compression| {
+        let path = log_path.to_str()?;
+        path.strip_suffix(compression)?.strip_suffix('.')
+    });
+    let mut path = match uncompressed {
+        Some(uncompressed) => uncompressed.into(),
+        None => log_path.as_os_str().to_owned(),
+    };
+    path.push(".");
+    path.push(EXTENSION);
+    PathBuf::from(path)
+}
+
+/// Tells if `file_name` is that of a sidecar, or of one being written.
+pub(crate) fn is_metadata_file_name(file_name: &str) -> bool {
+    let file_name = file_name.strip_suffix(".tmp").unwrap_or(file_name);
+    file_name
+        .strip_suffix(EXTENSION)
+        .is_some_and(|stem| stem.ends_with('.'))
+}
+
+/// The data records decoded from a log file against those recorded in its sidecar, as of
+/// [`crate::reader::LogReader::reconcile`].
+#[derive(Debug)]
+pub struct Reconciliation {
+    pub epoch: usize,
+    pub path: PathBuf,
+    /// [`None`] if the file has no sidecar, e.g. if it is still being written.
+    pub metadata: Option<EpochMetadata>,
+    /// Data records decoded from the file, or the error that stopped the decoding.
+    pub records: std::io::Result<usize>,
+}
+impl Reconciliation {
+    /// The file holds exactly the data records its sidecar says.
+    pub fn is_exact(&self) -> bool {
+        match (&self.metadata, &self.records) {
+            (Some(metadata), Ok(records)) => metadata.records == *records,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let metadata = EpochMetadata {
+            records: 3,
+            aux_records: 2,
+            schema: Some(0xabc),
+            schema_text: Some("a,\"b\\\nc\"".to_string()),
+        };
+        let s = metadata.to_string();
+        assert_eq!(
+            s,
+            "records=3\naux_records=2\nschema=0000000000000abc\nschema_text=a,\"b\\\\\\nc\"\n"
+        );
+        assert_eq!(EpochMetadata::parse(&s).unwrap(), metadata);
+        assert!(EpochMetadata::parse("records=x\n").is_err());
+    }
+
+    #[test]
+    fn test_metadata_path() {
+        let path = Path::new("d/0.csv.gz");
+        assert_eq!(metadata_path(path, Some("gz")), Path::new("d/0.csv.meta"));
+        assert_eq!(metadata_path(path, None), Path::new("d/0.csv.gz.meta"));
+        assert!(is_metadata_file_name("0.csv.meta"));
+        assert!(is_metadata_file_name("0.csv.meta.tmp"));
+        assert!(!is_metadata_file_name("0.meta_csv"));
+    }
+}
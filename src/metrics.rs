@@ -0,0 +1,150 @@
+//! Serializable snapshots of the rotator counters.
+//!
+//! The field names are stable since they are exposed verbatim as JSON.
+
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct RotatorMetrics {
+    /// Epoch of the file being written.
+    pub epoch: usize,
+    /// Data records written to the current file.
+    pub records_current: usize,
+    /// Data records written since the rotator was created.
+    pub records_total: u64,
+    /// Auxiliary rows, such as banners and footers, of the current file.
+    pub aux_records_current: usize,
+    /// Auxiliary rows written since the rotator was created.
+    pub aux_records_total: u64,
+    /// On-disk size of the current file, excluding data still buffered by the writer.
+    pub bytes: Option<u64>,
+    /// Rotations performed since the rotator was created.
+    pub rotations: u64,
+    /// Time of the last rotation performed by this rotator.
+    pub last_rotation: Option<jiff::Timestamp>,
+    /// Earliest watermark covered by the current file.
+    pub watermark_start: Option<jiff::Timestamp>,
+    /// Latest watermark covered by the current file.
+    pub watermark_end: Option<jiff::Timestamp>,
+    /// Watermarks ignored for going backwards.
+    pub watermark_regressions: u64,
+    /// Background compressions that failed; see [`crate::rotator::LogRotator::wait_for_compression`].
+    pub compression_failures: u64,
+    /// Failures of calls that could not return them, such as those of flushers; see
+    /// [`crate::rotator::LogRotator::flush`].
+    pub deferred_failures: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DistributorMetrics {
+    /// Data records written across all tables since they were created.
+    pub records_total: u64,
+    /// Sum of the on-disk sizes of the tables' current files.
+    pub bytes: u64,
+    /// Rotations performed across all tables.
+    pub rotations: u64,
+    /// Per-table breakdown keyed by table name.
+    pub tables: BTreeMap<String, RotatorMetrics>,
+    /// Rate limiting and sampling counters of the tables that have them configured.
+    pub admission: BTreeMap<String, AdmissionMetrics>,
+}
+impl DistributorMetrics {
+    pub fn new(
+        tables: BTreeMap<String, RotatorMetrics>,
+        admission: BTreeMap<String, AdmissionMetrics>,
+    ) -> Self {
+        let records_total = tables.values().map(|t| t.records_total).sum();
+        let bytes = tables.values().filter_map(|t| t.bytes).sum();
+        let rotations = tables.values().map(|t| t.rotations).sum();
+        Self {
+            records_total,
+            bytes,
+            rotations,
+            tables,
+            admission,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct AdmissionMetrics {
+    /// Records dropped by the rate limit.
+    pub dropped: u64,
+    /// Records that waited for the rate limit.
+    pub blocked: u64,
+    /// Records written to the overflow table instead.
+    pub routed: u64,
+    /// Records discarded by sampling.
+    pub sampled_out: u64,
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json() {
+        let table = RotatorMetrics {
+            epoch: 3,
+            records_current: 1,
+            records_total: 7,
+            aux_records_current: 1,
+            aux_records_total: 4,
+            bytes: Some(12),
+            rotations: 3,
+            last_rotation: Some(jiff::Timestamp::UNIX_EPOCH),
+            watermark_start: None,
+            watermark_end: None,
+            watermark_regressions: 0,
+            compression_failures: 0,
+            deferred_failures: 0,
+        };
+        let admission = AdmissionMetrics {
+            dropped: 1,
+            blocked: 0,
+            routed: 2,
+            sampled_out: 3,
+        };
+        let metrics = DistributorMetrics::new(
+            BTreeMap::from([("t".to_string(), table)]),
+            BTreeMap::from([("t".to_string(), admission)]),
+        );
+        let json = serde_json::to_value(&metrics).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "records_total": 7,
+                "bytes": 12,
+                "rotations": 3,
+                "tables": {
+                    "t": {
+                        "epoch": 3,
+                        "records_current": 1,
+                        "records_total": 7,
+                        "aux_records_current": 1,
+                        "aux_records_total": 4,
+                        "bytes": 12,
+                        "rotations": 3,
+                        "last_rotation": "1970-01-01T00:00:00Z",
+                        "watermark_start": null,
+                        "watermark_end": null,
+                        "watermark_regressions": 0,
+                        "compression_failures": 0,
+                        "deferred_failures": 0,
+                    },
+                },
+                "admission": {
+                    "t": {
+                        "dropped": 1,
+                        "blocked": 0,
+                        "routed": 2,
+                        "sampled_out": 3,
+                    },
+                },
+            })
+        );
+    }
+}
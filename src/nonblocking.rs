@@ -0,0 +1,390 @@
+//! A write path where producers never wait on the rotator or on file IO.
+//!
+//! Records go into a bounded queue that a worker thread, which owns the [`LogRotator`], drains
+//! in batches. What happens to a record that finds the queue full is up to the [`Overflow`].
+
+use std::{
+    collections::VecDeque,
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc, Arc, Condvar, Mutex, PoisonError,
+    },
+    thread::JoinHandle,
+};
+
+use crate::{
+    drain::{Drain, WriteFn, MAX_BATCH},
+    rotator::{LogRotator, RotatorError},
+    LogWriter,
+};
+
+/// What happens to a record that finds the queue full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overflow {
+    /// Waits for the worker to make room.
+    Block,
+    /// Drops the record and counts it.
+    DropNewest,
+    /// Drops the oldest queued record to make room and counts it.
+    DropOldest,
+}
+
+type Message<W> = crate::drain::Message<Box<WriteFn<W>>>;
+
+struct Queue<W> {
+    messages: VecDeque<Message<W>>,
+    /// Records queued and not yet taken by the worker.
+    records: usize,
+    is_closed: bool,
+}
+
+struct Shared<W> {
+    queue: Mutex<Queue<W>>,
+    not_empty: Condvar,
+    not_full: Condvar,
+    capacity: usize,
+    overflow: Overflow,
+    dropped: AtomicU64,
+}
+
+/// Queues records for the worker; cloning it is cheap.
+pub struct NonBlockingLogger<W> {
+    shared: Arc<Shared<W>>,
+}
+impl<W> NonBlockingLogger<W>
+where
+    W: LogWriter + Send + 'static,
+{
+    /// Hands `rotator` to a new worker that takes up to `capacity` queued records.
+    pub fn spawn(
+        rotator: LogRotator<W>,
+        capacity: NonZeroUsize,
+        overflow: Overflow,
+    ) -> (Self, WorkerGuard<W>) {
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(Queue {
+                messages: VecDeque::new(),
+                records: 0,
+                is_closed: false,
+            }),
+            not_empty: Condvar::new(),
+            not_full: Condvar::new(),
+            capacity: capacity.get(),
+            overflow,
+            dropped: AtomicU64::new(0),
+        });
+        let worker = std::thread::Builder::new()
+            .name("NonBlockingLogger::drain()".to_string())
+            .spawn({
+                let shared = Arc::clone(&shared);
+                move || drain(&shared, rotator)
+            })
+            .expect("Failed to spawn the draining thread");
+        let guard = WorkerGuard {
+            shared: Arc::clone(&shared),
+            worker: Some(worker),
+        };
+        (Self { shared }, guard)
+    }
+}
+impl<W> NonBlockingLogger<W> {
+    /// Queues `write` to be called with the writer on the worker, counting one record.
+    ///
+    /// Records queued after the worker shut down are dropped.
+    pub fn write(&self, write: impl FnOnce(&mut W) + Send + 'static) {
+        let shared = &self.shared;
+        let mut queue = shared.queue.lock().unwrap();
+        if shared.overflow == Overflow::Block {
+            while shared.capacity <= queue.records && !queue.is_closed {
+                queue = shared.not_full.wait(queue).unwrap();
+            }
+        }
+        if queue.is_closed {
+            shared.dropped.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        if shared.capacity <= queue.records {
+            shared.dropped.fetch_add(1, Ordering::Relaxed);
+            match shared.overflow {
+                Overflow::Block => unreachable!(),
+                Overflow::DropNewest => return,
+                Overflow::DropOldest => {
+                    let oldest = queue
+                        .messages
+                        .iter()
+                        .position(|message| matches!(message, Message::Write(_)))
+                        .unwrap();
+                    queue.messages.remove(oldest);
+                    queue.records -= 1;
+                }
+            }
+        }
+        queue.messages.push_back(Message::Write(Box::new(write)));
+        queue.records += 1;
+        shared.not_empty.notify_one();
+    }
+
+    /// Waits for every record queued so far to be written, then flushes the rotator.
+    ///
+    /// Fails with the first rotation that failed since the previous flush, if any.
+    ///
+    /// Returns at once after the worker shut down, which flushes on the way out.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the worker panics before it gets to this flush.
+    pub fn flush(&self) -> Result<(), RotatorError> {
+        let (done, flushed) = mpsc::sync_channel(1);
+        {
+            let mut queue = self.shared.queue.lock().unwrap();
+            if queue.is_closed {
+                return Ok(());
+            }
+            queue.messages.push_back(Message::Flush(done));
+            self.shared.not_empty.notify_one();
+        }
+        flushed.recv().expect("The draining thread panicked")
+    }
+
+    /// Records dropped on overflow or after shutdown.
+    pub fn dropped(&self) -> u64 {
+        self.shared.dropped.load(Ordering::Relaxed)
+    }
+}
+impl<W> Clone for NonBlockingLogger<W> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+/// Shuts the worker down on drop, once it has written the records already queued.
+pub struct WorkerGuard<W>
+where
+    W: LogWriter,
+{
+    shared: Arc<Shared<W>>,
+    worker: Option<JoinHandle<LogRotator<W>>>,
+}
+impl<W> WorkerGuard<W>
+where
+    W: LogWriter,
+{
+    /// Like dropping the guard but gives the rotator back, whose next [`LogRotator::flush`]
+    /// returns the failures of the records written since the last flush, if any.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the worker has panicked.
+    pub fn shutdown(mut self) -> LogRotator<W> {
+        self.close();
+        let worker = self.worker.take().unwrap();
+        worker.join().expect("The draining thread panicked")
+    }
+
+    fn close(&self) {
+        let mut queue = self.shared.queue.lock().unwrap();
+        queue.is_closed = true;
+        self.shared.not_empty.notify_one();
+        self.shared.not_full.notify_all();
+    }
+}
+impl<W> Drop for WorkerGuard<W>
+where
+    W: LogWriter,
+{
+    fn drop(&mut self) {
+        if let Some(worker) = self.worker.take() {
+            self.close();
+            let _ = worker.join();
+        }
+    }
+}
+
+fn drain<W>(shared: &Shared<W>, mut rotator: LogRotator<W>) -> LogRotator<W>
+where
+    W: LogWriter,
+{
+    let _close = CloseOnExit(shared);
+    let mut drain = Drain::default();
+    loop {
+        let batch = {
+            let mut queue = shared.queue.lock().unwrap();
+            while queue.messages.is_empty() && !queue.is_closed {
+                queue = shared.not_empty.wait(queue).unwrap();
+            }
+            if queue.messages.is_empty() {
+                break;
+            }
+            let len = queue.messages.len().min(MAX_BATCH);
+            let batch = queue.messages.drain(..len).collect::<Vec<_>>();
+            let records = batch
+                .iter()
+                .filter(|message| matches!(message, Message::Write(_)))
+                .count();
+            queue.records -= records;
+            shared.not_full.notify_all();
+            batch
+        };
+        drain.write_batch(&mut rotator, batch);
+    }
+    if let Err(e) = drain.finish(&mut rotator) {
+        rotator.defer_error(e);
+    }
+    rotator
+}
+
+/// Closes the queue once the worker exits, even by panicking, so that blocked producers and
+/// flushes stop waiting on it.
+struct CloseOnExit<'a, W>(&'a Shared<W>);
+impl<W> Drop for CloseOnExit<'_, W> {
+    fn drop(&mut self) {
+        let shared = self.0;
+        let mut queue = shared.queue.lock().unwrap_or_else(PoisonError::into_inner);
+        queue.is_closed = true;
+        shared
+            .dropped
+            .fetch_add(queue.records as u64, Ordering::Relaxed);
+        queue.records = 0;
+        queue.messages.clear();
+        shared.not_empty.notify_all();
+        shared.not_full.notify_all();
+    }
+}
+
+impl<W> core::fmt::Debug for NonBlockingLogger<W> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("NonBlockingLogger")
+            .field("capacity", &self.shared.capacity)
+            .field("overflow", &self.shared.overflow)
+            .field("dropped", &self.dropped())
+            .finish()
+    }
+}
+impl<W> core::fmt::Debug for WorkerGuard<W>
+where
+    W: LogWriter,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("WorkerGuard")
+            .field("worker", &self.worker)
+            .finish()
+    }
+}
+
+#[cfg(all(test, feature = "csv"))]
+mod tests {
+    use serde::Serialize;
+
+    use crate::{
+        rotator::{RotationPolicy, RotatorOp},
+        writers::csv::CsvLogWriter,
+    };
+
+    use super::*;
+
+    #[derive(Debug, Serialize)]
+    struct Record {
+        n: usize,
+    }
+
+    fn log_contents(dir: &tempfile::TempDir) -> String {
+        std::fs::read_to_string(dir.path().join("0.csv")).unwrap()
+    }
+
+    #[test]
+    fn test_overflow() {
+        for (overflow, expected) in [
+            (Overflow::DropNewest, "n\n0\n1\n2\n3\n"),
+            (Overflow::DropOldest, "n\n3\n4\n5\n6\n"),
+        ] {
+            let dir = tempfile::tempdir().unwrap();
+            let rotation = RotationPolicy::default();
+            let rotator = LogRotator::<CsvLogWriter>::new_or_panic(dir.path().to_owned(), rotation);
+            let capacity = NonZeroUsize::new(4).unwrap();
+            let (logger, guard) = NonBlockingLogger::spawn(rotator, capacity, overflow);
+
+            // Holds the worker up until the queue has overflowed.
+            let (started, has_started) = mpsc::channel();
+            let (release, gate) = mpsc::channel::<()>();
+            logger.write(move |_| {
+                started.send(()).unwrap();
+                gate.recv().unwrap();
+            });
+            has_started.recv().unwrap();
+            for n in 0..7 {
+                logger.write(move |w| w.serialize(&Record { n }).unwrap());
+            }
+            assert_eq!(logger.dropped(), 3);
+            release.send(()).unwrap();
+            logger.flush().unwrap();
+            assert_eq!(log_contents(&dir), expected);
+
+            let rotator = guard.shutdown();
+            assert_eq!(rotator.metrics().records_total, 5);
+        }
+    }
+
+    #[test]
+    fn test_shutdown_drains() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy::default();
+        let rotator = LogRotator::<CsvLogWriter>::new_or_panic(dir.path().to_owned(), rotation);
+        let capacity = NonZeroUsize::new(1).unwrap();
+        let (logger, guard) = NonBlockingLogger::spawn(rotator, capacity, Overflow::Block);
+        std::thread::scope(|s| {
+            for producer in 0..4 {
+                let logger = logger.clone();
+                s.spawn(move || {
+                    for n in 0..100 {
+                        let n = producer * 100 + n;
+                        logger.write(move |w| w.serialize(&Record { n }).unwrap());
+                    }
+                });
+            }
+        });
+        drop(guard);
+        assert_eq!(logger.dropped(), 0);
+        assert_eq!(log_contents(&dir).lines().count(), 1 + 400);
+        logger.write(|w| w.serialize(&Record { n: 400 }).unwrap());
+        assert_eq!(logger.dropped(), 1);
+        logger.flush().unwrap();
+    }
+
+    #[test]
+    fn test_worker_panic() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy::default();
+        let rotator = LogRotator::<CsvLogWriter>::new_or_panic(dir.path().to_owned(), rotation);
+        let capacity = NonZeroUsize::new(1).unwrap();
+        let (logger, guard) = NonBlockingLogger::spawn(rotator, capacity, Overflow::Block);
+        logger.write(|_| panic!("Failed to write the record"));
+        for n in 0..10 {
+            logger.write(move |w| w.serialize(&Record { n }).unwrap());
+        }
+        assert!(8 <= logger.dropped());
+        logger.flush().unwrap();
+        drop(guard);
+    }
+
+    #[test]
+    fn test_failed_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy {
+            max_records: NonZeroUsize::new(1),
+            ..Default::default()
+        };
+        let rotator = LogRotator::<CsvLogWriter>::new_or_panic(dir.path().to_owned(), rotation);
+        // The next file cannot be opened where a directory stands.
+        std::fs::create_dir(dir.path().join("1.csv")).unwrap();
+        let capacity = NonZeroUsize::new(4).unwrap();
+        let (logger, guard) = NonBlockingLogger::spawn(rotator, capacity, Overflow::Block);
+        logger.write(|w| w.serialize(&Record { n: 0 }).unwrap());
+        let e = logger.flush().unwrap_err();
+        assert_eq!(e.op, RotatorOp::OpenLogFile);
+        logger.flush().unwrap();
+        drop(guard);
+    }
+}
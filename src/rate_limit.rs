@@ -0,0 +1,151 @@
+use std::{
+    num::{NonZeroU32, NonZeroU64},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crate::metrics::AdmissionMetrics;
+
+/// Token bucket refilled at `records_per_second` and holding at most `burst` records.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RateLimit {
+    pub records_per_second: NonZeroU32,
+    pub burst: NonZeroU32,
+    pub overflow: Overflow,
+}
+
+/// What happens to a record above the [`RateLimit`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Overflow {
+    /// Drops the record and counts it.
+    Drop,
+    /// Waits for the bucket to refill.
+    ///
+    /// [`TableHandle::write`] fails with the wait rather than sleeping on the distributor, which
+    /// may be locked; [`LogDistributor::write_blocking`] sleeps it out with the lock released.
+    ///
+    /// [`TableHandle::write`]: crate::distributor::TableHandle::write
+    /// [`LogDistributor::write_blocking`]: crate::distributor::LogDistributor::write_blocking
+    Block,
+    /// Writes the record to the named table instead, bypassing that table's limits.
+    RouteTo(Arc<str>),
+}
+
+/// A record of a table with [`Overflow::Block`] has to wait for the rate limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimited {
+    /// Until the bucket holds a token again.
+    pub wait: Duration,
+}
+impl core::fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "The table is rate limited for another {:?}", self.wait)
+    }
+}
+impl std::error::Error for RateLimited {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Admission {
+    Admit,
+    Drop,
+    SampleOut,
+    /// Asks to retry once the rate limit lets the record through.
+    Wait(Duration),
+    RouteTo(Arc<str>),
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TableAdmission {
+    rate_limit: Option<(RateLimit, TokenBucket)>,
+    keep_one_in: Option<NonZeroU64>,
+    seen: u64,
+    /// A record has been told to wait and not admitted since.
+    is_waiting: bool,
+    metrics: AdmissionMetrics,
+}
+impl TableAdmission {
+    pub fn set_rate_limit(&mut self, rate_limit: Option<RateLimit>) {
+        self.rate_limit = rate_limit.map(|r| {
+            let bucket = TokenBucket::new(&r);
+            (r, bucket)
+        });
+    }
+
+    pub fn set_sampling(&mut self, keep_one_in: Option<NonZeroU64>) {
+        self.keep_one_in = keep_one_in;
+        self.seen = 0;
+    }
+
+    pub fn metrics(&self) -> AdmissionMetrics {
+        self.metrics.clone()
+    }
+
+    /// Sampling is applied before the rate limit so that sampled-out records take no tokens.
+    pub fn admit(&mut self) -> Admission {
+        if let Some(keep_one_in) = self.keep_one_in {
+            let seen = self.seen;
+            self.seen += 1;
+            if !seen.is_multiple_of(keep_one_in.get()) {
+                self.metrics.sampled_out += 1;
+                return Admission::SampleOut;
+            }
+        }
+        let Some((rate_limit, bucket)) = &mut self.rate_limit else {
+            return Admission::Admit;
+        };
+        if bucket.try_take(Instant::now()) {
+            if core::mem::take(&mut self.is_waiting) {
+                self.metrics.blocked += 1;
+            }
+            return Admission::Admit;
+        }
+        match &rate_limit.overflow {
+            Overflow::Drop => {
+                self.metrics.dropped += 1;
+                Admission::Drop
+            }
+            Overflow::Block => {
+                self.is_waiting = true;
+                Admission::Wait(bucket.refill_wait())
+            }
+            Overflow::RouteTo(table_name) => {
+                self.metrics.routed += 1;
+                Admission::RouteTo(Arc::clone(table_name))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct TokenBucket {
+    capacity: f64,
+    per_second: f64,
+    tokens: f64,
+    last: Instant,
+}
+impl TokenBucket {
+    pub fn new(rate_limit: &RateLimit) -> Self {
+        let capacity = f64::from(rate_limit.burst.get());
+        Self {
+            capacity,
+            per_second: f64::from(rate_limit.records_per_second.get()),
+            tokens: capacity,
+            last: Instant::now(),
+        }
+    }
+
+    pub fn try_take(&mut self, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.per_second).min(self.capacity);
+        self.last = now;
+        if self.tokens < 1. {
+            return false;
+        }
+        self.tokens -= 1.;
+        true
+    }
+
+    pub fn refill_wait(&self) -> Duration {
+        Duration::from_secs_f64(((1. - self.tokens) / self.per_second).max(0.))
+    }
+}
@@ -0,0 +1,462 @@
+//! Reads the retained log files of an output directory back in epoch order.
+
+use std::{
+    fs::File,
+    io::Read,
+    marker::PhantomData,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
+
+use crate::{
+    compression::Compressor,
+    metadata::{EpochMetadata, Reconciliation},
+    rotator::{cur_epoch, epoch_bounds, list_named_log_files, FileNaming, LogFile, RotationPolicy},
+    LogWriter,
+};
+
+/// A log file along with its metadata.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EpochFile {
+    pub epoch: usize,
+    pub path: PathBuf,
+    /// The size on disk, compressed if the file is.
+    pub len: u64,
+    pub modified: SystemTime,
+}
+
+/// The uncompressed files named `<epoch>.<extension>` in an output directory, oldest epoch
+/// first, skipping the same files as [`LogReader`].
+///
+/// Files deleted while listing are left out.
+pub fn list_epochs(output_dir: impl AsRef<Path>, extension: &str) -> Vec<EpochFile> {
+    epoch_files(ordered_log_files(
+        output_dir,
+        extension,
+        None,
+        None,
+        &FileNaming::Epoch,
+    ))
+}
+
+/// Like [`list_epochs`] but for the files written by `W` under `rotation`, as named by its
+/// [`RotationPolicy::naming`], [`RotationPolicy::extension`] and [`RotationPolicy::instance`],
+/// and compressed by its [`RotationPolicy::compression`] or not.
+pub fn list_epochs_with_policy<W>(
+    output_dir: impl AsRef<Path>,
+    rotation: &RotationPolicy,
+) -> Vec<EpochFile>
+where
+    W: LogWriter,
+{
+    epoch_files(policy_log_files::<W>(output_dir, rotation))
+}
+
+/// Like [`list_epochs`] but without the file the rotator is writing, as told by the epoch file.
+///
+/// The newest file is taken for the current one if the epoch file is missing or unreadable.
+pub fn list_retired_epochs(output_dir: impl AsRef<Path>, extension: &str) -> Vec<EpochFile> {
+    let output_dir = output_dir.as_ref();
+    retired(output_dir, None, list_epochs(output_dir, extension))
+}
+
+/// Like [`list_retired_epochs`] but for the files listed by [`list_epochs_with_policy`].
+pub fn list_retired_epochs_with_policy<W>(
+    output_dir: impl AsRef<Path>,
+    rotation: &RotationPolicy,
+) -> Vec<EpochFile>
+where
+    W: LogWriter,
+{
+    let output_dir = output_dir.as_ref();
+    let files = list_epochs_with_policy::<W>(output_dir, rotation);
+    retired(output_dir, rotation.instance.as_deref(), files)
+}
+
+/// Like [`list_epochs_with_policy`] for a table of a [`crate::distributor::LogDistributor`]
+/// writing to `output_dir` under `rotation`, which is the shard directory of the table if the
+/// distributor is sharded.
+pub fn list_table_epochs<W>(
+    output_dir: impl AsRef<Path>,
+    table_name: &str,
+    rotation: &RotationPolicy,
+) -> Vec<EpochFile>
+where
+    W: LogWriter,
+{
+    list_epochs_with_policy::<W>(output_dir.as_ref().join(table_name), rotation)
+}
+
+fn epoch_files(files: Vec<LogFile>) -> Vec<EpochFile> {
+    files
+        .into_iter()
+        .filter_map(|file| {
+            let metadata = std::fs::metadata(&file.path).ok()?;
+            Some(EpochFile {
+                epoch: file.epoch,
+                path: file.path,
+                len: metadata.len(),
+                modified: metadata.modified().ok()?,
+            })
+        })
+        .collect()
+}
+
+fn retired(output_dir: &Path, instance: Option<&str>, mut files: Vec<EpochFile>) -> Vec<EpochFile> {
+    match cur_epoch(output_dir, instance).ok().flatten() {
+        Some(epoch) => files.retain(|file| file.epoch != epoch),
+        None => drop(files.pop()),
+    }
+    files
+}
+
+fn policy_log_files<W>(output_dir: impl AsRef<Path>, rotation: &RotationPolicy) -> Vec<LogFile>
+where
+    W: LogWriter,
+{
+    ordered_log_files(
+        output_dir,
+        rotation.file_extension::<W>(),
+        rotation.compression.as_deref().map(|c| c.extension()),
+        rotation.instance.as_deref(),
+        &rotation.naming,
+    )
+}
+
+/// The log files of `instance`, oldest epoch first.
+///
+/// A file being compressed is listed uncompressed until the compressed one replaces it.
+fn ordered_log_files(
+    output_dir: impl AsRef<Path>,
+    extension: &str,
+    compression: Option<&str>,
+    instance: Option<&str>,
+    naming: &FileNaming,
+) -> Vec<LogFile> {
+    let mut files = list_named_log_files(output_dir, extension, compression, naming)
+        .into_iter()
+        .filter(|file| file.instance.as_deref() == instance)
+        .collect::<Vec<_>>();
+    files.sort_unstable_by_key(|file| (file.epoch, is_compressed(&file.path, compression)));
+    files.dedup_by_key(|file| file.epoch);
+    let epochs = files.iter().map(|file| file.epoch).collect::<Vec<_>>();
+    if let Some((oldest, _)) = epoch_bounds(&epochs) {
+        let start = epochs.binary_search(&oldest).unwrap();
+        files.rotate_left(start);
+    }
+    files
+}
+
+fn is_compressed(path: &Path, compression: Option<&str>) -> bool {
+    compression.is_some_and(|compression| {
+        path.extension()
+            .is_some_and(|extension| extension == compression)
+    })
+}
+
+/// Decodes the records of a file written by `W`.
+pub trait LogReaderFor<W>: Sized
+where
+    W: LogWriter,
+{
+    fn read_records(reader: impl std::io::Read) -> impl Iterator<Item = std::io::Result<Self>>;
+}
+
+/// The log files of `W` in an output directory, oldest epoch first.
+///
+/// Epochs missing from retention are skipped, as are the epoch file and the files of other
+/// instances.
+/// The files of a [`crate::distributor::LogDistributor`] table are in its subdirectory.
+#[derive(Debug, Clone)]
+pub struct LogReader<W> {
+    files: Vec<LogFile>,
+    compression: Option<Arc<dyn Compressor>>,
+    _writer: PhantomData<fn() -> W>,
+}
+impl<W> LogReader<W>
+where
+    W: LogWriter,
+{
+    /// Reads the uncompressed files of the default [`RotationPolicy`].
+    pub fn new(output_dir: impl AsRef<Path>) -> Self {
+        Self::with_policy(output_dir, &RotationPolicy::default())
+    }
+
+    /// Like [`Self::new`] but for the files written under `rotation`, as named by its
+    /// [`RotationPolicy::naming`], [`RotationPolicy::extension`] and
+    /// [`RotationPolicy::instance`].
+    ///
+    /// The files compressed by its [`RotationPolicy::compression`] are read through
+    /// [`Compressor::decompress`].
+    pub fn with_policy(output_dir: impl AsRef<Path>, rotation: &RotationPolicy) -> Self {
+        Self {
+            files: policy_log_files::<W>(output_dir, rotation),
+            compression: rotation.compression.clone(),
+            _writer: PhantomData,
+        }
+    }
+
+    pub fn files(&self) -> &[LogFile] {
+        &self.files
+    }
+
+    /// Opens the files one after another, decompressing the compressed ones.
+    pub fn open_files(&self) -> impl Iterator<Item = std::io::Result<Box<dyn Read + Send>>> + '_ {
+        self.files.iter().map(|file| self.open(file))
+    }
+
+    fn open(&self, file: &LogFile) -> std::io::Result<Box<dyn Read + Send>> {
+        match self.compressor(file) {
+            Some(compressor) => compressor.decompress(&file.path),
+            None => Ok(Box::new(File::open(&file.path)?)),
+        }
+    }
+
+    fn compressor(&self, file: &LogFile) -> Option<&dyn Compressor> {
+        let compressor = self.compression.as_deref()?;
+        is_compressed(&file.path, Some(compressor.extension())).then_some(compressor)
+    }
+
+    fn read_metadata(&self, file: &LogFile) -> std::io::Result<EpochMetadata> {
+        let compression = self.compressor(file).map(|c| c.extension());
+        EpochMetadata::read(&file.path, compression)
+    }
+
+    /// Decodes the records of all the files in order.
+    ///
+    /// A file that cannot be opened yields its error in place of its records.
+    pub fn records<'a, R>(&'a self) -> impl Iterator<Item = std::io::Result<R>> + 'a
+    where
+        R: LogReaderFor<W> + 'a,
+    {
+        self.open_files().flat_map(|file| {
+            let (records, error) = match file {
+                Ok(file) => (Some(R::read_records(file)), None),
+                Err(e) => (None, Some(Err(e))),
+            };
+            records.into_iter().flatten().chain(error)
+        })
+    }
+
+    /// The sidecar of each file by epoch, in the order of [`Self::files`], which the files
+    /// retired under [`RotationPolicy::write_metadata`] have.
+    pub fn metadata(&self) -> impl Iterator<Item = (usize, std::io::Result<EpochMetadata>)> + '_ {
+        self.files
+            .iter()
+            .map(|file| (file.epoch, self.read_metadata(file)))
+    }
+
+    /// Decodes each file to count its data records against its sidecar, which the files
+    /// retired under [`RotationPolicy::write_metadata`] have.
+    pub fn reconcile<'a, R>(&'a self) -> impl Iterator<Item = Reconciliation> + 'a
+    where
+        R: LogReaderFor<W> + 'a,
+    {
+        self.files.iter().map(|file| {
+            let records = self.open(file).and_then(|file| {
+                R::read_records(file).try_fold(0, |records, record| record.map(|_| records + 1))
+            });
+            Reconciliation {
+                epoch: file.epoch,
+                path: file.path.clone(),
+                metadata: self.read_metadata(file).ok(),
+                records,
+            }
+        })
+    }
+}
+
+#[cfg(all(test, feature = "csv"))]
+mod tests {
+    use std::{
+        num::NonZeroUsize,
+        sync::{Arc, Mutex},
+    };
+
+    use serde::{Deserialize, Serialize};
+
+    use crate::{
+        rotator::{LogRotator, RotationPolicy},
+        writers::csv::CsvLogWriter,
+    };
+
+    use super::*;
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+    struct Record {
+        n: usize,
+    }
+
+    #[test]
+    fn test_records() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy {
+            max_records: NonZeroUsize::new(2),
+            max_epochs: 3,
+            ..Default::default()
+        };
+        let rotator = LogRotator::<CsvLogWriter>::new_or_panic(dir.path().to_owned(), rotation);
+        let rotator = Arc::new(Mutex::new(rotator));
+        for n in 0..9 {
+            let mut rotator = rotator.lock().unwrap();
+            rotator.writer().serialize(&Record { n }).unwrap();
+            rotator.try_incr_record_count().unwrap();
+        }
+        rotator.lock().unwrap().flush_or_panic();
+
+        let reader = LogReader::<CsvLogWriter>::new(dir.path());
+        let epochs = reader.files().iter().map(|f| f.epoch).collect::<Vec<_>>();
+        assert_eq!(epochs, [2, 3, 4]);
+        let records = reader.records::<Record>().map(Result::unwrap);
+        let records = records.map(|r| r.n).collect::<Vec<_>>();
+        assert_eq!(records, [4, 5, 6, 7, 8]);
+
+        std::fs::remove_file(&reader.files()[1].path).unwrap();
+        let reader = LogReader::<CsvLogWriter>::new(dir.path());
+        let records = reader.records::<Record>().map(Result::unwrap);
+        let records = records.map(|r| r.n).collect::<Vec<_>>();
+        assert_eq!(records, [4, 5, 8]);
+    }
+
+    #[test]
+    fn test_with_policy() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy {
+            max_records: NonZeroUsize::new(2),
+            max_epochs: usize::MAX,
+            instance: Some("svc".to_string()),
+            extension: Some("v1.csv".to_string()),
+            ..Default::default()
+        };
+        let mut rotator =
+            LogRotator::<CsvLogWriter>::new_or_panic(dir.path().to_owned(), rotation.clone());
+        for n in 0..5 {
+            rotator.writer().serialize(&Record { n }).unwrap();
+            rotator.try_incr_record_count().unwrap();
+        }
+        rotator.flush_or_panic();
+        std::fs::write(dir.path().join("0.csv"), "n\n9\n").unwrap();
+
+        let reader = LogReader::<CsvLogWriter>::with_policy(dir.path(), &rotation);
+        let epochs = reader.files().iter().map(|f| f.epoch).collect::<Vec<_>>();
+        assert_eq!(epochs, [0, 1, 2]);
+        let records = reader.records::<Record>().map(Result::unwrap);
+        let records = records.map(|r| r.n).collect::<Vec<_>>();
+        assert_eq!(records, [0, 1, 2, 3, 4]);
+        let reader = LogReader::<CsvLogWriter>::new(dir.path());
+        let records = reader.records::<Record>().map(Result::unwrap);
+        assert_eq!(records.map(|r| r.n).collect::<Vec<_>>(), [9]);
+    }
+
+    #[test]
+    fn test_list_epochs() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy {
+            max_records: NonZeroUsize::new(2),
+            max_epochs: 4,
+            ..Default::default()
+        };
+        let mut rotator =
+            LogRotator::<CsvLogWriter>::new_or_panic(dir.path().to_owned(), rotation.clone());
+        for n in 0..9 {
+            rotator.writer().serialize(&Record { n }).unwrap();
+            rotator.try_incr_record_count().unwrap();
+        }
+        rotator.flush_or_panic();
+        std::fs::remove_file(dir.path().join("2.csv")).unwrap();
+        std::fs::write(dir.path().join("notes.csv"), "").unwrap();
+        std::fs::write(dir.path().join("5.txt"), "").unwrap();
+
+        let files = list_epochs(dir.path(), "csv");
+        let epochs = files.iter().map(|f| f.epoch).collect::<Vec<_>>();
+        assert_eq!(epochs, [1, 3, 4]);
+        for file in &files {
+            assert_eq!(file.path, dir.path().join(format!("{}.csv", file.epoch)));
+            assert_eq!(file.len, std::fs::metadata(&file.path).unwrap().len());
+        }
+        let retired = list_retired_epochs(dir.path(), "csv");
+        let epochs = retired.iter().map(|f| f.epoch).collect::<Vec<_>>();
+        assert_eq!(epochs, [1, 3]);
+
+        let mut distributor = crate::distributor::LogDistributor::<CsvLogWriter>::new(
+            dir.path().join("d"),
+            rotation.clone(),
+        );
+        for n in 0..3 {
+            distributor
+                .table_handle("t")
+                .write(|w| w.serialize(&Record { n }).unwrap())
+                .unwrap();
+        }
+        distributor.flush().unwrap();
+        let files = list_table_epochs::<CsvLogWriter>(dir.path().join("d"), "t", &rotation);
+        let epochs = files.iter().map(|f| f.epoch).collect::<Vec<_>>();
+        assert_eq!(epochs, [0, 1]);
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_compressed() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy {
+            max_records: NonZeroUsize::new(2),
+            max_epochs: usize::MAX,
+            compression: Some(Arc::new(crate::compression::Gzip::default())),
+            write_metadata: true,
+            ..Default::default()
+        };
+        let mut rotator =
+            LogRotator::<CsvLogWriter>::new_or_panic(dir.path().to_owned(), rotation.clone());
+        for n in 0..5 {
+            rotator.writer().serialize(&Record { n }).unwrap();
+            rotator.try_incr_record_count().unwrap();
+        }
+        rotator.wait_for_compression().unwrap();
+        rotator.flush_or_panic();
+
+        let files = list_epochs_with_policy::<CsvLogWriter>(dir.path(), &rotation);
+        let paths = files.iter().map(|f| f.path.clone()).collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            [
+                dir.path().join("0.csv.gz"),
+                dir.path().join("1.csv.gz"),
+                dir.path().join("2.csv")
+            ]
+        );
+        let retired = list_retired_epochs_with_policy::<CsvLogWriter>(dir.path(), &rotation);
+        assert_eq!(retired.len(), 2);
+        let files = list_epochs(dir.path(), "csv");
+        assert_eq!(files.iter().map(|f| f.epoch).collect::<Vec<_>>(), [2]);
+
+        let reader = LogReader::<CsvLogWriter>::with_policy(dir.path(), &rotation);
+        let records = reader.records::<Record>().map(Result::unwrap);
+        let records = records.map(|r| r.n).collect::<Vec<_>>();
+        assert_eq!(records, [0, 1, 2, 3, 4]);
+        let metadata = reader.metadata().map(|(_, m)| m.map(|m| m.records));
+        let metadata = metadata.map(Result::ok).collect::<Vec<_>>();
+        assert_eq!(metadata, [Some(2), Some(2), None]);
+        let reconciled = reader.reconcile::<Record>().collect::<Vec<_>>();
+        assert!(reconciled[..2].iter().all(Reconciliation::is_exact));
+
+        #[derive(Debug)]
+        struct Opaque;
+        impl Compressor for Opaque {
+            fn compress(&self, _src: &Path, _dst: &Path) -> std::io::Result<()> {
+                unreachable!()
+            }
+
+            fn extension(&self) -> &str {
+                "gz"
+            }
+        }
+        let rotation = RotationPolicy {
+            compression: Some(Arc::new(Opaque)),
+            ..rotation
+        };
+        let reader = LogReader::<CsvLogWriter>::with_policy(dir.path(), &rotation);
+        let error = reader.records::<Record>().next().unwrap().unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::Unsupported);
+    }
+}
@@ -1,337 +1,5173 @@
 use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet},
     io::{Read, Write},
-    num::NonZeroUsize,
+    num::{NonZeroU64, NonZeroUsize},
+    ops::RangeInclusive,
     path::{Path, PathBuf},
-    sync::{Arc, Mutex},
-    time::Duration,
+    sync::{
+        atomic::{self, AtomicBool},
+        mpsc, Arc, Condvar, Mutex,
+    },
+    time::{Duration, SystemTime},
 };
 
-use crate::{table::Table, time_past::TimePast, LogWriter};
+use crate::{
+    clock::{Clock, SystemClock},
+    compression::{compressed_path, Compressor},
+    cron::Cron,
+    flusher::{AdaptiveInterval, Backoff, FlushIntervalError, FlusherHandle},
+    metadata::{is_metadata_file_name, metadata_path, EpochMetadata},
+    metrics::RotatorMetrics,
+    table::Table,
+    time_past::{DailyContains, TimePast},
+    LogWriter,
+};
+
+/// Intervals outside of [`MIN_FLUSH_INTERVAL`]`..=`[`MAX_FLUSH_INTERVAL`] are clamped, as told by
+/// [`RotatorFlusherHandle::clamped`]; see [`try_spawn_flushers`] to reject them instead.
+///
+/// [`MIN_FLUSH_INTERVAL`]: crate::flusher::MIN_FLUSH_INTERVAL
+/// [`MAX_FLUSH_INTERVAL`]: crate::flusher::MAX_FLUSH_INTERVAL
+pub fn spawn_flushers<W>(
+    rotators: Vec<Arc<Mutex<LogRotator<W>>>>,
+    flush_interval: Duration,
+) -> RotatorFlusherHandle<W>
+where
+    W: LogWriter + Sync + Send + 'static,
+{
+    spawn_flushers_adaptive(rotators, AdaptiveInterval::fixed(flush_interval))
+}
+
+pub fn try_spawn_flushers<W>(
+    rotators: Vec<Arc<Mutex<LogRotator<W>>>>,
+    flush_interval: Duration,
+) -> Result<RotatorFlusherHandle<W>, FlushIntervalError>
+where
+    W: LogWriter + Sync + Send + 'static,
+{
+    try_spawn_flushers_adaptive(rotators, AdaptiveInterval::fixed(flush_interval))
+}
+
+pub fn try_spawn_flushers_adaptive<W>(
+    rotators: Vec<Arc<Mutex<LogRotator<W>>>>,
+    flush_interval: AdaptiveInterval,
+) -> Result<RotatorFlusherHandle<W>, FlushIntervalError>
+where
+    W: LogWriter + Sync + Send + 'static,
+{
+    flush_interval.validate()?;
+    Ok(spawn_flushers_adaptive(rotators, flush_interval))
+}
 
-pub fn spawn_flushers<W>(rotators: Vec<Arc<Mutex<LogRotator<W>>>>, flush_interval: Duration)
+/// Like [`spawn_flushers`] but backs off while none of the rotators is dirty.
+pub fn spawn_flushers_adaptive<W>(
+    rotators: Vec<Arc<Mutex<LogRotator<W>>>>,
+    flush_interval: AdaptiveInterval,
+) -> RotatorFlusherHandle<W>
 where
     W: LogWriter + Sync + Send + 'static,
 {
-    std::thread::Builder::new()
+    let shutdown = Arc::new(AtomicBool::new(false));
+    let weak_rotators = rotators.iter().map(Arc::downgrade).collect::<Vec<_>>();
+    let weak_rotators = Arc::new(Mutex::new(weak_rotators));
+    let polls_time = rotators
+        .iter()
+        .any(|rotator| rotator.lock().unwrap().rotation.has_time_trigger());
+    let flusher = std::thread::Builder::new()
         .name("LogRotator::flush()".to_string())
         .spawn({
-            let mut rotators = rotators.iter().map(Arc::downgrade).collect::<Vec<_>>();
+            let rotators = Arc::clone(&weak_rotators);
+            let mut backoff = Backoff::new(flush_interval);
+            backoff.set_polls_time(polls_time);
+            let shutdown = Arc::clone(&shutdown);
             move || loop {
-                std::thread::sleep(flush_interval);
-                let mut i = 0;
-                while let Some(rotator) = rotators.get(i) {
-                    let Some(rotator) = rotator.upgrade() else {
-                        rotators.swap_remove(i);
-                        continue;
-                    };
-                    i += 1;
-                    let mut rotator = rotator.lock().unwrap();
-                    rotator.flush();
-                    rotator.try_rotate_file();
+                std::thread::park_timeout(backoff.timeout());
+                let is_shut_down = shutdown.load(atomic::Ordering::Relaxed);
+                let flush = is_shut_down || backoff.is_flush_due();
+                let mut live = rotators.lock().unwrap();
+                let (is_dirty, polls_time) = flush_rotators(&mut live, flush);
+                // Rotators may still be added while the handle is alive.
+                let is_done = live.is_empty() && Arc::strong_count(&rotators) == 1;
+                if is_shut_down || is_done {
+                    return;
+                }
+                drop(live);
+                if flush {
+                    backoff.tick(is_dirty);
                 }
+                backoff.set_polls_time(polls_time);
             }
         })
         .expect("Failed to spawn the flushing worker thread");
+    for rotator in &rotators {
+        let mut rotator = rotator.lock().unwrap();
+        rotator.flushers.push(flusher.thread().clone());
+    }
+    RotatorFlusherHandle {
+        handle: FlusherHandle::new(shutdown, flusher, &flush_interval),
+        rotators: weak_rotators,
+    }
+}
+
+/// A [`FlusherHandle`] that also takes rotators created after the thread was spawned.
+///
+/// The thread keeps running while the handle is alive, even with no rotators left.
+#[derive(Debug)]
+pub struct RotatorFlusherHandle<W>
+where
+    W: LogWriter,
+{
+    handle: FlusherHandle,
+    rotators: Arc<Mutex<WeakRotators<W>>>,
+}
+type WeakRotators<W> = Vec<std::sync::Weak<Mutex<LogRotator<W>>>>;
+impl<W> RotatorFlusherHandle<W>
+where
+    W: LogWriter,
+{
+    /// Flushes `rotator` along with the others until it is dropped.
+    pub fn add(&self, rotator: Arc<Mutex<LogRotator<W>>>) {
+        self.rotators.lock().unwrap().push(Arc::downgrade(&rotator));
+        let thread = self.handle.thread().clone();
+        let mut rotator = rotator.lock().unwrap();
+        rotator.flushers.push(thread.clone());
+        if rotator.rotation.has_time_trigger() {
+            // Shortens the current wait.
+            thread.unpark();
+        }
+    }
+
+    /// Like [`FlusherHandle::clamped`].
+    pub fn clamped(&self) -> Option<FlushIntervalError> {
+        self.handle.clamped()
+    }
+
+    /// Like [`FlusherHandle::is_finished`].
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    /// Like [`FlusherHandle::shutdown`].
+    pub fn shutdown(self) {
+        self.handle.shutdown();
+    }
+}
+
+/// Like [`spawn_flushers`] but runs as a tokio task.
+///
+/// Flushing happens on the blocking thread pool. The task ends once `cancel` fires or all the
+/// rotators are dropped.
+///
+/// The interval is clamped like that of [`spawn_flushers`] but without telling; check it with
+/// [`AdaptiveInterval::validate`] beforehand.
+#[cfg(feature = "tokio")]
+pub fn spawn_flushers_async<W>(
+    rotators: Vec<Arc<Mutex<LogRotator<W>>>>,
+    flush_interval: Duration,
+    cancel: tokio_util::sync::CancellationToken,
+) -> tokio::task::JoinHandle<()>
+where
+    W: LogWriter + Sync + Send + 'static,
+{
+    let flush_interval = AdaptiveInterval::fixed(flush_interval).clamped().base;
+    let mut rotators = rotators.iter().map(Arc::downgrade).collect::<Vec<_>>();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(flush_interval);
+        interval.tick().await;
+        loop {
+            tokio::select! {
+                _ = cancel.cancelled() => return,
+                _ = interval.tick() => (),
+            }
+            rotators = tokio::task::spawn_blocking(move || {
+                flush_rotators(&mut rotators, true);
+                rotators
+            })
+            .await
+            .expect("The flushing task panicked");
+            if rotators.is_empty() {
+                return;
+            }
+        }
+    })
+}
+
+/// Rotates the rotators still alive on every `SIGHUP`, like [`LogRotator::rotate_now`], until
+/// the handle is dropped.
+///
+/// Failures are returned by the next [`LogRotator::flush`] of the rotator.
+#[cfg(all(unix, feature = "signal"))]
+pub fn rotate_on_sighup<W>(
+    rotators: Vec<Arc<Mutex<LogRotator<W>>>>,
+    skip_empty: bool,
+) -> std::io::Result<crate::signal::SighupHandle>
+where
+    W: LogWriter + Send + 'static,
+{
+    let rotators = rotators.iter().map(Arc::downgrade).collect::<Vec<_>>();
+    crate::signal::register(Box::new(move || {
+        for rotator in rotators.iter().filter_map(std::sync::Weak::upgrade) {
+            let mut rotator = rotator.lock().unwrap();
+            if let Err(e) = rotator.rotate_now(skip_empty) {
+                rotator.defer_error(e);
+            }
+        }
+    }))
+}
+
+/// Flushes, if `flush`, and rotates the live rotators, forgetting the dropped ones, and tells if
+/// any of them was dirty and if any of them has a time trigger.
+fn flush_rotators<W>(rotators: &mut WeakRotators<W>, flush: bool) -> (bool, bool)
+where
+    W: LogWriter,
+{
+    let mut is_dirty = false;
+    let mut polls_time = false;
+    let mut i = 0;
+    while let Some(rotator) = rotators.get(i) {
+        let Some(rotator) = rotator.upgrade() else {
+            rotators.swap_remove(i);
+            continue;
+        };
+        i += 1;
+        let mut rotator = rotator.lock().unwrap();
+        is_dirty |= rotator.is_dirty();
+        polls_time |= rotator.rotation.has_time_trigger();
+        rotator.flusher_tick(flush);
+    }
+    (is_dirty, polls_time)
 }
 
+/// Flushes its writer on drop.
+///
+/// Epochs wrap around from `usize::MAX` to 0. Epochs are ordered modulo the epoch range, so an
+/// epoch up to half the range behind another is older than it; a file of a previous cycle at
+/// the next epoch is overwritten.
 #[derive(Debug)]
-pub struct LogRotator<W> {
+pub struct LogRotator<W>
+where
+    W: LogWriter,
+{
     output_dir: PathBuf,
+    /// Path of the current file.
+    path: PathBuf,
     table: Table<W>,
     rotation: RotationPolicy,
+    rotations: u64,
+    last_rotation: Option<jiff::Timestamp>,
+    startup_report: ConsistencyReport,
+    watermark: Option<jiff::Zoned>,
+    watermark_start: Option<jiff::Zoned>,
+    watermark_regressions: u64,
+    compression: Option<std::thread::JoinHandle<Result<(), RotatorError>>>,
+    /// The last background compression failure not yet returned by
+    /// [`Self::wait_for_compression`].
+    compression_error: Option<RotatorError>,
+    compression_failures: u64,
+    /// The last failure of a call that could not return it, not yet returned by [`Self::flush`].
+    deferred_error: Option<RotatorError>,
+    deferred_failures: u64,
+    compression_gate: Option<Arc<CompressionGate>>,
+    /// Flusher threads to wake up on drop.
+    flushers: Vec<std::thread::Thread>,
+    on_rotate: Option<RotationHook>,
+    /// Epochs of retired files that retention leaves alone until released, or until the hook
+    /// declines them.
+    claimed: Arc<Mutex<BTreeSet<usize>>>,
+    /// [`LogWriter::schema`] of the last retired file that had one.
+    retired_schema: Option<u64>,
+    /// Records the distributor discarded for the table while the current file was open.
+    dropped: u64,
+    sampled_out: u64,
 }
 impl<W> LogRotator<W>
 where
     W: LogWriter,
 {
-    pub fn new(output_dir: PathBuf, rotation: RotationPolicy) -> Self {
-        let epoch = cur_epoch(&output_dir)
-            .map(|e| e.wrapping_add(1))
-            .unwrap_or_default();
-        let path = log_file_path(&output_dir, epoch, W::file_extension());
-        let writer = create_clean_log_writer(path);
-        let table = Table::new(writer, epoch);
+    pub fn new(output_dir: PathBuf, rotation: RotationPolicy) -> Result<Self, RotatorError> {
+        let instance = rotation.instance.as_deref();
+        let persisted_epoch = cur_epoch(&output_dir, instance)?;
+        if let Some(compressor) = rotation.compression.as_deref() {
+            finish_interrupted_compression(
+                &output_dir,
+                &rotation,
+                rotation.file_extension::<W>(),
+                compressor,
+            )?;
+        }
+        let mut startup_report = ConsistencyReport::inspect(
+            &output_dir,
+            &rotation,
+            rotation.file_extension::<W>(),
+            persisted_epoch,
+            persisted_epoch,
+        );
+        // Without the epoch file, carry on after the newest file rather than truncating epoch 0.
+        let last_epoch = persisted_epoch.or(newest_epoch(&startup_report.present_epochs));
+        let mut epoch = last_epoch.map(|e| e.wrapping_add(1)).unwrap_or_default();
+        let mut resumed = None;
+        if rotation.repair_on_startup && startup_report.epoch_mismatch {
+            epoch = startup_report.repair_epoch();
+            startup_report.repaired_epoch = Some(epoch);
+        } else if let (true, Some(last_epoch)) = (rotation.resume, last_epoch) {
+            resumed = resume_log_writer(&output_dir, last_epoch, &rotation)?;
+        }
+        if let (None, Some(last_epoch)) = (persisted_epoch, last_epoch) {
+            let resumed_epoch = resumed.as_ref().map(|_| last_epoch);
+            startup_report.repaired_epoch = Some(resumed_epoch.unwrap_or(epoch));
+        }
+        let (path, table) = match resumed {
+            Some((path, writer, records_written)) => {
+                let table = Table::resume(writer, last_epoch.unwrap(), records_written);
+                (path, table)
+            }
+            None => {
+                let path = new_log_file_path(
+                    &output_dir,
+                    epoch,
+                    &rotation,
+                    rotation.file_extension::<W>(),
+                );
+                let (writer, aux_records) = create_clean_log_writer(&path, &rotation)?;
+                let mut table = Table::new(writer, epoch);
+                table.add_aux_records(aux_records);
+                (path, table)
+            }
+        };
 
         let mut this = Self {
             output_dir,
+            path,
             table,
             rotation,
+            rotations: 0,
+            last_rotation: None,
+            startup_report,
+            watermark: None,
+            watermark_start: None,
+            watermark_regressions: 0,
+            compression: None,
+            compression_error: None,
+            compression_failures: 0,
+            deferred_error: None,
+            deferred_failures: 0,
+            compression_gate: None,
+            flushers: vec![],
+            on_rotate: None,
+            claimed: Arc::default(),
+            retired_schema: None,
+            dropped: 0,
+            sampled_out: 0,
         };
 
-        this.enforce_epoch();
+        this.update_current_link(true)?;
+        this.enforce_epoch()?;
 
-        this
+        Ok(this)
     }
 
-    pub fn flush(&mut self) {
-        self.table.flush();
+    /// Builds a rotator along with its flusher, starting from [`RotationPolicy::builder`].
+    pub fn builder(output_dir: impl Into<PathBuf>) -> LogRotatorBuilder<W> {
+        LogRotatorBuilder {
+            output_dir: output_dir.into(),
+            policy: RotationPolicy::builder(),
+            flush_interval: DEFAULT_FLUSH_INTERVAL,
+            _writer: std::marker::PhantomData,
+        }
     }
 
-    pub fn writer(&mut self) -> &mut W {
-        self.table.writer()
+    /// # Panics
+    ///
+    /// Panics if the output directory cannot be set up; see [`Self::new`].
+    pub fn new_or_panic(output_dir: PathBuf, rotation: RotationPolicy) -> Self {
+        Self::new(output_dir, rotation).expect("Failed to create the log rotator")
     }
 
-    pub fn incr_record_count(&mut self) {
-        self.table.incr_record_count();
+    /// Calls `on_rotate` with each retired file, once the next file is open and the retired one
+    /// is compressed, if compression is on.
+    ///
+    /// The callback runs in order on a thread of its own, outside any lock guarding the rotator,
+    /// so it may log to the same rotator. Returning `true` claims the retired file, which
+    /// retention then skips until [`Self::release`]; until the callback returns, retention skips
+    /// it as well. [`Self::shutdown`] with `finalize` retires the current file the same way.
+    ///
+    /// Replaces the previous callback, which still receives the rotations before this call.
+    pub fn set_on_rotate(&mut self, on_rotate: Box<dyn FnMut(&RotationEvent) -> bool + Send>) {
+        let hook = RotationHook::spawn(on_rotate, Arc::clone(&self.claimed));
+        // The previous callback may be waiting for a lock held around this call.
+        if let Some(mut previous) = self.on_rotate.replace(hook) {
+            previous.thread.take();
+        }
+    }
 
-        self.try_rotate_file();
+    /// Counts a record dropped by the rate limit of the distributor for the next
+    /// [`RotationEvent`].
+    pub(crate) fn incr_dropped_count(&mut self) {
+        self.dropped += 1;
     }
 
-    pub fn try_rotate_file(&mut self) {
-        let is_max_records_triggered = match self.rotation.max_records {
-            Some(max_records) => max_records.get() <= self.table.records_written(),
-            None => false,
-        };
-        let is_time_triggered = match &mut self.rotation.time {
-            Some(time_past) => time_past.poll(jiff::Zoned::now()),
-            None => false,
-        };
-        let should_rotate = is_max_records_triggered || is_time_triggered;
-        if !should_rotate {
-            return;
+    /// Counts a record discarded by the sampling of the distributor for the next
+    /// [`RotationEvent`].
+    pub(crate) fn incr_sampled_out_count(&mut self) {
+        self.sampled_out += 1;
+    }
+
+    /// Waits for the callback of [`Self::set_on_rotate`] to handle the rotations so far.
+    #[cfg(test)]
+    pub(crate) fn wait_for_on_rotate(&mut self) {
+        self.join_compression();
+        if let Some(on_rotate) = &self.on_rotate {
+            let (done, handled) = mpsc::channel();
+            on_rotate.sender.send(HookMessage::Handled(done));
+            let _ = handled.recv();
         }
+    }
 
-        self.replace_writer();
-        self.enforce_epoch();
+    /// Lets retention delete the file of `epoch` claimed by the [`Self::set_on_rotate`]
+    /// callback, from the next rotation on.
+    pub fn release(&mut self, epoch: usize) {
+        self.claimed.lock().unwrap().remove(&epoch);
     }
 
-    fn replace_writer(&mut self) {
-        let new_path = log_file_path(
-            &self.output_dir,
-            self.table.epoch().wrapping_add(1),
-            W::file_extension(),
-        );
-        let new_writer = create_clean_log_writer(new_path);
-        self.table.replace(new_writer);
+    /// Also fails with the last failure of a call that could not return it, such as
+    /// [`Self::writer`], a flusher or a signal rotation, if the file itself flushed.
+    pub fn flush(&mut self) -> Result<(), RotatorError> {
+        self.flush_file()?;
+        match self.deferred_error.take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
     }
 
-    fn enforce_epoch(&mut self) {
-        let epoch = self.table.epoch();
-        write_epoch(&self.output_dir, epoch);
-        delete_old_log_file(
-            epoch,
-            self.rotation.max_epochs,
-            &self.output_dir,
-            W::file_extension(),
-        );
+    fn flush_file(&mut self) -> Result<(), RotatorError> {
+        let is_dirty = self.table.is_dirty();
+        self.table
+            .flush()
+            .map_err(|e| RotatorError::new(RotatorOp::Flush, &self.path, e))?;
+        if is_dirty && self.rotation.durability.syncs_on_flush() {
+            self.table
+                .sync()
+                .map_err(|e| RotatorError::new(RotatorOp::Sync, &self.path, e))?;
+        }
+        Ok(())
     }
-}
 
-#[derive(Debug, Clone)]
-pub struct RotationPolicy {
-    pub max_records: Option<NonZeroUsize>,
-    pub time: Option<TimePast>,
-    pub max_epochs: usize,
-}
+    pub fn flush_or_panic(&mut self) {
+        self.flush().expect("Failed to flush the log file");
+    }
 
-fn delete_old_log_file(
-    epoch: usize,
-    max_epochs: usize,
-    output_dir: impl AsRef<Path>,
-    extension: &str,
-) {
-    let del_epoch = epoch.wrapping_sub(max_epochs);
-    let del_path = log_file_path(output_dir, del_epoch, extension);
-    if del_path.exists() {
-        std::fs::remove_file(del_path).expect("Failed to remove outdated log file");
+    /// Flushes the current file and syncs it and the epoch file to disk.
+    ///
+    /// With `finalize`, the current file is also retired like on rotation, footer and compression
+    /// included, but no next file is opened.
+    pub fn shutdown(mut self, finalize: bool) -> Result<(), RotatorError> {
+        self.wait_for_compression()?;
+        if finalize {
+            self.close_writer()?;
+        }
+        self.flush()?;
+        let path = self.path.clone();
+        sync_file(&path)?;
+        let instance = self.rotation.instance.as_deref();
+        write_epoch(&self.output_dir, instance, self.table.epoch())?;
+        sync_file(&epoch_file_path(&self.output_dir, instance))?;
+        if !finalize {
+            return Ok(());
+        }
+        let path = match self.rotation.compression.as_deref() {
+            Some(compressor) => {
+                let dst = reserve_compressed_file(&path, compressor)?;
+                compress_log_file(&path, &dst, compressor)?;
+                dst
+            }
+            None => path,
+        };
+        let schema_changed = self.schema_changed();
+        if let Some(on_rotate) = &self.on_rotate {
+            let old_epoch = self.table.epoch();
+            on_rotate.sender.send(HookMessage::Rotated(RotationEvent {
+                old_epoch,
+                old_path: path,
+                new_epoch: old_epoch.wrapping_add(1),
+                new_path: None,
+                schema_changed,
+                dropped: self.dropped,
+                sampled_out: self.sampled_out,
+            }));
+        }
+        Ok(())
     }
-}
 
-fn create_clean_log_writer<W>(path: impl AsRef<Path>) -> W
-where
-    W: LogWriter,
-{
-    std::fs::create_dir_all(path.as_ref().parent().unwrap()).expect("Failed to create directories");
-    W::open(path)
-}
+    /// With [`RotationPolicy::reopen_if_missing`], first calls [`Self::ensure_open`], leaving
+    /// failures to the next [`Self::flush`]; see [`Self::try_writer`] to get them at once.
+    pub fn writer(&mut self) -> &mut W {
+        if self.rotation.reopen_if_missing {
+            if let Err(e) = self.ensure_open() {
+                self.defer_error(e);
+            }
+        }
+        self.table.writer()
+    }
 
-fn write_epoch(output_dir: impl AsRef<Path>, epoch: usize) {
-    let path = epoch_file_path(output_dir);
-    std::fs::create_dir_all(path.parent().unwrap()).expect("Failed to create directories");
-    let mut file = std::fs::File::options()
-        .create(true)
-        .truncate(true)
-        .write(true)
-        .open(path)
-        .expect("Failed to create an epoch file");
-    file.write_all(epoch.to_string().as_bytes())
-        .expect("Failed to write epoch to the file");
-}
+    /// Like [`Self::writer`] but fails if the current file cannot be recreated.
+    pub fn try_writer(&mut self) -> Result<&mut W, RotatorError> {
+        if self.rotation.reopen_if_missing {
+            self.ensure_open()?;
+        }
+        Ok(self.table.writer())
+    }
 
-fn cur_epoch(output_dir: impl AsRef<Path>) -> Option<usize> {
-    let path = epoch_file_path(output_dir);
-    if !path.exists() {
-        return None;
+    /// Keeps `e`, the failure of a call that could not return it, for [`Self::flush`].
+    pub(crate) fn defer_error(&mut self, e: RotatorError) {
+        self.deferred_failures += 1;
+        self.deferred_error = Some(e);
     }
-    let mut file = std::fs::File::options()
-        .read(true)
-        .open(&path)
-        .expect("Failed to open the epoch file");
-    let mut epoch = String::new();
-    file.read_to_string(&mut epoch)
-        .expect("Failed to read the epoch file");
-    let epoch: usize = match epoch.parse() {
-        Ok(epoch) => epoch,
-        Err(_) => {
-            std::fs::remove_file(&path).expect("Failed to delete old epoch file");
-            return None;
+
+    /// Recreates the current file if it has been deleted, e.g. by a cleanup script.
+    ///
+    /// The new file starts over in the same epoch, so records still buffered for the deleted
+    /// file are lost and [`Self::records_written`] is reset.
+    pub fn ensure_open(&mut self) -> Result<(), RotatorError> {
+        if self.path.exists() {
+            return Ok(());
         }
-    };
-    Some(epoch)
-}
+        let (writer, aux_records) = create_clean_log_writer(&self.path, &self.rotation)?;
+        let epoch = self.table.epoch();
+        self.table.replace(writer, epoch);
+        self.table.add_aux_records(aux_records);
+        Ok(())
+    }
 
-fn epoch_file_path(output_dir: impl AsRef<Path>) -> PathBuf {
-    output_dir.as_ref().join("epoch")
-}
+    pub(crate) fn writer_ref(&self) -> &W {
+        self.table.writer_ref()
+    }
 
-fn log_file_path(output_dir: impl AsRef<Path>, epoch: usize, extension: &str) -> PathBuf {
-    let mut path = output_dir.as_ref().join(epoch.to_string());
-    path.set_extension(extension);
-    path
-}
+    /// Whether anything may have been written since the last flush.
+    pub fn is_dirty(&self) -> bool {
+        self.table.is_dirty()
+    }
 
-#[cfg(test)]
-mod tests {
-    use std::{
-        io::Read,
-        sync::{Arc, Mutex},
-    };
+    /// Counts a data record and tells if it rotated the file.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a rotation is due and fails; see [`Self::try_incr_record_count`].
+    pub fn incr_record_count_or_panic(&mut self) -> bool {
+        self.try_incr_record_count()
+            .expect("Failed to rotate the log file")
+    }
 
-    use serde::Serialize;
+    /// Counts a data record and tells if it rotated the file, reporting a failed rotation, after
+    /// which records keep going to the current file until a later rotation succeeds.
+    ///
+    /// Also fails, once the record is counted and any rotation done, if the
+    /// [`LogWriter::schema`] of the current file has changed since it was resumed or since its
+    /// first record, and keeps failing until the next file.
+    pub fn try_incr_record_count(&mut self) -> Result<bool, RotatorError> {
+        let schema = self
+            .table
+            .check_schema()
+            .map_err(|e| RotatorError::new(RotatorOp::CheckSchema, &self.path, e));
+        self.table.incr_record_count();
 
-    use super::*;
+        let rotated = match self.is_max_bytes_triggered() {
+            true => self.rotate().map(|()| true),
+            false => self.try_rotate_file(),
+        };
+        schema?;
+        rotated
+    }
 
-    struct CsvLogWriter {
-        writer: csv::Writer<std::fs::File>,
+    /// Counts a non-data row such as a banner or a footer.
+    ///
+    /// Auxiliary rows never count towards [`RotationPolicy::max_records`].
+    pub fn incr_aux_record_count(&mut self) {
+        self.table.incr_aux_record_count();
     }
-    impl CsvLogWriter {
-        pub fn writer(&mut self) -> &mut csv::Writer<std::fs::File> {
-            &mut self.writer
+
+    /// Writes the records one by one, rotating exactly at [`RotationPolicy::max_records`].
+    ///
+    /// Returns the number of data records committed. Stops at the first failed rotation, with
+    /// the record that triggered it committed to the current file.
+    pub fn write_batch<R>(
+        &mut self,
+        records: impl IntoIterator<Item = R>,
+        mut write: impl FnMut(&mut W, R),
+    ) -> Result<usize, RotatorError> {
+        let mut committed = 0;
+        for record in records {
+            write(self.writer(), record);
+            self.try_incr_record_count()?;
+            committed += 1;
         }
+        Ok(committed)
     }
-    impl LogWriter for CsvLogWriter {
-        fn flush(&mut self) {
-            self.writer.flush().unwrap();
+
+    /// Feeds the event time of the records, rotating once it crosses a boundary of
+    /// [`RotationPolicy::watermark`].
+    ///
+    /// Tells if it rotated the file. A watermark behind the previous one is ignored and counted.
+    ///
+    /// On a failed rotation the watermark still advances and records keep going to the current
+    /// file.
+    pub fn advance_watermark(&mut self, t: jiff::Zoned) -> Result<bool, RotatorError> {
+        if self.watermark.as_ref().is_some_and(|prev| t < *prev) {
+            self.watermark_regressions += 1;
+            return Ok(false);
         }
+        let is_crossed = match &mut self.rotation.watermark {
+            Some(time_past) => time_past.poll(t.clone()),
+            None => false,
+        };
+        let rotated = if is_crossed { self.rotate() } else { Ok(()) };
+        self.watermark_start.get_or_insert_with(|| t.clone());
+        self.watermark = Some(t);
+        rotated.map(|()| is_crossed)
+    }
+
+    pub fn records_written(&self) -> usize {
+        self.table.records_written()
+    }
+
+    /// The file being written.
+    pub fn current_path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Epoch of the file being written.
+    pub(crate) fn epoch(&self) -> usize {
+        self.table.epoch()
+    }
+
+    /// Bytes written to the current file, through [`LogWriter::bytes_written`] so that unflushed
+    /// records count too, or else as found on disk.
+    pub fn current_file_bytes(&mut self) -> u64 {
+        self.bytes_written()
+    }
+
+    /// Records counted since the rotator was created, unlike [`Self::records_written`] which
+    /// only counts those of the current file.
+    pub fn lifetime_records(&self) -> u64 {
+        self.table.lifetime_records()
+    }
+
+    /// Auxiliary rows of the current file, such as a banner; see [`Self::incr_aux_record_count`].
+    pub fn aux_records(&self) -> usize {
+        self.table.aux_records()
+    }
+
+    /// Auxiliary rows counted since the rotator was created, including the footers and trailers
+    /// of the retired files.
+    pub fn lifetime_aux_records(&self) -> u64 {
+        self.table.lifetime_aux_records()
+    }
 
-        fn open(path: impl AsRef<Path>) -> Self {
-            let file = std::fs::File::options()
-                .write(true)
-                .create(true)
-                .truncate(true)
-                .open(path)
-                .expect("Cannot create a log file");
-            let writer = csv::Writer::from_writer(file);
-            Self { writer }
+    /// Snapshot of the counters, e.g. to export as gauges.
+    ///
+    /// Only [`RotatorMetrics::bytes`] touches the filesystem, with a single `stat`.
+    pub fn metrics(&self) -> RotatorMetrics {
+        let path = &self.path;
+        RotatorMetrics {
+            epoch: self.table.epoch(),
+            records_current: self.table.records_written(),
+            records_total: self.lifetime_records(),
+            aux_records_current: self.table.aux_records(),
+            aux_records_total: self.lifetime_aux_records(),
+            bytes: std::fs::metadata(path).ok().map(|m| m.len()),
+            rotations: self.rotations,
+            last_rotation: self.last_rotation,
+            watermark_start: self.watermark_start.as_ref().map(|t| t.timestamp()),
+            watermark_end: self
+                .watermark_start
+                .as_ref()
+                .and(self.watermark.as_ref())
+                .map(|t| t.timestamp()),
+            watermark_regressions: self.watermark_regressions,
+            compression_failures: self.compression_failures,
+            deferred_failures: self.deferred_failures,
         }
+    }
 
-        fn file_extension() -> &'static str {
-            "csv"
+    /// The consistency pass taken before the first file was opened.
+    pub fn startup_report(&self) -> &ConsistencyReport {
+        &self.startup_report
+    }
+
+    /// Compares the epoch file, the log files on disk, and the retention window.
+    ///
+    /// With `repair`, a disagreement is fixed by rotating to the epoch after the newest file
+    /// present and rewriting the epoch file. Repairs never delete files.
+    pub fn check_consistency(&mut self, repair: bool) -> Result<ConsistencyReport, RotatorError> {
+        let instance = self.rotation.instance.as_deref();
+        let mut report = ConsistencyReport::inspect(
+            &self.output_dir,
+            &self.rotation,
+            self.rotation.file_extension::<W>(),
+            cur_epoch(&self.output_dir, instance)?,
+            Some(self.table.epoch()),
+        );
+        if repair && report.epoch_mismatch {
+            let next_epoch = self.table.epoch().wrapping_add(1);
+            let epoch = match report.repair_epoch() {
+                epoch if is_ahead(epoch, next_epoch) => epoch,
+                _ => next_epoch,
+            };
+            self.replace_writer(epoch)?;
+            write_epoch(&self.output_dir, self.rotation.instance.as_deref(), epoch)?;
+            report.repaired_epoch = Some(epoch);
         }
+        Ok(report)
     }
 
-    struct Logger {
-        rotator: Arc<Mutex<LogRotator<CsvLogWriter>>>,
+    /// Rotates if any trigger of the [`RotationPolicy`] fires, and tells if it did.
+    ///
+    /// On failure, keeps writing to the current file and retries on the next call.
+    pub fn try_rotate_file(&mut self) -> Result<bool, RotatorError> {
+        self.try_rotate_file_at(self.rotation.now())
     }
-    impl Logger {
-        pub fn new(rotator: Arc<Mutex<LogRotator<CsvLogWriter>>>) -> Self {
-            Self { rotator }
+
+    /// Like [`Self::try_rotate_file`] but polls [`RotationPolicy::time`] at `now`.
+    pub fn try_rotate_file_at(&mut self, now: jiff::Zoned) -> Result<bool, RotatorError> {
+        let is_max_records_triggered = self.is_max_records_reached();
+        let is_time_triggered = match &mut self.rotation.time {
+            Some(time_past) => time_past.poll(now.clone()),
+            None => false,
+        };
+        let is_cron_triggered = match &mut self.rotation.cron {
+            Some(cron) => cron.edge_triggered_poll(now),
+            None => false,
+        };
+        let should_rotate = is_max_records_triggered || is_time_triggered || is_cron_triggered;
+        if !should_rotate {
+            return Ok(false);
         }
 
-        pub fn write<R>(&self, record: &R)
-        where
-            R: Serialize,
-        {
-            let mut rotator = self.rotator.lock().unwrap();
-            rotator.writer().writer().serialize(record).unwrap();
-            rotator.incr_record_count();
+        self.rotate()?;
+        Ok(true)
+    }
+
+    /// Flushes if `flush` and dirty and polls the triggers that can fire without a write,
+    /// leaving failures to the next [`Self::flush`].
+    ///
+    /// An idle rotator without a time trigger costs no IO, so that a flusher can walk many of
+    /// them each tick.
+    pub(crate) fn flusher_tick(&mut self, flush: bool) {
+        if flush && self.is_dirty() {
+            if let Err(e) = self.flush_file() {
+                self.defer_error(e);
+            }
         }
+        // A rotation left for the next try by a failure of `try_incr_record_count` is retried.
+        if self.rotation.has_time_trigger() || self.is_max_records_reached() {
+            if let Err(e) = self.try_rotate_file() {
+                self.defer_error(e);
+            }
+        }
+    }
 
-        pub fn flush(&self) {
-            self.rotator.lock().unwrap().flush();
+    fn is_max_records_reached(&self) -> bool {
+        match self.rotation.max_records {
+            Some(max_records) => max_records.get() <= self.table.records_written(),
+            None => false,
         }
     }
 
-    #[derive(Serialize)]
-    struct TestRecord {
-        pub s: &'static str,
-        pub n: usize,
+    /// Rotates regardless of the triggers, e.g. on a signal while all of them are off, and tells
+    /// if it did.
+    ///
+    /// With `skip_empty`, a file without data records is kept so that repeated calls do not churn
+    /// through retention.
+    pub fn rotate_now(&mut self, skip_empty: bool) -> Result<bool, RotatorError> {
+        if skip_empty && self.table.records_written() == 0 {
+            return Ok(false);
+        }
+        self.rotate()?;
+        Ok(true)
     }
 
-    #[test]
-    fn test_logger() {
-        let dir = tempfile::tempdir().unwrap();
-        let log_rotator = LogRotator::new(
-            dir.path().to_owned(),
-            RotationPolicy {
-                max_records: Some(NonZeroUsize::new(2).unwrap()),
-                time: None,
-                max_epochs: 2,
-            },
-        );
-        let logger = Logger::new(Arc::new(Mutex::new(log_rotator)));
-        logger.write(&TestRecord { s: "a", n: 0 });
-        logger.write(&TestRecord { s: "b", n: 1 });
-        logger.flush();
-        let path = log_file_path(dir.path(), 0, "csv");
-        assert!(path.exists());
-        let mut file = std::fs::File::options().read(true).open(path).unwrap();
-        let mut csv = String::new();
-        file.read_to_string(&mut csv).unwrap();
-        assert_eq!(
-            csv,
-            r#"s,n
-a,0
-b,1
-"#
-        );
+    pub fn try_rotate_file_or_panic(&mut self) -> bool {
+        self.try_rotate_file()
+            .expect("Failed to rotate the log file")
     }
 
-    #[test]
-    fn test_rotation() {
-        let dir = tempfile::tempdir().unwrap();
-        let rotator = LogRotator::new(
-            dir.path().to_owned(),
-            RotationPolicy {
-                max_records: Some(NonZeroUsize::new(2).unwrap()),
-                time: None,
-                max_epochs: 2,
-            },
-        );
-        let logger = Logger::new(Arc::new(Mutex::new(rotator)));
+    /// Only checked as a record is committed so that a file never ends in a partial record.
+    fn is_max_bytes_triggered(&mut self) -> bool {
+        match self.rotation.max_bytes {
+            // A file never rotates before its first record, however large its header.
+            Some(max_bytes) if self.table.records_written() != 0 => {
+                max_bytes.get() <= self.bytes_written()
+            }
+            _ => false,
+        }
+    }
 
-        logger.write(&TestRecord { s: "a", n: 0 });
-        logger.flush();
-        let path = log_file_path(dir.path(), 0, "csv");
-        assert!(path.exists());
-        let path = log_file_path(dir.path(), 1, "csv");
-        assert!(!path.exists());
+    fn bytes_written(&mut self) -> u64 {
+        if let Some(bytes) = self.table.bytes_written() {
+            return bytes;
+        }
+        std::fs::metadata(&self.path)
+            .map(|m| m.len())
+            .unwrap_or_default()
+    }
 
-        logger.write(&TestRecord { s: "b", n: 1 });
-        let path = log_file_path(dir.path(), 0, "csv");
-        assert!(path.exists());
-        let path = log_file_path(dir.path(), 1, "csv");
+    fn rotate(&mut self) -> Result<(), RotatorError> {
+        let old_epoch = self.table.epoch();
+        self.replace_writer(old_epoch.wrapping_add(1))?;
+        self.enforce_epoch()?;
+        self.rotations += 1;
+        self.last_rotation = Some(self.rotation.now().timestamp());
+        self.watermark_start = None;
+        Ok(())
+    }
+
+    /// Writes the footer, if any, lets the writer finish the current file, and writes its
+    /// sidecar, if any.
+    fn close_writer(&mut self) -> Result<(), RotatorError> {
+        let close = |e| RotatorError::new(RotatorOp::Close, &self.path, e);
+        if self.rotation.write_footer {
+            let records_written = self.table.records_written();
+            self.table
+                .writer()
+                .write_footer(records_written)
+                .map_err(close)?;
+            self.table.incr_aux_record_count();
+        }
+        let aux_records = self.table.writer().on_close().map_err(close)?;
+        self.table.add_aux_records(aux_records);
+        if !self.rotation.write_metadata {
+            return Ok(());
+        }
+        let metadata = EpochMetadata {
+            records: self.table.records_written(),
+            aux_records: self.table.aux_records(),
+            schema: self.table.writer_ref().schema(),
+            schema_text: self.table.writer_ref().schema_text(),
+        };
+        metadata
+            .write(&self.path)
+            .map_err(|e| RotatorError::new(RotatorOp::WriteMetadata, &self.path, e))
+    }
+
+    /// Tells if the [`LogWriter::schema`] of the current file differs from that of the file
+    /// retired before it, when both are known, and remembers the current one as retired.
+    fn schema_changed(&mut self) -> bool {
+        let schema = self.table.writer_ref().schema();
+        let schema_changed = match (self.retired_schema, schema) {
+            (Some(retired), Some(schema)) => retired != schema,
+            _ => false,
+        };
+        if schema.is_some() {
+            self.retired_schema = schema;
+        }
+        schema_changed
+    }
+
+    /// Keeps the current file, footer-less, if the next one cannot be opened, and also if the
+    /// current one cannot be finished.
+    fn replace_writer(&mut self, epoch: usize) -> Result<(), RotatorError> {
+        let old_path = self.path.clone();
+        let old_epoch = self.table.epoch();
+        let compressed_path = self
+            .rotation
+            .compression
+            .as_deref()
+            .map(|c| reserve_compressed_file(&old_path, c))
+            .transpose()?;
+        let new_path = new_log_file_path(
+            &self.output_dir,
+            epoch,
+            &self.rotation,
+            self.rotation.file_extension::<W>(),
+        );
+        let (new_writer, aux_records) = create_clean_log_writer(&new_path, &self.rotation)?;
+        self.close_writer()?;
+        let schema_changed = self.schema_changed();
+        self.table.replace(new_writer, epoch);
+        self.table.add_aux_records(aux_records);
+        self.path = new_path;
+        if self.rotation.durability.syncs_on_rotate() {
+            sync_file(&old_path)?;
+        }
+        self.update_current_link(false)?;
+        let event = RotationEvent {
+            old_epoch,
+            old_path: compressed_path.clone().unwrap_or_else(|| old_path.clone()),
+            new_epoch: epoch,
+            new_path: Some(self.path.clone()),
+            schema_changed,
+            dropped: core::mem::take(&mut self.dropped),
+            sampled_out: core::mem::take(&mut self.sampled_out),
+        };
+        // Retention skips the retired file until the hook has decided on it.
+        let on_rotate = self.on_rotate.as_ref().map(|on_rotate| {
+            self.claimed.lock().unwrap().insert(old_epoch);
+            on_rotate.sender.clone()
+        });
+        let compressor = self.rotation.compression.clone();
+        let (Some(compressor), Some(dst)) = (compressor, compressed_path) else {
+            if let Some(on_rotate) = on_rotate {
+                on_rotate.send(HookMessage::Rotated(event));
+            }
+            return Ok(());
+        };
+        self.join_compression();
+        let gate = self.compression_gate.clone();
+        let compression = std::thread::Builder::new()
+            .name("LogRotator::compress()".to_string())
+            .spawn(move || {
+                let _running = gate.as_deref().map(CompressionGate::enter);
+                let compressed = compress_log_file(&old_path, &dst, compressor.as_ref());
+                if let Some(on_rotate) = on_rotate {
+                    match &compressed {
+                        Ok(()) => on_rotate.send(HookMessage::Rotated(event)),
+                        Err(_) => on_rotate.decline(old_epoch),
+                    }
+                }
+                compressed
+            })
+            .map_err(|e| RotatorError::new(RotatorOp::Compress, &self.output_dir, e))?;
+        self.compression = Some(compression);
+        Ok(())
+    }
+
+    /// Also removes the link left by a run with [`RotationPolicy::current_link`] on startup if
+    /// it is unset, which would otherwise point at a retired file.
+    fn update_current_link(&self, is_startup: bool) -> Result<(), RotatorError> {
+        if !self.rotation.current_link && !is_startup {
+            return Ok(());
+        }
+        let instance = self.rotation.instance.as_deref();
+        let link = current_link_path(
+            &self.output_dir,
+            instance,
+            self.rotation.file_extension::<W>(),
+        );
+        let linked = match self.rotation.current_link {
+            true => replace_current_link(&link, &self.path),
+            false => remove_current_link(&link),
+        };
+        linked.map_err(|e| RotatorError::new(RotatorOp::Link, &link, e))
+    }
+
+    /// Waits for the file retired by the last rotation to be compressed.
+    ///
+    /// Compression runs in the background, one file at a time: each rotation first waits for
+    /// the previous file. Fails with the last compression that failed since the previous call,
+    /// including those the rotations waited for. Dropping the rotator ignores it, while
+    /// [`Self::shutdown`] returns it.
+    pub fn wait_for_compression(&mut self) -> Result<(), RotatorError> {
+        self.join_compression();
+        match self.compression_error.take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    /// Has the background compression of each retired file wait for `gate` to open.
+    pub(crate) fn set_compression_gate(&mut self, gate: Arc<CompressionGate>) {
+        self.compression_gate = Some(gate);
+    }
+
+    /// Keeps the failure of the background compression, if any, for
+    /// [`Self::wait_for_compression`].
+    fn join_compression(&mut self) {
+        let Some(compression) = self.compression.take() else {
+            return;
+        };
+        if let Err(e) = compression.join().expect("The compression thread panicked") {
+            self.compression_failures += 1;
+            self.compression_error = Some(e);
+        }
+    }
+
+    fn enforce_epoch(&mut self) -> Result<(), RotatorError> {
+        let epoch = self.table.epoch();
+        let instance = self.rotation.instance.as_deref();
+        write_epoch(&self.output_dir, instance, epoch)?;
+        if self.rotation.durability.syncs_on_rotate() {
+            sync_dir(&self.output_dir)?;
+        }
+        self.delete_old_files()?;
+        if self.rotation.durability.syncs_on_rotate() {
+            sync_dir(&self.output_dir)?;
+        }
+        Ok(())
+    }
+
+    fn delete_old_files(&mut self) -> Result<(), RotatorError> {
+        let epoch = self.table.epoch();
+        let instance = self.rotation.instance.as_deref();
+        match &self.rotation.retention {
+            RetentionOrder::Epoch => match instance {
+                None => {
+                    let mut kept = self.claimed.lock().unwrap().clone();
+                    kept.extend(self.compressing_epoch());
+                    delete_old_log_files(
+                        epoch,
+                        self.rotation.max_epochs,
+                        &self.output_dir,
+                        self.rotation.file_extension::<W>(),
+                        self.rotation.compression.as_deref(),
+                        &self.rotation.naming,
+                        &kept,
+                    )
+                }
+                Some(_) => self.delete_oldest_instance_files(),
+            },
+            RetentionOrder::Mtime => self.delete_oldest_log_files(compare_mtime),
+            RetentionOrder::Custom(cmp) => self.delete_oldest_log_files(|a, b| cmp(a, b)),
+        }?;
+        if let Some(max_age) = self.rotation.max_age {
+            self.delete_expired_log_files(max_age)?;
+        }
+        match self.rotation.max_total_bytes {
+            Some(max_total_bytes) => self.delete_log_files_over_budget(max_total_bytes.get()),
+            None => Ok(()),
+        }
+    }
+
+    /// The epoch of this instance whose retired file is still being compressed in the
+    /// background, or waits to be.
+    fn compressing_epoch(&self) -> Option<usize> {
+        self.compression
+            .is_some()
+            .then(|| self.table.epoch().wrapping_sub(1))
+    }
+
+    fn is_compressing(&self, file: &LogFile) -> bool {
+        self.compressing_epoch() == Some(file.epoch) && file.instance == self.rotation.instance
+    }
+
+    fn is_claimed(&self, file: &LogFile) -> bool {
+        file.instance == self.rotation.instance
+            && self.claimed.lock().unwrap().contains(&file.epoch)
+    }
+
+    fn is_current_file(&self, file: &LogFile) -> Result<bool, RotatorError> {
+        if file.path == self.path {
+            return Ok(true);
+        }
+        let instance = file.instance.as_deref();
+        Ok(instance.is_some() && cur_epoch(&self.output_dir, instance)? == Some(file.epoch))
+    }
+
+    /// Deletes the epochs whose log files were all last modified before `max_age` ago, except the
+    /// current files of all instances and the file still being compressed.
+    fn delete_expired_log_files(&self, max_age: jiff::Span) -> Result<(), RotatorError> {
+        // No file is that old if the cutoff precedes the supported time range.
+        let Ok(cutoff) = self.rotation.now().checked_sub(max_age) else {
+            return Ok(());
+        };
+        let cutoff = cutoff.timestamp();
+        for group in self.log_file_groups() {
+            if self.is_protected(&group)? {
+                continue;
+            }
+            let is_expired = group.iter().all(|file| {
+                let modified = std::fs::metadata(&file.path)
+                    .and_then(|m| m.modified())
+                    .ok()
+                    .and_then(|t| jiff::Timestamp::try_from(t).ok());
+                modified.is_some_and(|t| t < cutoff)
+            });
+            if is_expired {
+                group
+                    .iter()
+                    .try_for_each(|file| self.remove_log_file(&file.path))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Deletes the log files furthest behind the current epoch of their instance until the files
+    /// of all instances total at most `max_total_bytes`, except the current files and the file
+    /// still being compressed.
+    ///
+    /// The current epoch of another instance is read from its epoch file, or else taken to be its
+    /// newest.
+    fn delete_log_files_over_budget(&self, max_total_bytes: u64) -> Result<(), RotatorError> {
+        let mut files = list_named_log_files(
+            &self.output_dir,
+            self.rotation.file_extension::<W>(),
+            self.rotation.compression_extension(),
+            &self.rotation.naming,
+        )
+        .into_iter()
+        .map(|file| {
+            let len = std::fs::metadata(&file.path).map(|m| m.len()).unwrap_or(0);
+            (file, len)
+        })
+        .collect::<Vec<_>>();
+        let mut cur_epochs = BTreeMap::from([(self.rotation.instance.clone(), self.table.epoch())]);
+        for (file, _) in &files {
+            if cur_epochs.contains_key(&file.instance) {
+                continue;
+            }
+            let newest = || {
+                let epochs = files.iter().filter(|(f, _)| f.instance == file.instance);
+                epochs.map(|(f, _)| f.epoch).max().unwrap_or(file.epoch)
+            };
+            let epoch = cur_epoch(&self.output_dir, file.instance.as_deref())?;
+            cur_epochs.insert(file.instance.clone(), epoch.unwrap_or_else(newest));
+        }
+        let age = |file: &LogFile| cur_epochs[&file.instance].wrapping_sub(file.epoch) as isize;
+        files.sort_by(|(a, _), (b, _)| (age(b), &a.instance).cmp(&(age(a), &b.instance)));
+        let mut total = files.iter().map(|(_, len)| len).sum::<u64>();
+        for (file, len) in files {
+            if total <= max_total_bytes {
+                break;
+            }
+            if self.is_compressing(&file)
+                || self.is_claimed(&file)
+                || self.is_current_file(&file)?
+            {
+                continue;
+            }
+            self.remove_log_file(&file.path)?;
+            total -= len;
+        }
+        Ok(())
+    }
+
+    /// Treats the files of all instances as one table and never deletes any instance's current
+    /// file.
+    fn delete_oldest_instance_files(&self) -> Result<(), RotatorError> {
+        let groups = self.log_file_groups();
+        let excess = groups.len().saturating_sub(self.rotation.max_epochs);
+        let mut deleted = 0;
+        for group in &groups {
+            if deleted == excess {
+                break;
+            }
+            if self.is_protected(group)? {
+                continue;
+            }
+            group
+                .iter()
+                .try_for_each(|file| self.remove_log_file(&file.path))?;
+            deleted += 1;
+        }
+        Ok(())
+    }
+
+    /// Deletes the oldest epochs of this instance by `cmp` so that, along with the current file,
+    /// at most [`RotationPolicy::max_epochs`] remain.
+    ///
+    /// The claimed epochs do not count, and the one still being compressed counts but is kept.
+    fn delete_oldest_log_files(
+        &self,
+        cmp: impl Fn(&Path, &Path) -> Ordering,
+    ) -> Result<(), RotatorError> {
+        let mut groups = self
+            .log_file_groups()
+            .into_iter()
+            .filter(|group| group[0].instance == self.rotation.instance)
+            .filter(|group| {
+                let is_current = group.iter().any(|file| file.path == self.path);
+                !is_current && !self.is_claimed(&group[0])
+            })
+            .map(|mut group| {
+                group.sort_by(|a, b| cmp(&a.path, &b.path));
+                group
+            })
+            .collect::<Vec<_>>();
+        // An epoch is as new as its newest file, e.g. a compressed file written after rotation.
+        let newest = |group: &[LogFile]| group.last().unwrap().path.clone();
+        groups.sort_by(|a, b| cmp(&newest(a), &newest(b)));
+        let keep = self.rotation.max_epochs.saturating_sub(1);
+        let excess = groups.len().saturating_sub(keep);
+        groups
+            .iter()
+            .filter(|group| !self.is_compressing(&group[0]))
+            .take(excess)
+            .flatten()
+            .try_for_each(|file| self.remove_log_file(&file.path))
+    }
+
+    /// The log files of all instances, with the files of the same instance and epoch, e.g. a
+    /// retired file and its compressed counterpart, grouped as one, in the order of
+    /// [`list_named_log_files`].
+    fn log_file_groups(&self) -> Vec<Vec<LogFile>> {
+        let files = list_named_log_files(
+            &self.output_dir,
+            self.rotation.file_extension::<W>(),
+            self.rotation.compression_extension(),
+            &self.rotation.naming,
+        );
+        let mut groups: Vec<Vec<LogFile>> = vec![];
+        for file in files {
+            let group = groups
+                .iter_mut()
+                .find(|group| group[0].epoch == file.epoch && group[0].instance == file.instance);
+            match group {
+                Some(group) => group.push(file),
+                None => groups.push(vec![file]),
+            }
+        }
+        groups
+    }
+
+    /// Removes a log file, compressed or not, along with its sidecar, if any.
+    fn remove_log_file(&self, path: &Path) -> Result<(), RotatorError> {
+        remove_log_file(path, self.rotation.compression_extension())
+    }
+
+    /// The epoch of `group` is claimed, still being compressed, or current to any instance.
+    fn is_protected(&self, group: &[LogFile]) -> Result<bool, RotatorError> {
+        let file = &group[0];
+        if self.is_claimed(file) || self.is_compressing(file) {
+            return Ok(true);
+        }
+        for file in group {
+            if self.is_current_file(file)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+}
+
+impl<W> Drop for LogRotator<W>
+where
+    W: LogWriter,
+{
+    /// Failures are ignored, like those of [`std::io::BufWriter`] on drop; see
+    /// [`LogRotator::shutdown`] to get them.
+    fn drop(&mut self) {
+        let _ = self.flush_file();
+        if let Some(compression) = self.compression.take() {
+            let _ = compression.join();
+        }
+        self.flushers.iter().for_each(|flusher| flusher.unpark());
+    }
+}
+
+/// A rotation, as seen by [`LogRotator::set_on_rotate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RotationEvent {
+    pub old_epoch: usize,
+    /// Path of the retired file, compressed if compression is on.
+    pub old_path: PathBuf,
+    pub new_epoch: usize,
+    /// `None` if [`LogRotator::shutdown`] retired the file without opening another one.
+    pub new_path: Option<PathBuf>,
+    /// The [`LogWriter::schema`] of the retired file differs from that of the file retired
+    /// before it, when both are known.
+    pub schema_changed: bool,
+    /// Records the rate limit of the [`crate::distributor::LogDistributor`] dropped for the
+    /// table while the retired file was current.
+    pub dropped: u64,
+    /// Records the sampling of the distributor discarded likewise.
+    pub sampled_out: u64,
+}
+
+/// Holds off the background compression of the rotators sharing it while closed.
+#[derive(Debug, Default)]
+pub(crate) struct CompressionGate {
+    state: Mutex<GateState>,
+    changed: Condvar,
+}
+#[derive(Debug, Default)]
+struct GateState {
+    closed: usize,
+    running: usize,
+}
+impl CompressionGate {
+    /// Closes the gate until as many [`Self::open`] calls, and waits for the compressions that
+    /// got through it.
+    pub(crate) fn close(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.closed += 1;
+        while state.running != 0 {
+            state = self.changed.wait(state).unwrap();
+        }
+    }
+
+    pub(crate) fn open(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.closed -= 1;
+        if state.closed == 0 {
+            self.changed.notify_all();
+        }
+    }
+
+    fn enter(&self) -> GatePass<'_> {
+        let mut state = self.state.lock().unwrap();
+        while state.closed != 0 {
+            state = self.changed.wait(state).unwrap();
+        }
+        state.running += 1;
+        GatePass { gate: self }
+    }
+}
+
+/// A compression let through a [`CompressionGate`], running until dropped.
+struct GatePass<'a> {
+    gate: &'a CompressionGate,
+}
+impl Drop for GatePass<'_> {
+    fn drop(&mut self) {
+        let mut state = self.gate.state.lock().unwrap();
+        state.running -= 1;
+        if state.running == 0 {
+            self.gate.changed.notify_all();
+        }
+    }
+}
+
+/// Runs the callback of [`LogRotator::set_on_rotate`] on a thread of its own.
+struct RotationHook {
+    sender: HookSender,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+impl RotationHook {
+    fn spawn(
+        mut on_rotate: Box<dyn FnMut(&RotationEvent) -> bool + Send>,
+        claimed: Arc<Mutex<BTreeSet<usize>>>,
+    ) -> Self {
+        let (messages, receiver) = mpsc::channel();
+        let sender = HookSender {
+            messages,
+            claimed: Arc::clone(&claimed),
+        };
+        let thread = std::thread::Builder::new()
+            .name("LogRotator::on_rotate()".to_string())
+            .spawn(move || {
+                for message in receiver {
+                    match message {
+                        HookMessage::Rotated(event) => {
+                            if !on_rotate(&event) {
+                                claimed.lock().unwrap().remove(&event.old_epoch);
+                            }
+                        }
+                        #[cfg(test)]
+                        HookMessage::Handled(done) => {
+                            let _ = done.send(());
+                        }
+                    }
+                }
+            })
+            .expect("Failed to spawn the rotation hook thread");
+        Self {
+            sender,
+            thread: Some(thread),
+        }
+    }
+}
+impl Drop for RotationHook {
+    fn drop(&mut self) {
+        // Closes the channel, then lets the callback finish the rotations so far unless it is the
+        // one dropping the rotator.
+        self.sender.messages = mpsc::channel().0;
+        let Some(thread) = self.thread.take() else {
+            return;
+        };
+        if thread.thread().id() != std::thread::current().id() {
+            let _ = thread.join();
+        }
+    }
+}
+impl core::fmt::Debug for RotationHook {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("RotationHook").finish_non_exhaustive()
+    }
+}
+
+enum HookMessage {
+    Rotated(RotationEvent),
+    /// Answered once the rotations sent before are handled.
+    #[cfg(test)]
+    Handled(mpsc::Sender<()>),
+}
+
+#[derive(Clone)]
+struct HookSender {
+    messages: mpsc::Sender<HookMessage>,
+    claimed: Arc<Mutex<BTreeSet<usize>>>,
+}
+impl HookSender {
+    fn send(&self, message: HookMessage) {
+        if let Err(mpsc::SendError(HookMessage::Rotated(event))) = self.messages.send(message) {
+            self.decline(event.old_epoch);
+        }
+    }
+
+    /// Lets retention delete a retired file the callback will never see.
+    fn decline(&self, epoch: usize) {
+        self.claimed.lock().unwrap().remove(&epoch);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RotationPolicy {
+    pub max_records: Option<NonZeroUsize>,
+    /// Rotates once the current file holds a record and reaches this size.
+    ///
+    /// The size comes from [`LogWriter::bytes_written`] and is checked by
+    /// [`LogRotator::try_incr_record_count`], so bytes of a record still being written count towards
+    /// the next commit.
+    pub max_bytes: Option<NonZeroU64>,
+    pub time: Option<TimePast>,
+    pub max_epochs: usize,
+    /// Take the repair of [`LogRotator::check_consistency`] on startup.
+    pub repair_on_startup: bool,
+    pub retention: RetentionOrder,
+    /// Lets several processes share the output directory.
+    ///
+    /// Each instance names its files `<epoch>.<instance>.<ext>` and keeps its own epoch file.
+    /// With [`RetentionOrder::Epoch`], retention treats the files of all instances as one table
+    /// ordered like [`list_log_files`].
+    pub instance: Option<String>,
+    /// Finalize each file with [`LogWriter::write_footer`].
+    pub write_footer: bool,
+    /// Writes the [`EpochMetadata`] sidecar of each file as it is retired, including by
+    /// [`LogRotator::shutdown`] with `finalize`; see [`crate::metadata`].
+    pub write_metadata: bool,
+    /// Compresses each file in the background once it is rotated out.
+    ///
+    /// Retention and [`list_log_files`] also pick up the files named with the compressor's
+    /// extension.
+    ///
+    /// A file left behind next to its compressed counterpart by a crash is compressed again on
+    /// startup.
+    pub compression: Option<Arc<dyn Compressor>>,
+    /// Rotates on the event time fed to [`LogRotator::advance_watermark`] instead of the clock.
+    pub watermark: Option<TimePast>,
+    pub naming: FileNaming,
+    /// Also deletes the log files last modified longer than this ago on each rotation.
+    ///
+    /// Only files named like log files of this table are considered, and never the current file
+    /// of any instance.
+    pub max_age: Option<jiff::Span>,
+    /// Rotates once in each minute matched by the schedule, as polled by
+    /// [`LogRotator::try_rotate_file`].
+    pub cron: Option<Cron>,
+    /// Also deletes the log files of the lowest epochs on each rotation until all log files,
+    /// compressed ones included, total at most this many bytes, even if fewer than
+    /// [`Self::max_epochs`] remain.
+    ///
+    /// The current file of each instance is never deleted.
+    pub max_total_bytes: Option<NonZeroU64>,
+    /// Keeps appending to the current file of the last run on startup instead of starting the
+    /// next epoch, with the records already in it counting towards [`Self::max_records`].
+    ///
+    /// Falls back to the next epoch if the file is gone, e.g. compressed, or the writer does not
+    /// support [`LogWriter::try_open_append`].
+    pub resume: bool,
+    /// Checks that the current file still exists each time [`LogRotator::writer`] is called and
+    /// recreates it otherwise, at the cost of a `stat` per call.
+    pub reopen_if_missing: bool,
+    /// Points `current.<ext>`, or `current.<instance>.<ext>`, in the output directory at the
+    /// current file, for readers that follow a fixed path.
+    ///
+    /// The link is replaced by a rename, so it never dangles mid-rotation. Where symbolic links
+    /// cannot be created, `current` or `current.<instance>` holds the name of the current file
+    /// instead.
+    ///
+    /// If unset, a link left by an earlier run is removed on startup.
+    pub current_link: bool,
+    /// What is synced to disk beyond flushing; see [`Durability`] for the cost.
+    pub durability: Durability,
+    /// Read by the time triggers, [`Self::max_age`] and [`FileNaming::EpochTimestamp`];
+    /// [`SystemClock`] if [`None`].
+    pub clock: Option<Arc<dyn Clock>>,
+    /// Names the log files with this extension instead of [`LogWriter::file_extension`].
+    pub extension: Option<String>,
+    /// Wraps the data key of each file opened by an
+    /// [`crate::encryption::EncryptedWriter`].
+    #[cfg(feature = "encryption")]
+    pub key_wrapper: Option<Arc<dyn crate::encryption::KeyWrapper>>,
+}
+/// A single file per run with no rotation triggers, keeping the last seven files.
+impl Default for RotationPolicy {
+    fn default() -> Self {
+        Self {
+            max_records: None,
+            max_bytes: None,
+            time: None,
+            max_epochs: 7,
+            repair_on_startup: false,
+            retention: RetentionOrder::Epoch,
+            instance: None,
+            write_footer: false,
+            write_metadata: false,
+            compression: None,
+            watermark: None,
+            naming: FileNaming::Epoch,
+            max_age: None,
+            cron: None,
+            max_total_bytes: None,
+            resume: false,
+            reopen_if_missing: false,
+            current_link: false,
+            durability: Durability::None,
+            clock: None,
+            extension: None,
+            #[cfg(feature = "encryption")]
+            key_wrapper: None,
+        }
+    }
+}
+impl RotationPolicy {
+    /// Starts from [`Self::default`], except that [`RotationPolicyBuilder::build`] insists on a
+    /// rotation trigger.
+    pub fn builder() -> RotationPolicyBuilder {
+        RotationPolicyBuilder {
+            policy: Self::default(),
+            max_records: None,
+            max_bytes: None,
+            max_total_bytes: None,
+        }
+    }
+
+    /// Rotates daily and keeps the last `max_epochs` files.
+    pub fn daily_keep(max_epochs: usize) -> Result<Self, PolicyError> {
+        Self::builder().daily().max_epochs(max_epochs).build()
+    }
+
+    pub(crate) fn now(&self) -> jiff::Zoned {
+        match &self.clock {
+            Some(clock) => clock.now(),
+            None => SystemClock.now(),
+        }
+    }
+
+    pub(crate) fn has_time_trigger(&self) -> bool {
+        self.time.is_some() || self.cron.is_some()
+    }
+
+    /// The extension of the log files written by `W`.
+    pub(crate) fn file_extension<W>(&self) -> &str
+    where
+        W: LogWriter,
+    {
+        self.extension.as_deref().unwrap_or(W::file_extension())
+    }
+
+    fn compression_extension(&self) -> Option<&str> {
+        self.compression.as_deref().map(|c| c.extension())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RotationPolicyBuilder {
+    policy: RotationPolicy,
+    max_records: Option<usize>,
+    max_bytes: Option<u64>,
+    max_total_bytes: Option<u64>,
+}
+impl RotationPolicyBuilder {
+    pub fn max_records(mut self, max_records: usize) -> Self {
+        self.max_records = Some(max_records);
+        self
+    }
+
+    pub fn max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    pub fn time(mut self, time: TimePast) -> Self {
+        self.policy.time = Some(time);
+        self
+    }
+
+    /// Rotates when the local date changes.
+    pub fn daily(self) -> Self {
+        self.time(TimePast::new(Arc::new(DailyContains)))
+    }
+
+    pub fn watermark(mut self, watermark: TimePast) -> Self {
+        self.policy.watermark = Some(watermark);
+        self
+    }
+
+    pub fn max_epochs(mut self, max_epochs: usize) -> Self {
+        self.policy.max_epochs = max_epochs;
+        self
+    }
+
+    pub fn retention(mut self, retention: RetentionOrder) -> Self {
+        self.policy.retention = retention;
+        self
+    }
+
+    pub fn repair_on_startup(mut self, repair_on_startup: bool) -> Self {
+        self.policy.repair_on_startup = repair_on_startup;
+        self
+    }
+
+    pub fn instance(mut self, instance: impl Into<String>) -> Self {
+        self.policy.instance = Some(instance.into());
+        self
+    }
+
+    pub fn write_footer(mut self, write_footer: bool) -> Self {
+        self.policy.write_footer = write_footer;
+        self
+    }
+
+    pub fn write_metadata(mut self, write_metadata: bool) -> Self {
+        self.policy.write_metadata = write_metadata;
+        self
+    }
+
+    pub fn compression(mut self, compression: Arc<dyn Compressor>) -> Self {
+        self.policy.compression = Some(compression);
+        self
+    }
+
+    pub fn naming(mut self, naming: FileNaming) -> Self {
+        self.policy.naming = naming;
+        self
+    }
+
+    pub fn max_age(mut self, max_age: jiff::Span) -> Self {
+        self.policy.max_age = Some(max_age);
+        self
+    }
+
+    pub fn cron(mut self, cron: Cron) -> Self {
+        self.policy.cron = Some(cron);
+        self
+    }
+
+    pub fn max_total_bytes(mut self, max_total_bytes: u64) -> Self {
+        self.max_total_bytes = Some(max_total_bytes);
+        self
+    }
+
+    pub fn resume(mut self, resume: bool) -> Self {
+        self.policy.resume = resume;
+        self
+    }
+
+    pub fn reopen_if_missing(mut self, reopen_if_missing: bool) -> Self {
+        self.policy.reopen_if_missing = reopen_if_missing;
+        self
+    }
+
+    pub fn current_link(mut self, current_link: bool) -> Self {
+        self.policy.current_link = current_link;
+        self
+    }
+
+    pub fn durability(mut self, durability: Durability) -> Self {
+        self.policy.durability = durability;
+        self
+    }
+
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.policy.clock = Some(clock);
+        self
+    }
+
+    pub fn extension(mut self, extension: impl Into<String>) -> Self {
+        self.policy.extension = Some(extension.into());
+        self
+    }
+
+    #[cfg(feature = "encryption")]
+    pub fn key_wrapper(mut self, key_wrapper: Arc<dyn crate::encryption::KeyWrapper>) -> Self {
+        self.policy.key_wrapper = Some(key_wrapper);
+        self
+    }
+
+    pub fn build(self) -> Result<RotationPolicy, PolicyError> {
+        let mut policy = self.policy;
+        if let Some(max_records) = self.max_records {
+            policy.max_records =
+                Some(NonZeroUsize::new(max_records).ok_or(PolicyError::ZeroMaxRecords)?);
+        }
+        if let Some(max_bytes) = self.max_bytes {
+            policy.max_bytes = Some(NonZeroU64::new(max_bytes).ok_or(PolicyError::ZeroMaxBytes)?);
+        }
+        if let Some(max_total_bytes) = self.max_total_bytes {
+            policy.max_total_bytes =
+                Some(NonZeroU64::new(max_total_bytes).ok_or(PolicyError::ZeroMaxTotalBytes)?);
+        }
+        if policy.max_epochs == 0 {
+            return Err(PolicyError::ZeroMaxEpochs);
+        }
+        let has_trigger = policy.max_records.is_some()
+            || policy.max_bytes.is_some()
+            || policy.time.is_some()
+            || policy.cron.is_some()
+            || policy.watermark.is_some();
+        if !has_trigger {
+            return Err(PolicyError::NoTrigger);
+        }
+        if (policy.time.is_some() || policy.cron.is_some()) && policy.watermark.is_some() {
+            return Err(PolicyError::TimeAndWatermark);
+        }
+        if policy.instance.is_some() && matches!(policy.naming, FileNaming::Custom(_)) {
+            return Err(PolicyError::InstanceWithCustomNaming);
+        }
+        if policy.max_age.is_some_and(|max_age| max_age.signum() <= 0) {
+            return Err(PolicyError::NonPositiveMaxAge);
+        }
+        Ok(policy)
+    }
+}
+
+/// Flush interval of [`LogRotatorBuilder`] and [`crate::distributor::LogDistributorBuilder`]
+/// unless set.
+pub const DEFAULT_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+type SharedRotator<W> = Arc<Mutex<LogRotator<W>>>;
+
+/// A [`RotationPolicyBuilder`] for a rotator in `output_dir` flushed by its own thread.
+#[derive(Debug)]
+pub struct LogRotatorBuilder<W> {
+    output_dir: PathBuf,
+    policy: RotationPolicyBuilder,
+    flush_interval: Duration,
+    _writer: std::marker::PhantomData<fn() -> W>,
+}
+impl<W> LogRotatorBuilder<W>
+where
+    W: LogWriter + Sync + Send + 'static,
+{
+    pub fn max_records(self, max_records: usize) -> Self {
+        self.policy(|p| p.max_records(max_records))
+    }
+
+    pub fn max_bytes(self, max_bytes: u64) -> Self {
+        self.policy(|p| p.max_bytes(max_bytes))
+    }
+
+    pub fn time(self, time: TimePast) -> Self {
+        self.policy(|p| p.time(time))
+    }
+
+    /// Like [`RotationPolicyBuilder::daily`].
+    pub fn daily(self) -> Self {
+        self.policy(RotationPolicyBuilder::daily)
+    }
+
+    pub fn cron(self, cron: Cron) -> Self {
+        self.policy(|p| p.cron(cron))
+    }
+
+    pub fn max_epochs(self, max_epochs: usize) -> Self {
+        self.policy(|p| p.max_epochs(max_epochs))
+    }
+
+    /// Like [`RotationPolicyBuilder::clock`], which also pins the time zone of the time triggers
+    /// to that of the clock.
+    pub fn clock(self, clock: Arc<dyn Clock>) -> Self {
+        self.policy(|p| p.clock(clock))
+    }
+
+    /// Sets the rest of the policy.
+    pub fn policy(
+        mut self,
+        f: impl FnOnce(RotationPolicyBuilder) -> RotationPolicyBuilder,
+    ) -> Self {
+        self.policy = f(self.policy);
+        self
+    }
+
+    /// [`DEFAULT_FLUSH_INTERVAL`] unless set.
+    pub fn flush_every(mut self, flush_interval: Duration) -> Self {
+        self.flush_interval = flush_interval;
+        self
+    }
+
+    /// Checks the policy and the flush interval and creates the output directory before opening
+    /// the rotator.
+    pub fn build(self) -> Result<(SharedRotator<W>, RotatorFlusherHandle<W>), BuildError> {
+        let rotation = self.policy.build()?;
+        let flush_interval = AdaptiveInterval::fixed(self.flush_interval);
+        flush_interval.validate()?;
+        create_dir(&self.output_dir)?;
+        let rotator = LogRotator::new(self.output_dir, rotation)?;
+        let rotator = Arc::new(Mutex::new(rotator));
+        let flusher = spawn_flushers_adaptive(vec![Arc::clone(&rotator)], flush_interval);
+        Ok((rotator, flusher))
+    }
+}
+
+#[derive(Debug)]
+pub enum BuildError {
+    Policy(PolicyError),
+    FlushInterval(FlushIntervalError),
+    Rotator(RotatorError),
+}
+impl core::fmt::Display for BuildError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Policy(e) => write!(f, "{e}"),
+            Self::FlushInterval(e) => write!(f, "{e}"),
+            Self::Rotator(e) => write!(f, "{e}"),
+        }
+    }
+}
+impl std::error::Error for BuildError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Policy(e) => Some(e),
+            Self::FlushInterval(e) => Some(e),
+            Self::Rotator(e) => Some(e),
+        }
+    }
+}
+impl From<PolicyError> for BuildError {
+    fn from(e: PolicyError) -> Self {
+        Self::Policy(e)
+    }
+}
+impl From<FlushIntervalError> for BuildError {
+    fn from(e: FlushIntervalError) -> Self {
+        Self::FlushInterval(e)
+    }
+}
+impl From<RotatorError> for BuildError {
+    fn from(e: RotatorError) -> Self {
+        Self::Rotator(e)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolicyError {
+    /// None of the record count, size, time or watermark triggers is set.
+    NoTrigger,
+    ZeroMaxRecords,
+    ZeroMaxBytes,
+    ZeroMaxTotalBytes,
+    /// Retention would delete the current file.
+    ZeroMaxEpochs,
+    /// The clock, through the time or the cron schedule, and the event time would both drive
+    /// rotation.
+    TimeAndWatermark,
+    /// [`FileNaming::Custom`] leaves the instance out of the file names.
+    InstanceWithCustomNaming,
+    /// Retention would delete every file but the current one.
+    NonPositiveMaxAge,
+    /// A [`TemplateFileNamer`] template without exactly one `{epoch}`, with a path separator or
+    /// with an invalid `strftime` specifier.
+    InvalidTemplate,
+}
+impl core::fmt::Display for PolicyError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let msg = match self {
+            Self::NoTrigger => "The policy never rotates",
+            Self::ZeroMaxRecords => "max_records is zero",
+            Self::ZeroMaxBytes => "max_bytes is zero",
+            Self::ZeroMaxTotalBytes => "max_total_bytes is zero",
+            Self::ZeroMaxEpochs => "max_epochs is zero",
+            Self::TimeAndWatermark => "Both a clock trigger and watermark are set",
+            Self::InstanceWithCustomNaming => "Custom file naming cannot tell instances apart",
+            Self::NonPositiveMaxAge => "max_age is not positive",
+            Self::InvalidTemplate => "Invalid file name template",
+        };
+        f.write_str(msg)
+    }
+}
+impl std::error::Error for PolicyError {}
+
+#[derive(Debug, Clone, Default)]
+pub enum FileNaming {
+    /// `<epoch>.<ext>`
+    #[default]
+    Epoch,
+    /// `<epoch>_<timestamp>.<ext>`, with the UTC creation time formatted as `2024-06-01T13-00-00`.
+    EpochTimestamp,
+    /// Names files with the namer, ignoring [`RotationPolicy::instance`], which should be left
+    /// unset.
+    Custom(Arc<dyn FileNamer>),
+}
+
+/// Names the log files of [`FileNaming::Custom`].
+pub trait FileNamer: core::fmt::Debug + Sync + Send {
+    /// Path of the file of `epoch` created at `now`, whose name ends with `.<extension>`.
+    ///
+    /// `now` is read from [`RotationPolicy::clock`].
+    fn path(&self, dir: &Path, epoch: usize, extension: &str, now: &jiff::Zoned) -> PathBuf;
+
+    /// Epoch of the file named by [`Self::path`], or [`None`] for any other file name.
+    fn parse_epoch(&self, file_name: &str, extension: &str) -> Option<usize>;
+}
+
+/// Reproduces [`FileNaming::Epoch`].
+#[derive(Debug, Clone, Default)]
+pub struct EpochFileNamer;
+impl FileNamer for EpochFileNamer {
+    fn path(&self, dir: &Path, epoch: usize, extension: &str, _now: &jiff::Zoned) -> PathBuf {
+        log_file_path(dir, epoch, None, extension)
+    }
+
+    fn parse_epoch(&self, file_name: &str, extension: &str) -> Option<usize> {
+        match parse_log_file_name(file_name, extension, None, &FileNaming::Epoch)? {
+            (epoch, None) => Some(epoch),
+            (_, Some(_)) => None,
+        }
+    }
+}
+
+/// Names files `<template>.<ext>`, replacing `{epoch}` with the epoch and formatting `strftime`
+/// specifiers such as `%Y-%m-%d` with the creation time told by [`FileNamer::path`].
+///
+/// For example, `app-%Y-%m-%d-{epoch}` names files like `app-2024-01-15-3.log`. A specifier
+/// right next to `{epoch}` should be separated from it, e.g. by `-`, for the epoch to be parsed
+/// back unambiguously.
+#[derive(Debug, Clone)]
+pub struct TemplateFileNamer {
+    before_epoch: String,
+    after_epoch: String,
+}
+impl TemplateFileNamer {
+    pub fn new(template: &str) -> Result<Self, PolicyError> {
+        let Some((before_epoch, after_epoch)) = template.split_once("{epoch}") else {
+            return Err(PolicyError::InvalidTemplate);
+        };
+        if after_epoch.contains("{epoch}") || template.contains(std::path::is_separator) {
+            return Err(PolicyError::InvalidTemplate);
+        }
+        let this = Self {
+            before_epoch: before_epoch.to_string(),
+            after_epoch: after_epoch.to_string(),
+        };
+        let any_time = jiff::Timestamp::UNIX_EPOCH.to_zoned(jiff::tz::TimeZone::UTC);
+        this.format(&any_time).ok_or(PolicyError::InvalidTemplate)?;
+        Ok(this)
+    }
+
+    fn format(&self, now: &jiff::Zoned) -> Option<(String, String)> {
+        let format = |template: &str| jiff::fmt::strtime::format(template, now).ok();
+        Some((format(&self.before_epoch)?, format(&self.after_epoch)?))
+    }
+}
+impl FileNamer for TemplateFileNamer {
+    fn path(&self, dir: &Path, epoch: usize, extension: &str, now: &jiff::Zoned) -> PathBuf {
+        let (before, after) = self.format(now).expect("The template has been checked");
+        dir.join(format!("{before}{epoch}{after}.{extension}"))
+    }
+
+    fn parse_epoch(&self, file_name: &str, extension: &str) -> Option<usize> {
+        let stem = file_name.strip_suffix(extension)?.strip_suffix('.')?;
+        let matches =
+            |template: &str, input: &str| jiff::fmt::strtime::parse(template, input).is_ok();
+        // The formatted time may hold digits too, so try every run of digits as the epoch.
+        for (start, _) in stem.char_indices() {
+            if !matches(&self.before_epoch, &stem[..start]) {
+                continue;
+            }
+            let digits = stem[start..].len()
+                - stem[start..]
+                    .trim_start_matches(|c: char| c.is_ascii_digit())
+                    .len();
+            for end in (start + 1..=start + digits).rev() {
+                let epoch = &stem[start..end];
+                let Ok(parsed) = epoch.parse::<usize>() else {
+                    continue;
+                };
+                if parsed.to_string() == epoch && matches(&self.after_epoch, &stem[end..]) {
+                    return Some(parsed);
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Which writes are synced to disk to survive a power loss, on top of flushing them to the OS.
+///
+/// Every sync waits for the device, from tens of microseconds on an SSD with a power-loss
+/// protected cache to tens of milliseconds on a spinning disk.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Durability {
+    #[default]
+    None,
+    /// Syncs the current file through [`LogWriter::sync`] on every flush that has written
+    /// records, which is once per tick of a busy flusher.
+    FsyncOnFlush,
+    /// Syncs the retired file and, after the epoch file is written and after old files are
+    /// deleted, the output directory: three to four syncs per rotation.
+    FsyncOnRotate,
+    Both,
+}
+impl Durability {
+    fn syncs_on_flush(self) -> bool {
+        matches!(self, Self::FsyncOnFlush | Self::Both)
+    }
+
+    fn syncs_on_rotate(self) -> bool {
+        matches!(self, Self::FsyncOnRotate | Self::Both)
+    }
+}
+
+/// Decides which log files are the oldest when enforcing [`RotationPolicy::max_epochs`].
+#[derive(Clone, Default)]
+pub enum RetentionOrder {
+    /// Deletes the file `max_epochs` epochs behind the current one.
+    #[default]
+    Epoch,
+    /// Keeps the most recently modified log files of this instance.
+    Mtime,
+    /// Keeps the greatest log files by the comparison, which orders older files first.
+    ///
+    /// Like [`Self::Mtime`], only the log files of this instance named by
+    /// [`RotationPolicy::naming`] are considered.
+    Custom(Arc<RetentionCmp>),
+}
+pub type RetentionCmp = dyn Fn(&Path, &Path) -> Ordering + Sync + Send;
+impl core::fmt::Debug for RetentionOrder {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Epoch => write!(f, "Epoch"),
+            Self::Mtime => write!(f, "Mtime"),
+            Self::Custom(_) => write!(f, "Custom(..)"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConsistencyReport {
+    /// Epoch recorded in the epoch file.
+    pub persisted_epoch: Option<usize>,
+    /// Epochs that have a log file on disk, in ascending order.
+    pub present_epochs: Vec<usize>,
+    /// Runs of epochs missing between the oldest and the newest present epoch, oldest first.
+    ///
+    /// A run wrapping around from `usize::MAX` to 0 is split in two.
+    pub gaps: Vec<RangeInclusive<usize>>,
+    /// Entries in the output directory that are neither log files nor the epoch file.
+    pub unexpected_files: Vec<PathBuf>,
+    /// Present epochs that the retention window no longer covers.
+    pub outside_retention: Vec<usize>,
+    /// The epoch file disagrees with the current epoch or with the files present.
+    pub epoch_mismatch: bool,
+    /// Epoch adopted by the repair, or after the newest file if the epoch file was missing.
+    pub repaired_epoch: Option<usize>,
+}
+impl ConsistencyReport {
+    fn inspect(
+        output_dir: impl AsRef<Path>,
+        rotation: &RotationPolicy,
+        extension: &str,
+        persisted_epoch: Option<usize>,
+        cur_epoch: Option<usize>,
+    ) -> Self {
+        let max_epochs = rotation.max_epochs;
+        let (present_epochs, unexpected_files) =
+            scan_output_dir(output_dir.as_ref(), rotation, extension);
+        let gaps = epoch_gaps(&present_epochs);
+        let outside_retention = match cur_epoch {
+            Some(cur_epoch) => present_epochs
+                .iter()
+                .copied()
+                .filter(|&e| {
+                    let age = cur_epoch.wrapping_sub(e);
+                    max_epochs <= age && age <= usize::MAX / 2
+                })
+                .collect(),
+            None => vec![],
+        };
+        let epoch_mismatch = match cur_epoch {
+            Some(cur_epoch) => {
+                persisted_epoch != Some(cur_epoch)
+                    || present_epochs.binary_search(&cur_epoch).is_err()
+                    || newest_epoch(&present_epochs)
+                        .is_some_and(|newest| is_ahead(newest, cur_epoch))
+            }
+            None => !present_epochs.is_empty(),
+        };
+        Self {
+            persisted_epoch,
+            present_epochs,
+            gaps,
+            unexpected_files,
+            outside_retention,
+            epoch_mismatch,
+            repaired_epoch: None,
+        }
+    }
+
+    pub fn is_consistent(&self) -> bool {
+        self.gaps.is_empty()
+            && self.unexpected_files.is_empty()
+            && self.outside_retention.is_empty()
+            && !self.epoch_mismatch
+    }
+
+    fn repair_epoch(&self) -> usize {
+        newest_epoch(&self.present_epochs)
+            .map(|e| e.wrapping_add(1))
+            .unwrap_or_default()
+    }
+}
+
+/// The oldest and the newest of the ascending `epochs`, which are split where they are the
+/// furthest apart modulo the epoch range so that a run of epochs across the wrap stays whole.
+pub(crate) fn epoch_bounds(epochs: &[usize]) -> Option<(usize, usize)> {
+    let (&first, &last) = (epochs.first()?, epochs.last()?);
+    let around = (first, last, first.wrapping_sub(last));
+    let (oldest, newest, _) =
+        epochs
+            .windows(2)
+            .map(|w| (w[1], w[0], w[1] - w[0]))
+            .fold(around, |widest, split| match split.2 > widest.2 {
+                true => split,
+                false => widest,
+            });
+    Some((oldest, newest))
+}
+
+/// The runs of epochs missing between consecutive epochs of `epochs`, sorted ascending, in the
+/// order of [`epoch_bounds`].
+fn epoch_gaps(epochs: &[usize]) -> Vec<RangeInclusive<usize>> {
+    let Some((oldest, _)) = epoch_bounds(epochs) else {
+        return vec![];
+    };
+    let start = epochs.binary_search(&oldest).unwrap();
+    let mut ordered = epochs[start..].iter().chain(&epochs[..start]);
+    let mut gaps = vec![];
+    let mut prev = *ordered.next().unwrap();
+    for &epoch in ordered {
+        if epoch.wrapping_sub(prev) > 1 {
+            let (first, last) = (prev.wrapping_add(1), epoch.wrapping_sub(1));
+            match first <= last {
+                true => gaps.push(first..=last),
+                false => gaps.extend([first..=usize::MAX, 0..=last]),
+            }
+        }
+        prev = epoch;
+    }
+    gaps
+}
+
+fn newest_epoch(epochs: &[usize]) -> Option<usize> {
+    epoch_bounds(epochs).map(|(_, newest)| newest)
+}
+
+/// `epoch` is less than half the epoch range ahead of `of`.
+fn is_ahead(epoch: usize, of: usize) -> bool {
+    (1..=usize::MAX / 2).contains(&epoch.wrapping_sub(of))
+}
+
+/// A filesystem operation of the rotator failed.
+#[derive(Debug)]
+pub struct RotatorError {
+    pub op: RotatorOp,
+    pub path: PathBuf,
+    pub source: std::io::Error,
+}
+impl RotatorError {
+    pub(crate) fn new(op: RotatorOp, path: impl AsRef<Path>, source: std::io::Error) -> Self {
+        let path = path.as_ref().to_owned();
+        Self { op, path, source }
+    }
+}
+impl core::fmt::Display for RotatorError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let op = match self.op {
+            RotatorOp::CreateDir => "create the directory",
+            RotatorOp::OpenLogFile => "open the log file",
+            RotatorOp::Flush => "flush the log file",
+            RotatorOp::ReadEpoch => "read the epoch file",
+            RotatorOp::WriteEpoch => "write the epoch file",
+            RotatorOp::RemoveFile => "remove",
+            RotatorOp::Compress => "compress",
+            RotatorOp::Sync => "sync",
+            RotatorOp::Link => "link the current file as",
+            RotatorOp::Close => "finish the log file",
+            RotatorOp::WriteMetadata => "write the metadata of",
+            RotatorOp::Write => "write a record to",
+            RotatorOp::CheckSchema => "keep the schema of",
+        };
+        write!(f, "Failed to {op} {}: {}", self.path.display(), self.source)
+    }
+}
+impl std::error::Error for RotatorError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RotatorOp {
+    CreateDir,
+    OpenLogFile,
+    Flush,
+    ReadEpoch,
+    WriteEpoch,
+    RemoveFile,
+    Compress,
+    Sync,
+    Link,
+    /// Writing the footer or the trailer of a retired file.
+    Close,
+    /// Writing the sidecar of a retired file.
+    WriteMetadata,
+    /// Writing a record handed over already serialized.
+    Write,
+    /// Committing a record whose [`crate::LogWriter::schema`] is not that of the file.
+    CheckSchema,
+}
+
+/// Epochs of the instance's log files and the entries belonging to no instance.
+fn scan_output_dir(
+    output_dir: &Path,
+    rotation: &RotationPolicy,
+    extension: &str,
+) -> (Vec<usize>, Vec<PathBuf>) {
+    let instance = rotation.instance.as_deref();
+    let compression = rotation.compression_extension();
+    let mut epochs = vec![];
+    let mut unexpected = vec![];
+    let Ok(entries) = std::fs::read_dir(output_dir) else {
+        return (epochs, unexpected);
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Some(file_name) = path.file_name().and_then(|n| n.to_str()) else {
+            unexpected.push(path);
+            continue;
+        };
+        if is_epoch_file_name(file_name)
+            || is_current_link_name(file_name)
+            || is_metadata_file_name(file_name)
+        {
+            continue;
+        }
+        match parse_log_file_name(file_name, extension, compression, &rotation.naming) {
+            Some((epoch, i)) if i == instance => epochs.push(epoch),
+            Some(_) => (),
+            None => unexpected.push(path),
+        }
+    }
+    epochs.sort_unstable();
+    unexpected.sort_unstable();
+    (epochs, unexpected)
+}
+
+/// A log file of any instance in an output directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogFile {
+    pub path: PathBuf,
+    pub epoch: usize,
+    pub instance: Option<String>,
+    /// Creation time, or the modification time where the platform does not record it.
+    pub created_at: SystemTime,
+}
+
+/// Lists the log files of all instances in the output directory as one logical table.
+///
+/// Files are ordered by `(created_at, instance, epoch)`. Records of different instances are only
+/// ordered through the creation time of their files.
+///
+/// Files compressed with the extension `compression` are listed as well. Entries of the
+/// directory that fail to be read are skipped.
+pub fn list_log_files(
+    output_dir: impl AsRef<Path>,
+    extension: &str,
+    compression: Option<&str>,
+) -> Vec<LogFile> {
+    list_named_log_files(output_dir, extension, compression, &FileNaming::Epoch)
+}
+
+/// Like [`list_log_files`] but for files named by `naming`.
+pub fn list_named_log_files(
+    output_dir: impl AsRef<Path>,
+    extension: &str,
+    compression: Option<&str>,
+    naming: &FileNaming,
+) -> Vec<LogFile> {
+    let Ok(entries) = std::fs::read_dir(output_dir) else {
+        return vec![];
+    };
+    let mut files = entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let file_name = path.file_name()?.to_str()?;
+            let (epoch, instance) = parse_log_file_name(file_name, extension, compression, naming)?;
+            let instance = instance.map(str::to_string);
+            let metadata = std::fs::metadata(&path).ok()?;
+            let created_at = metadata.created().or_else(|_| metadata.modified()).ok()?;
+            Some(LogFile {
+                path,
+                epoch,
+                instance,
+                created_at,
+            })
+        })
+        .collect::<Vec<_>>();
+    files.sort_by(|a, b| {
+        (a.created_at, &a.instance, a.epoch).cmp(&(b.created_at, &b.instance, b.epoch))
+    });
+    files
+}
+
+/// Parses `<epoch>.<ext>` and `<epoch>.<instance>.<ext>`, optionally followed by `.<compression>`.
+///
+/// The epoch may carry the `_<timestamp>` of [`FileNaming::EpochTimestamp`].
+fn parse_log_file_name<'a>(
+    file_name: &'a str,
+    extension: &str,
+    compression: Option<&str>,
+    naming: &FileNaming,
+) -> Option<(usize, Option<&'a str>)> {
+    let file_name = strip_compression_extension(file_name, compression);
+    if let FileNaming::Custom(namer) = naming {
+        return Some((namer.parse_epoch(file_name, extension)?, None));
+    }
+    let stem = file_name.strip_suffix(extension)?.strip_suffix('.')?;
+    let (epoch, instance) = match stem.split_once('.') {
+        Some((_, "")) => return None,
+        Some((epoch, instance)) => (epoch, Some(instance)),
+        None => (stem, None),
+    };
+    let epoch = match epoch.split_once('_') {
+        Some((epoch, timestamp)) => {
+            jiff::civil::DateTime::strptime(TIMESTAMP_FORMAT, timestamp).ok()?;
+            epoch
+        }
+        None => epoch,
+    };
+    let parsed: usize = epoch.parse().ok()?;
+    if parsed.to_string() != epoch {
+        return None;
+    }
+    Some((parsed, instance))
+}
+
+fn strip_compression_extension<'a>(file_name: &'a str, compression: Option<&str>) -> &'a str {
+    compression
+        .and_then(|c| file_name.strip_suffix(c)?.strip_suffix('.'))
+        .unwrap_or(file_name)
+}
+
+/// Deletes every file at least `max_epochs` epochs behind `epoch`, e.g. left behind by a larger
+/// `max_epochs` of an earlier run, except the `kept` ones, e.g. those claimed or still being
+/// compressed.
+///
+/// Ages are taken modulo the epoch range so that retention carries on past wrapping, and files
+/// more than half the range behind count as ahead of `epoch` and are kept.
+fn delete_old_log_files(
+    epoch: usize,
+    max_epochs: usize,
+    output_dir: impl AsRef<Path>,
+    extension: &str,
+    compressor: Option<&dyn Compressor>,
+    naming: &FileNaming,
+    kept: &BTreeSet<usize>,
+) -> Result<(), RotatorError> {
+    let compression = compressor.map(|c| c.extension());
+    list_named_log_files(output_dir, extension, compression, naming)
+        .iter()
+        .filter(|file| {
+            let age = epoch.wrapping_sub(file.epoch);
+            let is_old = max_epochs <= age && age <= usize::MAX / 2;
+            file.instance.is_none() && is_old && !kept.contains(&file.epoch)
+        })
+        .try_for_each(|file| remove_log_file(&file.path, compression))
+}
+
+/// Creates the compressed file before the next log file so that it keeps the creation order of
+/// the epochs.
+fn reserve_compressed_file(
+    path: &Path,
+    compressor: &dyn Compressor,
+) -> Result<PathBuf, RotatorError> {
+    let dst = compressed_path(path, compressor);
+    if path.exists() {
+        std::fs::File::create(&dst).map_err(|e| RotatorError::new(RotatorOp::Compress, &dst, e))?;
+    }
+    Ok(dst)
+}
+
+/// Replaces the log file with its compressed counterpart.
+fn compress_log_file(
+    path: &Path,
+    dst: &Path,
+    compressor: &dyn Compressor,
+) -> Result<(), RotatorError> {
+    if !path.exists() {
+        return Ok(());
+    }
+    compressor
+        .compress(path, dst)
+        .map_err(|e| RotatorError::new(RotatorOp::Compress, path, e))?;
+    remove_file(path)
+}
+
+/// Compresses again the log files of the instance that still sit next to their compressed
+/// counterpart.
+///
+/// The original is removed only after its compression completes, so it is the compressed file
+/// that may be partial.
+fn finish_interrupted_compression(
+    output_dir: &Path,
+    rotation: &RotationPolicy,
+    extension: &str,
+    compressor: &dyn Compressor,
+) -> Result<(), RotatorError> {
+    let instance = rotation.instance.as_deref();
+    let Ok(entries) = std::fs::read_dir(output_dir) else {
+        return Ok(());
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Some(file_name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        match parse_log_file_name(file_name, extension, None, &rotation.naming) {
+            Some((_, i)) if i == instance => (),
+            _ => continue,
+        }
+        let dst = compressed_path(&path, compressor);
+        if dst.exists() {
+            compress_log_file(&path, &dst, compressor)?;
+        }
+    }
+    Ok(())
+}
+
+fn remove_file(path: &Path) -> Result<(), RotatorError> {
+    std::fs::remove_file(path).map_err(|e| RotatorError::new(RotatorOp::RemoveFile, path, e))
+}
+
+/// Also removes the sidecar of the log file, if any, which the other file of its epoch may have
+/// removed already.
+fn remove_log_file(path: &Path, compression: Option<&str>) -> Result<(), RotatorError> {
+    remove_file(path)?;
+    let metadata = metadata_path(path, compression);
+    match std::fs::remove_file(&metadata) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            Err(RotatorError::new(RotatorOp::RemoveFile, metadata, e))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn compare_mtime(a: &Path, b: &Path) -> Ordering {
+    let mtime = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    mtime(a).cmp(&mtime(b)).then_with(|| a.cmp(b))
+}
+
+/// Reopens the uncompressed file of the epoch, or returns [`None`] if it is gone or the writer
+/// cannot append.
+fn resume_log_writer<W>(
+    output_dir: &Path,
+    epoch: usize,
+    rotation: &RotationPolicy,
+) -> Result<Option<(PathBuf, W, usize)>, RotatorError>
+where
+    W: LogWriter,
+{
+    let file = list_named_log_files(
+        output_dir,
+        rotation.file_extension::<W>(),
+        None,
+        &rotation.naming,
+    )
+    .into_iter()
+    .find(|file| file.epoch == epoch && file.instance == rotation.instance);
+    let Some(file) = file else {
+        return Ok(None);
+    };
+    match W::try_open_append(&file.path) {
+        Ok((writer, records_written)) => Ok(Some((file.path, writer, records_written))),
+        Err(e) if e.kind() == std::io::ErrorKind::Unsupported => Ok(None),
+        Err(e) => Err(RotatorError::new(RotatorOp::OpenLogFile, &file.path, e)),
+    }
+}
+
+/// Also returns the auxiliary rows written by [`LogWriter::on_open`].
+fn create_clean_log_writer<W>(
+    path: impl AsRef<Path>,
+    rotation: &RotationPolicy,
+) -> Result<(W, usize), RotatorError>
+where
+    W: LogWriter,
+{
+    let path = path.as_ref();
+    create_parent_dir(path)?;
+    let open = |e| RotatorError::new(RotatorOp::OpenLogFile, path, e);
+    let mut writer = W::open_for(path, rotation).map_err(open)?;
+    let aux_records = writer.on_open().map_err(open)?;
+    Ok((writer, aux_records))
+}
+
+fn create_parent_dir(path: &Path) -> Result<(), RotatorError> {
+    create_dir(path.parent().unwrap())
+}
+
+pub(crate) fn create_dir(dir: &Path) -> Result<(), RotatorError> {
+    std::fs::create_dir_all(dir).map_err(|e| RotatorError::new(RotatorOp::CreateDir, dir, e))
+}
+
+/// Writes and syncs a temporary file renamed over the epoch file, so that a crash leaves either
+/// the old or the new epoch behind.
+fn write_epoch(
+    output_dir: impl AsRef<Path>,
+    instance: Option<&str>,
+    epoch: usize,
+) -> Result<(), RotatorError> {
+    let path = epoch_file_path(output_dir, instance);
+    create_parent_dir(&path)?;
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    std::fs::File::options()
+        .create(true)
+        .truncate(true)
+        .write(true)
+        .open(&tmp)
+        .and_then(|mut file| {
+            file.write_all(epoch.to_string().as_bytes())?;
+            file.sync_all()
+        })
+        .and_then(|()| std::fs::rename(&tmp, &path))
+        .map_err(|e| RotatorError::new(RotatorOp::WriteEpoch, &path, e))
+}
+
+fn sync_file(path: &Path) -> Result<(), RotatorError> {
+    std::fs::File::open(path)
+        .and_then(|file| file.sync_all())
+        .map_err(|e| RotatorError::new(RotatorOp::Sync, path, e))
+}
+
+/// Syncs the entries of `dir`, which only Unix supports.
+fn sync_dir(dir: &Path) -> Result<(), RotatorError> {
+    #[cfg(unix)]
+    std::fs::File::open(dir)
+        .and_then(|dir| dir.sync_all())
+        .map_err(|e| RotatorError::new(RotatorOp::Sync, dir, e))?;
+    #[cfg(not(unix))]
+    let _ = dir;
+    Ok(())
+}
+
+pub(crate) fn cur_epoch(
+    output_dir: impl AsRef<Path>,
+    instance: Option<&str>,
+) -> Result<Option<usize>, RotatorError> {
+    let path = epoch_file_path(output_dir, instance);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let mut epoch = String::new();
+    std::fs::File::options()
+        .read(true)
+        .open(&path)
+        .and_then(|mut file| file.read_to_string(&mut epoch))
+        .map_err(|e| RotatorError::new(RotatorOp::ReadEpoch, &path, e))?;
+    // An unparsable epoch file is left for the directory scan to repair.
+    Ok(epoch.parse().ok())
+}
+
+fn current_link_path(output_dir: &Path, instance: Option<&str>, extension: &str) -> PathBuf {
+    match instance {
+        Some(instance) => output_dir.join(format!("current.{instance}.{extension}")),
+        None => output_dir.join(format!("current.{extension}")),
+    }
+}
+
+fn is_current_link_name(file_name: &str) -> bool {
+    file_name == "current" || file_name.starts_with("current.")
+}
+
+/// Points `link` at the sibling `target` through a temporary link renamed over it.
+///
+/// Falls back to writing the file name of `target` to `link` without its extension.
+fn replace_current_link(link: &Path, target: &Path) -> std::io::Result<()> {
+    let target = Path::new(target.file_name().unwrap());
+    let mut tmp = link.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    match std::fs::remove_file(&tmp) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+        _ => (),
+    }
+    #[cfg(unix)]
+    let linked = std::os::unix::fs::symlink(target, &tmp);
+    #[cfg(windows)]
+    let linked = std::os::windows::fs::symlink_file(target, &tmp);
+    #[cfg(not(any(unix, windows)))]
+    let linked = Err(std::io::Error::from(std::io::ErrorKind::Unsupported));
+    match linked {
+        Ok(()) => std::fs::rename(&tmp, link),
+        Err(_) => {
+            std::fs::write(&tmp, target.as_os_str().as_encoded_bytes())?;
+            std::fs::rename(&tmp, link.with_extension(""))
+        }
+    }
+}
+
+/// Removes the link, or the file written in its place, made by [`replace_current_link`].
+fn remove_current_link(link: &Path) -> std::io::Result<()> {
+    for path in [link, &link.with_extension("")] {
+        match std::fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+fn epoch_file_path(output_dir: impl AsRef<Path>, instance: Option<&str>) -> PathBuf {
+    match instance {
+        Some(instance) => output_dir.as_ref().join(format!("epoch.{instance}")),
+        None => output_dir.as_ref().join("epoch"),
+    }
+}
+
+fn is_epoch_file_name(file_name: &str) -> bool {
+    file_name == "epoch" || file_name.starts_with("epoch.")
+}
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%dT%H-%M-%S";
+
+/// Names a file created now for `epoch`.
+fn new_log_file_path(
+    output_dir: impl AsRef<Path>,
+    epoch: usize,
+    rotation: &RotationPolicy,
+    extension: &str,
+) -> PathBuf {
+    let instance = rotation.instance.as_deref();
+    match &rotation.naming {
+        FileNaming::Epoch => log_file_path(output_dir, epoch, instance, extension),
+        FileNaming::EpochTimestamp => {
+            let timestamp = rotation.now().timestamp().strftime(TIMESTAMP_FORMAT);
+            let stem = format!("{epoch}_{timestamp}");
+            log_file_path(output_dir, stem, instance, extension)
+        }
+        FileNaming::Custom(namer) => {
+            namer.path(output_dir.as_ref(), epoch, extension, &rotation.now())
+        }
+    }
+}
+
+fn log_file_path(
+    output_dir: impl AsRef<Path>,
+    epoch: impl core::fmt::Display,
+    instance: Option<&str>,
+    extension: &str,
+) -> PathBuf {
+    match instance {
+        Some(instance) => output_dir
+            .as_ref()
+            .join(format!("{epoch}.{instance}.{extension}")),
+        None => output_dir.as_ref().join(format!("{epoch}.{extension}")),
+    }
+}
+
+#[cfg(all(test, feature = "csv"))]
+mod tests {
+    use std::{
+        io::Read,
+        sync::{Arc, Mutex},
+    };
+
+    use serde::Serialize;
+
+    use crate::{compression::Compressor, time_past::DailyContains, writers::csv::CsvLogWriter};
+
+    use super::*;
+
+    struct Logger {
+        rotator: Arc<Mutex<LogRotator<CsvLogWriter>>>,
+    }
+    impl Logger {
+        pub fn new(rotator: Arc<Mutex<LogRotator<CsvLogWriter>>>) -> Self {
+            Self { rotator }
+        }
+
+        pub fn write<R>(&self, record: &R)
+        where
+            R: Serialize,
+        {
+            let mut rotator = self.rotator.lock().unwrap();
+            rotator.writer().writer().serialize(record).unwrap();
+            rotator.try_incr_record_count().unwrap();
+        }
+
+        pub fn flush(&self) {
+            self.rotator.lock().unwrap().flush().unwrap();
+        }
+    }
+
+    #[derive(Serialize)]
+    struct TestRecord {
+        pub s: &'static str,
+        pub n: usize,
+    }
+
+    #[test]
+    fn test_logger() {
+        let dir = tempfile::tempdir().unwrap();
+        let log_rotator = LogRotator::new_or_panic(
+            dir.path().to_owned(),
+            RotationPolicy {
+                max_records: Some(NonZeroUsize::new(2).unwrap()),
+                max_epochs: 2,
+                ..Default::default()
+            },
+        );
+        let logger = Logger::new(Arc::new(Mutex::new(log_rotator)));
+        logger.write(&TestRecord { s: "a", n: 0 });
+        logger.write(&TestRecord { s: "b", n: 1 });
+        logger.flush();
+        let path = log_file_path(dir.path(), 0, None, "csv");
+        assert!(path.exists());
+        let mut file = std::fs::File::options().read(true).open(path).unwrap();
+        let mut csv = String::new();
+        file.read_to_string(&mut csv).unwrap();
+        assert_eq!(
+            csv,
+            r#"s,n
+a,0
+b,1
+"#
+        );
+    }
+
+    #[test]
+    fn test_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotator = LogRotator::new_or_panic(
+            dir.path().to_owned(),
+            RotationPolicy {
+                max_records: Some(NonZeroUsize::new(2).unwrap()),
+                max_epochs: 2,
+                ..Default::default()
+            },
+        );
+        let logger = Logger::new(Arc::new(Mutex::new(rotator)));
+
+        logger.write(&TestRecord { s: "a", n: 0 });
+        logger.flush();
+        let path = log_file_path(dir.path(), 0, None, "csv");
+        assert!(path.exists());
+        let path = log_file_path(dir.path(), 1, None, "csv");
+        assert!(!path.exists());
+
+        logger.write(&TestRecord { s: "b", n: 1 });
+        let path = log_file_path(dir.path(), 0, None, "csv");
         assert!(path.exists());
-        let path = log_file_path(dir.path(), 2, "csv");
+        let path = log_file_path(dir.path(), 1, None, "csv");
+        assert!(path.exists());
+        let path = log_file_path(dir.path(), 2, None, "csv");
+        assert!(!path.exists());
+
+        logger.write(&TestRecord { s: "c", n: 2 });
+        logger.flush();
+        let path = log_file_path(dir.path(), 0, None, "csv");
+        assert!(path.exists());
+        let path = log_file_path(dir.path(), 1, None, "csv");
+        assert!(path.exists());
+        let path = log_file_path(dir.path(), 2, None, "csv");
+        assert!(!path.exists());
+
+        logger.write(&TestRecord { s: "d", n: 3 });
+        let path = log_file_path(dir.path(), 0, None, "csv");
+        assert!(!path.exists());
+        let path = log_file_path(dir.path(), 1, None, "csv");
+        assert!(path.exists());
+        let path = log_file_path(dir.path(), 2, None, "csv");
+        assert!(path.exists());
+        let path = log_file_path(dir.path(), 3, None, "csv");
         assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_aux_records() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy {
+            max_records: NonZeroUsize::new(3),
+            max_epochs: 3,
+            write_footer: true,
+            write_metadata: true,
+            ..Default::default()
+        };
+        let mut rotator = LogRotator::<Bracketed>::new_or_panic(dir.path().to_owned(), rotation);
+        assert_eq!(rotator.aux_records(), 1);
+        let committed = rotator.write_batch(0..5, |w, n| writeln!(w.0, "{n}").unwrap());
+        assert_eq!(committed.unwrap(), 5);
+        assert_eq!(rotator.records_written(), 2);
+        assert_eq!(rotator.aux_records(), 1);
+        let metrics = rotator.metrics();
+        assert_eq!(metrics.aux_records_current, 1);
+        assert_eq!(metrics.aux_records_total, 4);
+        rotator.shutdown(true).unwrap();
+
+        let read = |epoch| std::fs::read_to_string(log_file_path(dir.path(), epoch, None, "log"));
+        assert_eq!(read(0).unwrap(), "[\n0\n1\n2\n=3\n]\n");
+        assert_eq!(read(1).unwrap(), "[\n3\n4\n=2\n]\n");
+        let metadata = |epoch| {
+            let path = log_file_path(dir.path(), epoch, None, "log");
+            EpochMetadata::read(path, None).unwrap()
+        };
+        let expected = |records| EpochMetadata {
+            records,
+            aux_records: 3,
+            schema: None,
+            schema_text: None,
+        };
+        assert_eq!(metadata(0), expected(3));
+        assert_eq!(metadata(1), expected(2));
+
+        let reader = crate::reader::LogReader::<Bracketed>::new(dir.path());
+        let reconciled = reader.reconcile::<usize>().collect::<Vec<_>>();
+        assert_eq!(reconciled.len(), 2);
+        assert!(reconciled.iter().all(|r| r.is_exact()), "{reconciled:?}");
+        let path = log_file_path(dir.path(), 0, None, "log");
+        std::fs::write(path, "[\n0\n1\n]\n").unwrap();
+        let reconciled = reader.reconcile::<usize>().next().unwrap();
+        assert_eq!(*reconciled.records.as_ref().unwrap(), 2);
+        assert!(!reconciled.is_exact());
+    }
+
+    #[test]
+    fn test_metrics() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotator = LogRotator::new_or_panic(
+            dir.path().to_owned(),
+            RotationPolicy {
+                max_records: Some(NonZeroUsize::new(2).unwrap()),
+                max_epochs: 2,
+                ..Default::default()
+            },
+        );
+        let logger = Logger::new(Arc::new(Mutex::new(rotator)));
+        for n in 0..5 {
+            logger.write(&TestRecord { s: "a", n });
+        }
+        logger.flush();
+        let metrics = logger.rotator.lock().unwrap().metrics();
+        assert_eq!(metrics.epoch, 2);
+        assert_eq!(metrics.records_current, 1);
+        assert_eq!(metrics.records_total, 5);
+        assert_eq!(metrics.bytes, Some("s,n\na,4\n".len() as u64));
+        assert_eq!(metrics.rotations, 2);
+        assert!(metrics.last_rotation.is_some());
+    }
+
+    #[test]
+    fn test_metrics_across_rotations() {
+        let dir = tempfile::tempdir().unwrap();
+        let start: jiff::Zoned = "2024-01-01T00:00:00[UTC]".parse().unwrap();
+        let clock = Arc::new(crate::clock::MockClock::new(start));
+        let rotation = RotationPolicy::builder()
+            .max_records(2)
+            .clock(Arc::clone(&clock) as _)
+            .build()
+            .unwrap();
+        let mut rotator = LogRotator::<CsvLogWriter>::new_or_panic(dir.path().to_owned(), rotation);
+        assert_eq!(rotator.metrics().last_rotation, None);
+        for n in 0..3 {
+            rotator
+                .writer()
+                .serialize(&TestRecord { s: "a", n })
+                .unwrap();
+            rotator.try_incr_record_count().unwrap();
+        }
+        clock.advance(jiff::SignedDuration::from_secs(60));
+        assert!(rotator.rotate_now(true).unwrap());
+        assert!(!rotator.rotate_now(true).unwrap());
+
+        let metrics = rotator.metrics();
+        assert_eq!(metrics.epoch, 2);
+        assert_eq!(metrics.records_current, 0);
+        assert_eq!(metrics.records_total, 3);
+        assert_eq!(metrics.rotations, 2);
+        let last_rotation: jiff::Timestamp = "2024-01-01T00:01:00Z".parse().unwrap();
+        assert_eq!(metrics.last_rotation, Some(last_rotation));
+    }
+
+    fn consistency_policy(repair_on_startup: bool) -> RotationPolicy {
+        RotationPolicy {
+            max_epochs: 4,
+            repair_on_startup,
+            ..Default::default()
+        }
+    }
+
+    fn create_log_files(output_dir: &Path, epochs: &[usize]) {
+        for &epoch in epochs {
+            std::fs::write(log_file_path(output_dir, epoch, None, "csv"), "").unwrap();
+        }
+    }
+
+    #[test]
+    fn test_consistency_restored_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        create_log_files(dir.path(), &[3, 4, 7, 8]);
+        write_epoch(dir.path(), None, 9).unwrap();
+        let rotator = LogRotator::<CsvLogWriter>::new_or_panic(
+            dir.path().to_owned(),
+            consistency_policy(false),
+        );
+        assert_eq!(
+            rotator.startup_report(),
+            &ConsistencyReport {
+                persisted_epoch: Some(9),
+                present_epochs: vec![3, 4, 7, 8],
+                gaps: vec![5..=6],
+                unexpected_files: vec![],
+                outside_retention: vec![3, 4],
+                epoch_mismatch: true,
+                repaired_epoch: None,
+            }
+        );
+        assert_eq!(rotator.metrics().epoch, 10);
+
+        let dir = tempfile::tempdir().unwrap();
+        create_log_files(dir.path(), &[3, 4, 7, 8]);
+        write_epoch(dir.path(), None, 9).unwrap();
+        let rotator = LogRotator::<CsvLogWriter>::new_or_panic(
+            dir.path().to_owned(),
+            consistency_policy(true),
+        );
+        assert_eq!(rotator.startup_report().repaired_epoch, Some(9));
+        assert_eq!(rotator.metrics().epoch, 9);
+        assert_eq!(cur_epoch(dir.path(), None).unwrap(), Some(9));
+        assert!(log_file_path(dir.path(), 9, None, "csv").exists());
+    }
+
+    #[test]
+    fn test_consistency_far_epoch() {
+        let dir = tempfile::tempdir().unwrap();
+        create_log_files(dir.path(), &[0, 1, 3, 1_700_000_000]);
+        let mut rotator = LogRotator::<CsvLogWriter>::new_or_panic(
+            dir.path().to_owned(),
+            consistency_policy(false),
+        );
+        let gaps = vec![2..=2, 4..=1_699_999_999];
+        assert_eq!(rotator.startup_report().gaps, gaps);
+        std::fs::write(
+            log_file_path(dir.path(), 17_179_869_184_usize, None, "csv"),
+            "",
+        )
+        .unwrap();
+        let report = rotator.check_consistency(false).unwrap();
+        assert_eq!(report.gaps.last(), Some(&(1_700_000_002..=17_179_869_183)));
+
+        assert_eq!(
+            epoch_gaps(&[1, usize::MAX - 1]),
+            [usize::MAX..=usize::MAX, 0..=0]
+        );
+        assert_eq!(epoch_gaps(&[2, usize::MAX]), [0..=1]);
+        assert!(epoch_gaps(&[0, usize::MAX]).is_empty());
+    }
+
+    #[test]
+    fn test_consistency_files_ahead_of_epoch_file() {
+        let dir = tempfile::tempdir().unwrap();
+        create_log_files(dir.path(), &[0, 1, 2, 5]);
+        std::fs::write(dir.path().join("notes.txt"), "").unwrap();
+        write_epoch(dir.path(), None, 1).unwrap();
+        let rotator = LogRotator::<CsvLogWriter>::new_or_panic(
+            dir.path().to_owned(),
+            consistency_policy(true),
+        );
+        let report = rotator.startup_report();
+        assert_eq!(report.gaps, vec![3..=4]);
+        assert_eq!(report.unexpected_files, vec![dir.path().join("notes.txt")]);
+        assert!(report.outside_retention.is_empty());
+        assert!(report.epoch_mismatch);
+        assert_eq!(report.repaired_epoch, Some(6));
+        assert_eq!(rotator.metrics().epoch, 6);
+        assert!(log_file_path(dir.path(), 5, None, "csv").exists());
+    }
+
+    #[test]
+    fn test_check_consistency_on_demand() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut rotator = LogRotator::<CsvLogWriter>::new_or_panic(
+            dir.path().to_owned(),
+            consistency_policy(false),
+        );
+        assert!(rotator.startup_report().is_consistent());
+        assert!(rotator.check_consistency(false).unwrap().is_consistent());
+
+        std::fs::remove_file(log_file_path(dir.path(), 0, None, "csv")).unwrap();
+        let report = rotator.check_consistency(false).unwrap();
+        assert!(report.epoch_mismatch);
+        assert_eq!(report.repaired_epoch, None);
+        assert_eq!(rotator.metrics().epoch, 0);
+
+        let report = rotator.check_consistency(true).unwrap();
+        assert_eq!(report.repaired_epoch, Some(1));
+        assert!(log_file_path(dir.path(), 1, None, "csv").exists());
+        assert_eq!(cur_epoch(dir.path(), None).unwrap(), Some(1));
+        assert!(rotator.check_consistency(false).unwrap().is_consistent());
+    }
+
+    #[test]
+    fn test_check_consistency_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut rotator = LogRotator::<CsvLogWriter>::new_or_panic(
+            dir.path().to_owned(),
+            consistency_policy(false),
+        );
+        // The epoch file cannot be read where a directory stands.
+        let epoch_file = epoch_file_path(dir.path(), None);
+        std::fs::remove_file(&epoch_file).unwrap();
+        std::fs::create_dir(&epoch_file).unwrap();
+        let e = rotator.check_consistency(true).unwrap_err();
+        assert_eq!(e.op, RotatorOp::ReadEpoch);
+        assert_eq!(rotator.metrics().epoch, 0);
+    }
+
+    fn retention_policy(retention: RetentionOrder) -> RotationPolicy {
+        RotationPolicy {
+            max_records: Some(NonZeroUsize::new(1).unwrap()),
+            max_epochs: 3,
+            retention,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_retention_by_mtime() {
+        let dir = tempfile::tempdir().unwrap();
+        let now = std::time::SystemTime::now();
+        for (name, age) in [("5.csv", 1), ("6.csv", 3), ("7.csv", 2), ("notes.csv", 4)] {
+            let file = std::fs::File::create(dir.path().join(name)).unwrap();
+            file.set_modified(now - Duration::from_secs(age * 60))
+                .unwrap();
+        }
+        std::fs::write(dir.path().join("notes.txt"), "").unwrap();
+        let rotator = LogRotator::new_or_panic(
+            dir.path().to_owned(),
+            retention_policy(RetentionOrder::Mtime),
+        );
+        assert!(log_file_path(dir.path(), 5, None, "csv").exists());
+        assert!(!log_file_path(dir.path(), 6, None, "csv").exists());
+        assert!(log_file_path(dir.path(), 7, None, "csv").exists());
+
+        let logger = Logger::new(Arc::new(Mutex::new(rotator)));
+        logger.write(&TestRecord { s: "a", n: 0 });
+        assert!(log_file_path(dir.path(), 5, None, "csv").exists());
+        assert!(!log_file_path(dir.path(), 7, None, "csv").exists());
+        assert!(log_file_path(dir.path(), 8, None, "csv").exists());
+        assert!(log_file_path(dir.path(), 9, None, "csv").exists());
+        assert!(dir.path().join("notes.csv").exists());
+        assert!(dir.path().join("notes.txt").exists());
+    }
+
+    #[test]
+    fn test_retention_by_mtime_skips_other_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("notes.csv"), "").unwrap();
+        std::fs::write(dir.path().join("0.other.csv"), "").unwrap();
+        let rotation = RotationPolicy {
+            max_epochs: 2,
+            current_link: true,
+            ..retention_policy(RetentionOrder::Mtime)
+        };
+        let rotator = LogRotator::new_or_panic(dir.path().to_owned(), rotation);
+        let logger = Logger::new(Arc::new(Mutex::new(rotator)));
+        for n in 0..7 {
+            logger.write(&TestRecord { s: "a", n });
+        }
+        let files = list_log_files(dir.path(), "csv", None);
+        let mut epochs = files
+            .iter()
+            .filter(|f| f.instance.is_none())
+            .map(|f| f.epoch)
+            .collect::<Vec<_>>();
+        epochs.sort_unstable();
+        assert_eq!(epochs, [6, 7]);
+        let link = dir.path().join("current.csv");
+        assert_eq!(std::fs::read_link(link).unwrap(), Path::new("7.csv"));
+        assert!(dir.path().join("notes.csv").exists());
+        assert!(dir.path().join("0.other.csv").exists());
+    }
+
+    #[test]
+    fn test_retention_by_custom_order() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["1.csv", "2.csv", "3.csv", "notes.csv"] {
+            std::fs::write(dir.path().join(name), "").unwrap();
+        }
+        let newest_name_first = RetentionOrder::Custom(Arc::new(|a: &Path, b: &Path| b.cmp(a)));
+        LogRotator::<CsvLogWriter>::new_or_panic(
+            dir.path().to_owned(),
+            retention_policy(newest_name_first),
+        );
+        assert!(dir.path().join("1.csv").exists());
+        assert!(dir.path().join("2.csv").exists());
+        assert!(!dir.path().join("3.csv").exists());
+        assert!(dir.path().join("4.csv").exists());
+        assert!(dir.path().join("notes.csv").exists());
+    }
+
+    #[test]
+    fn test_retention_by_age() {
+        let dir = tempfile::tempdir().unwrap();
+        let now = std::time::SystemTime::now();
+        let day = Duration::from_secs(24 * 60 * 60);
+        for (name, age) in [("5.csv", 3), ("6.csv", 1), ("notes.csv", 3), ("7.txt", 3)] {
+            let file = std::fs::File::create(dir.path().join(name)).unwrap();
+            file.set_modified(now - day * age).unwrap();
+        }
+        let rotation = RotationPolicy {
+            max_epochs: 10,
+            max_age: Some(jiff::Span::new().days(2)),
+            ..retention_policy(RetentionOrder::Epoch)
+        };
+        let rotator = LogRotator::new_or_panic(dir.path().to_owned(), rotation);
+        assert!(!dir.path().join("5.csv").exists());
+        assert!(dir.path().join("6.csv").exists());
+        assert!(dir.path().join("notes.csv").exists());
+        assert!(dir.path().join("7.txt").exists());
+
+        let logger = Logger::new(Arc::new(Mutex::new(rotator)));
+        logger.write(&TestRecord { s: "a", n: 0 });
+        assert!(log_file_path(dir.path(), 7, None, "csv").exists());
+        assert!(log_file_path(dir.path(), 8, None, "csv").exists());
+    }
+
+    #[test]
+    fn test_retention_by_age_keeps_current_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let three_days_ago = std::time::SystemTime::now() - Duration::from_secs(3 * 24 * 60 * 60);
+        for epoch in 0..3 {
+            let path = log_file_path(dir.path(), epoch, None, "csv");
+            let file = std::fs::File::create(path).unwrap();
+            file.set_modified(three_days_ago).unwrap();
+        }
+        write_epoch(dir.path(), None, 2).unwrap();
+        let rotation = RotationPolicy {
+            max_epochs: 2,
+            max_age: Some(jiff::Span::new().days(2)),
+            resume: true,
+            ..retention_policy(RetentionOrder::Epoch)
+        };
+        let rotator = LogRotator::<CsvLogWriter>::new_or_panic(dir.path().to_owned(), rotation);
+        assert_eq!(rotator.metrics().epoch, 2);
+        let epochs = list_log_files(dir.path(), "csv", None)
+            .iter()
+            .map(|f| f.epoch)
+            .collect::<Vec<_>>();
+        assert_eq!(epochs, [2]);
+    }
+
+    #[test]
+    fn test_retention_by_total_bytes() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy {
+            max_epochs: 10,
+            max_total_bytes: NonZeroU64::new(20),
+            ..retention_policy(RetentionOrder::Epoch)
+        };
+        let rotator = LogRotator::new_or_panic(dir.path().to_owned(), rotation);
+        let logger = Logger::new(Arc::new(Mutex::new(rotator)));
+        for n in 0..5 {
+            logger.write(&TestRecord { s: "a", n });
+        }
+        let files = list_log_files(dir.path(), "csv", None);
+        let epochs = files.iter().map(|f| f.epoch).collect::<Vec<_>>();
+        assert_eq!(epochs, [3, 4, 5]);
+        let total = files
+            .iter()
+            .map(|f| std::fs::metadata(&f.path).unwrap().len())
+            .sum::<u64>();
+        assert!(total <= 20);
+    }
+
+    #[test]
+    fn test_missing_epoch_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = retention_policy(RetentionOrder::Epoch);
+        let rotator = LogRotator::new_or_panic(dir.path().to_owned(), rotation.clone());
+        let logger = Logger::new(Arc::new(Mutex::new(rotator)));
+        logger.write(&TestRecord { s: "a", n: 0 });
+        drop(logger);
+        std::fs::remove_file(epoch_file_path(dir.path(), None)).unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "").unwrap();
+
+        let rotator = LogRotator::<CsvLogWriter>::new_or_panic(dir.path().to_owned(), rotation);
+        assert_eq!(rotator.startup_report().persisted_epoch, None);
+        assert_eq!(rotator.startup_report().repaired_epoch, Some(2));
+        assert_eq!(rotator.metrics().epoch, 2);
+        drop(rotator);
+        let contents = std::fs::read_to_string(log_file_path(dir.path(), 0, None, "csv"));
+        assert_eq!(contents.unwrap(), "s,n\na,0\n");
+        assert_eq!(cur_epoch(dir.path(), None).unwrap(), Some(2));
+    }
+
+    #[test]
+    fn test_epoch_inferred_from_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy {
+            max_records: NonZeroUsize::new(1),
+            max_epochs: 10,
+            ..Default::default()
+        };
+        let rotator = LogRotator::new_or_panic(dir.path().to_owned(), rotation.clone());
+        let logger = Logger::new(Arc::new(Mutex::new(rotator)));
+        for n in 0..6 {
+            logger.write(&TestRecord { s: "a", n });
+        }
+        drop(logger);
+        std::fs::remove_file(log_file_path(dir.path(), 6, None, "csv")).unwrap();
+        std::fs::remove_file(epoch_file_path(dir.path(), None)).unwrap();
+
+        let rotator = LogRotator::<CsvLogWriter>::new_or_panic(dir.path().to_owned(), rotation);
+        assert_eq!(rotator.startup_report().persisted_epoch, None);
+        assert_eq!(rotator.startup_report().present_epochs, [0, 1, 2, 3, 4, 5]);
+        assert_eq!(rotator.metrics().epoch, 6);
+        drop(rotator);
+        for n in 0..6 {
+            let contents = std::fs::read_to_string(log_file_path(dir.path(), n, None, "csv"));
+            assert_eq!(contents.unwrap(), format!("s,n\na,{n}\n"));
+        }
+        assert_eq!(cur_epoch(dir.path(), None).unwrap(), Some(6));
+    }
+
+    #[test]
+    fn test_corrupt_epoch_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = retention_policy(RetentionOrder::Epoch);
+        let rotator = LogRotator::new_or_panic(dir.path().to_owned(), rotation.clone());
+        let logger = Logger::new(Arc::new(Mutex::new(rotator)));
+        for n in 0..3 {
+            logger.write(&TestRecord { s: "a", n });
+        }
+        drop(logger);
+        let epoch_file = epoch_file_path(dir.path(), None);
+        assert_eq!(std::fs::read_to_string(&epoch_file).unwrap(), "3");
+        assert!(!dir.path().join("epoch.tmp").exists());
+        std::fs::File::create(&epoch_file).unwrap();
+
+        let rotator = LogRotator::<CsvLogWriter>::new_or_panic(dir.path().to_owned(), rotation);
+        assert_eq!(rotator.startup_report().persisted_epoch, None);
+        assert_eq!(rotator.startup_report().repaired_epoch, Some(4));
+        drop(rotator);
+        let contents = std::fs::read_to_string(log_file_path(dir.path(), 2, None, "csv"));
+        assert_eq!(contents.unwrap(), "s,n\na,2\n");
+        assert_eq!(cur_epoch(dir.path(), None).unwrap(), Some(4));
+    }
+
+    #[test]
+    fn test_stale_epoch_tmp_file() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("epoch.tmp"), "9").unwrap();
+        write_epoch(dir.path(), None, 5).unwrap();
+        assert!(!dir.path().join("epoch.tmp").exists());
+        assert_eq!(cur_epoch(dir.path(), None).unwrap(), Some(5));
+
+        // A crash before the rename leaves the previous epoch in place.
+        std::fs::write(dir.path().join("epoch.tmp"), "").unwrap();
+        let rotation = retention_policy(RetentionOrder::Epoch);
+        let rotator = LogRotator::<CsvLogWriter>::new_or_panic(dir.path().to_owned(), rotation);
+        assert_eq!(rotator.startup_report().persisted_epoch, Some(5));
+        assert!(rotator.startup_report().unexpected_files.is_empty());
+        assert_eq!(rotator.metrics().epoch, 6);
+    }
+
+    #[test]
+    fn test_epoch_wraparound() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(log_file_path(dir.path(), 0, None, "csv"), "stale").unwrap();
+        write_epoch(dir.path(), None, usize::MAX - 2).unwrap();
+        let rotation = retention_policy(RetentionOrder::Epoch);
+        let rotator = LogRotator::new_or_panic(dir.path().to_owned(), rotation.clone());
+        let logger = Logger::new(Arc::new(Mutex::new(rotator)));
+        for n in 0..3 {
+            logger.write(&TestRecord { s: "a", n });
+        }
+        logger.flush();
+        drop(logger);
+        let mut epochs = list_log_files(dir.path(), "csv", None)
+            .iter()
+            .map(|f| f.epoch)
+            .collect::<Vec<_>>();
+        epochs.sort_unstable();
+        assert_eq!(epochs, [0, 1, usize::MAX]);
+        let contents = std::fs::read_to_string(log_file_path(dir.path(), 0, None, "csv"));
+        assert_eq!(contents.unwrap(), "s,n\na,2\n");
+
+        std::fs::remove_file(epoch_file_path(dir.path(), None)).unwrap();
+        let mut rotator = LogRotator::<CsvLogWriter>::new_or_panic(dir.path().to_owned(), rotation);
+        assert_eq!(rotator.startup_report().repaired_epoch, Some(2));
+        let report = rotator.check_consistency(false).unwrap();
+        assert!(report.gaps.is_empty());
+        assert!(report.outside_retention.is_empty());
+        assert!(!report.epoch_mismatch);
+    }
+
+    #[test]
+    fn test_startup_sweep() {
+        let dir = tempfile::tempdir().unwrap();
+        for epoch in 0..10 {
+            std::fs::write(log_file_path(dir.path(), epoch, None, "csv"), "").unwrap();
+        }
+        std::fs::write(dir.path().join("0.txt"), "").unwrap();
+        write_epoch(dir.path(), None, 9).unwrap();
+        let rotation = retention_policy(RetentionOrder::Epoch);
+        let rotator = LogRotator::<CsvLogWriter>::new_or_panic(dir.path().to_owned(), rotation);
+        drop(rotator);
+        let epochs = list_log_files(dir.path(), "csv", None)
+            .iter()
+            .map(|f| f.epoch)
+            .collect::<Vec<_>>();
+        assert_eq!(epochs, [8, 9, 10]);
+        assert!(dir.path().join("0.txt").exists());
+        assert_eq!(cur_epoch(dir.path(), None).unwrap(), Some(10));
+
+        let dir = tempfile::tempdir().unwrap();
+        for epoch in [usize::MAX - 2, usize::MAX - 1, usize::MAX, 0] {
+            std::fs::write(log_file_path(dir.path(), epoch, None, "csv"), "").unwrap();
+        }
+        std::fs::write(log_file_path(dir.path(), 100, None, "csv"), "").unwrap();
+        let claimed = BTreeSet::new();
+        delete_old_log_files(1, 3, dir.path(), "csv", None, &FileNaming::Epoch, &claimed).unwrap();
+        let mut epochs = list_log_files(dir.path(), "csv", None)
+            .iter()
+            .map(|f| f.epoch)
+            .collect::<Vec<_>>();
+        epochs.sort_unstable();
+        assert_eq!(epochs, [0, 100, usize::MAX]);
+    }
+
+    #[test]
+    fn test_retention_above_current_epoch() {
+        let dir = tempfile::tempdir().unwrap();
+        for epoch in [0, 1, 2, 100] {
+            std::fs::write(log_file_path(dir.path(), epoch, None, "csv"), "").unwrap();
+        }
+        let epochs = |max_epochs| {
+            let naming = FileNaming::Epoch;
+            delete_old_log_files(
+                2,
+                max_epochs,
+                dir.path(),
+                "csv",
+                None,
+                &naming,
+                &BTreeSet::new(),
+            )
+            .unwrap();
+            let mut epochs = list_log_files(dir.path(), "csv", None)
+                .iter()
+                .map(|f| f.epoch)
+                .collect::<Vec<_>>();
+            epochs.sort_unstable();
+            epochs
+        };
+        assert_eq!(epochs(7), [0, 1, 2, 100]);
+        assert_eq!(epochs(1), [2, 100]);
+    }
+
+    #[test]
+    fn test_retention_by_total_bytes_and_epochs() {
+        let survivors = |max_total_bytes| {
+            let dir = tempfile::tempdir().unwrap();
+            for (epoch, len) in [(0, 10), (1, 10), (2, 30)] {
+                let path = log_file_path(dir.path(), epoch, None, "csv");
+                std::fs::write(path, vec![b'a'; len]).unwrap();
+            }
+            write_epoch(dir.path(), None, 2).unwrap();
+            let rotation = RotationPolicy {
+                max_total_bytes: NonZeroU64::new(max_total_bytes),
+                ..retention_policy(RetentionOrder::Epoch)
+            };
+            LogRotator::<CsvLogWriter>::new_or_panic(dir.path().to_owned(), rotation);
+            let mut epochs = list_log_files(dir.path(), "csv", None)
+                .iter()
+                .map(|f| f.epoch)
+                .collect::<Vec<_>>();
+            epochs.sort_unstable();
+            epochs
+        };
+        assert_eq!(survivors(45), [1, 2, 3]);
+        assert_eq!(survivors(40), [1, 2, 3]);
+        assert_eq!(survivors(35), [2, 3]);
+        assert_eq!(survivors(1), [3]);
+    }
+
+    #[test]
+    fn test_retention_by_total_bytes_across_instances() {
+        let dir = tempfile::tempdir().unwrap();
+        for (instance, epochs) in [("a", 0..=2), ("b", 10..=12)] {
+            for epoch in epochs.clone() {
+                let path = log_file_path(dir.path(), epoch, Some(instance), "csv");
+                std::fs::write(path, vec![b'a'; 10]).unwrap();
+            }
+            write_epoch(dir.path(), Some(instance), *epochs.end()).unwrap();
+        }
+        let rotation = RotationPolicy {
+            instance: Some("a".to_string()),
+            max_epochs: usize::MAX,
+            max_total_bytes: NonZeroU64::new(30),
+            ..retention_policy(RetentionOrder::Epoch)
+        };
+        LogRotator::<CsvLogWriter>::new_or_panic(dir.path().to_owned(), rotation);
+        let mut files = list_log_files(dir.path(), "csv", None)
+            .into_iter()
+            .map(|f| (f.instance.unwrap(), f.epoch))
+            .collect::<Vec<_>>();
+        files.sort_unstable();
+        let expected = [("a", 2), ("a", 3), ("b", 11), ("b", 12)];
+        assert_eq!(files, expected.map(|(i, e)| (i.to_string(), e)));
+    }
+
+    #[test]
+    fn test_reopen_if_missing() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy {
+            reopen_if_missing: true,
+            ..Default::default()
+        };
+        let rotator = LogRotator::new_or_panic(dir.path().to_owned(), rotation);
+        let logger = Logger::new(Arc::new(Mutex::new(rotator)));
+        logger.write(&TestRecord { s: "a", n: 0 });
+        logger.flush();
+        let path = log_file_path(dir.path(), 0, None, "csv");
+        std::fs::remove_file(&path).unwrap();
+        logger.write(&TestRecord { s: "a", n: 1 });
+        logger.flush();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "s,n\na,1\n");
+        assert_eq!(logger.rotator.lock().unwrap().records_written(), 1);
+    }
+
+    #[test]
+    fn test_failed_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let output_dir = dir.path().join("logs");
+        let rotation = RotationPolicy {
+            reopen_if_missing: true,
+            ..Default::default()
+        };
+        let mut rotator = LogRotator::<CsvLogWriter>::new_or_panic(output_dir.clone(), rotation);
+        // The file cannot be recreated where the output directory stood.
+        std::fs::remove_dir_all(&output_dir).unwrap();
+        std::fs::write(&output_dir, "").unwrap();
+        let e = rotator.try_writer().map(drop).unwrap_err();
+        assert_eq!(e.op, RotatorOp::CreateDir);
+        rotator.writer();
+        assert_eq!(rotator.metrics().deferred_failures, 1);
+        assert_eq!(rotator.flush().unwrap_err().op, RotatorOp::CreateDir);
+        rotator.flush().unwrap();
+    }
+
+    #[test]
+    fn test_flusher_tick_defers_failures() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy {
+            max_records: NonZeroUsize::new(1),
+            ..Default::default()
+        };
+        let mut rotator = LogRotator::<CsvLogWriter>::new_or_panic(dir.path().to_owned(), rotation);
+        // The next file cannot be opened where a directory stands.
+        std::fs::create_dir(log_file_path(dir.path(), 1, None, "csv")).unwrap();
+        rotator
+            .writer()
+            .serialize(&TestRecord { s: "a", n: 0 })
+            .unwrap();
+        rotator.try_incr_record_count().unwrap_err();
+        rotator.flusher_tick(true);
+        assert_eq!(rotator.metrics().deferred_failures, 1);
+        assert_eq!(rotator.flush().unwrap_err().op, RotatorOp::OpenLogFile);
+        rotator.flush().unwrap();
+    }
+
+    #[test]
+    fn test_lifetime_records() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy {
+            max_records: NonZeroUsize::new(2),
+            ..Default::default()
+        };
+        let rotator = LogRotator::new_or_panic(dir.path().to_owned(), rotation);
+        let logger = Logger::new(Arc::new(Mutex::new(rotator)));
+        for n in 0..5 {
+            logger.write(&TestRecord { s: "a", n });
+        }
+        let rotator = logger.rotator.lock().unwrap();
+        assert_eq!(rotator.records_written(), 1);
+        assert_eq!(rotator.lifetime_records(), 5);
+    }
+
+    #[test]
+    fn test_open_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy {
+            max_records: NonZeroUsize::new(1),
+            write_footer: true,
+            ..Default::default()
+        };
+        let mut rotator = LogRotator::<CsvLogWriter>::new_or_panic(dir.path().to_owned(), rotation);
+        // The next file cannot be opened where a directory stands.
+        let poisoned = log_file_path(dir.path(), 1, None, "csv");
+        std::fs::create_dir(&poisoned).unwrap();
+        for n in 0..2 {
+            rotator
+                .writer()
+                .serialize(&TestRecord { s: "a", n })
+                .unwrap();
+            let e = rotator.try_incr_record_count().unwrap_err();
+            assert_eq!(e.op, RotatorOp::OpenLogFile);
+        }
+        std::fs::remove_dir(&poisoned).unwrap();
+        rotator
+            .writer()
+            .serialize(&TestRecord { s: "a", n: 2 })
+            .unwrap();
+        rotator.try_incr_record_count().unwrap();
+        assert_eq!(rotator.metrics().epoch, 1);
+        let contents = std::fs::read_to_string(log_file_path(dir.path(), 0, None, "csv"));
+        assert_eq!(contents.unwrap(), "s,n\na,0\na,1\na,2\n#records=3\n");
+    }
+
+    #[test]
+    fn test_rotate_now() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotator = LogRotator::new_or_panic(dir.path().to_owned(), RotationPolicy::default());
+        let logger = Logger::new(Arc::new(Mutex::new(rotator)));
+        logger.write(&TestRecord { s: "a", n: 0 });
+        let rotate_now = |skip_empty| logger.rotator.lock().unwrap().rotate_now(skip_empty);
+        assert!(rotate_now(true).unwrap());
+        assert!(!rotate_now(true).unwrap());
+        logger.write(&TestRecord { s: "b", n: 1 });
+        assert!(rotate_now(false).unwrap());
+        assert!(rotate_now(false).unwrap());
+        logger.flush();
+        for (epoch, contents) in [(0, "s,n\na,0\n"), (1, "s,n\nb,1\n"), (2, ""), (3, "")] {
+            let path = log_file_path(dir.path(), epoch, None, "csv");
+            assert_eq!(std::fs::read_to_string(path).unwrap(), contents);
+        }
+        assert_eq!(logger.rotator.lock().unwrap().metrics().rotations, 3);
+    }
+
+    #[cfg(all(unix, feature = "signal"))]
+    #[test]
+    fn test_rotate_on_sighup() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotator = LogRotator::<CsvLogWriter>::new_or_panic(
+            dir.path().to_owned(),
+            RotationPolicy::default(),
+        );
+        let rotator = Arc::new(Mutex::new(rotator));
+        let handle = rotate_on_sighup(vec![Arc::clone(&rotator)], false).unwrap();
+        let epoch = || rotator.lock().unwrap().metrics().epoch;
+        unsafe { libc::raise(libc::SIGHUP) };
+        let start = std::time::Instant::now();
+        while epoch() == 0 {
+            assert!(start.elapsed() < Duration::from_secs(10));
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(log_file_path(dir.path(), 1, None, "csv").exists());
+        assert_eq!(cur_epoch(dir.path(), None).unwrap(), Some(1));
+
+        handle.unregister();
+        unsafe { libc::raise(libc::SIGHUP) };
+        std::thread::sleep(Duration::from_millis(100));
+        assert_eq!(epoch(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_current_link() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy {
+            max_records: NonZeroUsize::new(2),
+            current_link: true,
+            ..retention_policy(RetentionOrder::Epoch)
+        };
+        let rotator = LogRotator::new_or_panic(dir.path().to_owned(), rotation);
+        let link = dir.path().join("current.csv");
+        assert_eq!(std::fs::read_link(&link).unwrap(), Path::new("0.csv"));
+        let logger = Logger::new(Arc::new(Mutex::new(rotator)));
+        for n in 0..4 {
+            logger.write(&TestRecord { s: "a", n });
+        }
+        logger.flush();
+        assert_eq!(std::fs::read_link(&link).unwrap(), Path::new("2.csv"));
+        logger.write(&TestRecord { s: "b", n: 4 });
+        logger.flush();
+        assert_eq!(std::fs::read_to_string(&link).unwrap(), "s,n\nb,4\n");
+        let report = logger
+            .rotator
+            .lock()
+            .unwrap()
+            .check_consistency(false)
+            .unwrap();
+        assert!(report.unexpected_files.is_empty());
+        drop(logger);
+
+        let rotator = LogRotator::<CsvLogWriter>::new_or_panic(
+            dir.path().to_owned(),
+            retention_policy(RetentionOrder::Epoch),
+        );
+        assert!(std::fs::symlink_metadata(&link).is_err());
+        assert!(dir.path().join("2.csv").exists());
+        drop(rotator);
+    }
+
+    #[test]
+    fn test_dirty() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotator = LogRotator::new_or_panic(
+            dir.path().to_owned(),
+            retention_policy(RetentionOrder::Epoch),
+        );
+        let logger = Logger::new(Arc::new(Mutex::new(rotator)));
+        assert!(!logger.rotator.lock().unwrap().is_dirty());
+        logger.write(&TestRecord { s: "a", n: 0 });
+        assert!(!logger.rotator.lock().unwrap().is_dirty());
+        logger.rotator.lock().unwrap().writer();
+        assert!(logger.rotator.lock().unwrap().is_dirty());
+        logger.flush();
+        assert!(!logger.rotator.lock().unwrap().is_dirty());
+    }
+
+    fn instance_policy(instance: &str) -> RotationPolicy {
+        RotationPolicy {
+            max_records: Some(NonZeroUsize::new(1).unwrap()),
+            max_epochs: 4,
+            instance: Some(instance.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_instances_share_output_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let tick = || std::thread::sleep(Duration::from_millis(10));
+        let w1 = LogRotator::new_or_panic(dir.path().to_owned(), instance_policy("w1"));
+        let w1 = Logger::new(Arc::new(Mutex::new(w1)));
+        tick();
+        let w2 = LogRotator::new_or_panic(dir.path().to_owned(), instance_policy("w2"));
+        assert!(w2.startup_report().is_consistent());
+        let w2 = Logger::new(Arc::new(Mutex::new(w2)));
+        tick();
+        w1.write(&TestRecord { s: "a", n: 0 });
+        tick();
+        w2.write(&TestRecord { s: "b", n: 1 });
+        tick();
+        w1.write(&TestRecord { s: "c", n: 2 });
+
+        assert_eq!(cur_epoch(dir.path(), Some("w1")).unwrap(), Some(2));
+        assert_eq!(cur_epoch(dir.path(), Some("w2")).unwrap(), Some(1));
+        let files = list_log_files(dir.path(), "csv", None)
+            .into_iter()
+            .map(|f| (f.epoch, f.instance.unwrap()))
+            .collect::<Vec<_>>();
+        let expected = [(0, "w2"), (1, "w1"), (1, "w2"), (2, "w1")];
+        let expected = expected.map(|(e, i)| (e, i.to_string()));
+        assert_eq!(files, expected);
+
+        let read = |epoch, instance| {
+            std::fs::read_to_string(log_file_path(dir.path(), epoch, Some(instance), "csv"))
+                .unwrap()
+        };
+        assert_eq!(read(0, "w2"), "s,n\nb,1\n");
+        assert_eq!(read(1, "w1"), "s,n\nc,2\n");
+    }
+
+    #[test]
+    fn test_footer() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotator = LogRotator::new_or_panic(
+            dir.path().to_owned(),
+            RotationPolicy {
+                max_records: Some(NonZeroUsize::new(2).unwrap()),
+                max_epochs: 2,
+                write_footer: true,
+                ..Default::default()
+            },
+        );
+        let logger = Logger::new(Arc::new(Mutex::new(rotator)));
+        for n in 0..3 {
+            logger.write(&TestRecord { s: "a", n });
+        }
+        logger.flush();
+
+        let path = log_file_path(dir.path(), 0, None, "csv");
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "s,n\na,0\na,1\n#records=2\n"
+        );
+        let mut reader = csv::ReaderBuilder::new()
+            .comment(Some(b'#'))
+            .from_path(&path)
+            .unwrap();
+        assert_eq!(reader.records().count(), 2);
+        let path = log_file_path(dir.path(), 1, None, "csv");
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "s,n\na,2\n");
+    }
+
+    #[derive(Debug)]
+    struct CopyCompressor;
+    impl Compressor for CopyCompressor {
+        fn compress(&self, src: &Path, dst: &Path) -> std::io::Result<()> {
+            std::fs::copy(src, dst).map(|_| ())
+        }
+
+        fn extension(&self) -> &str {
+            "cp"
+        }
+    }
+
+    #[test]
+    fn test_compression() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy {
+            max_records: Some(NonZeroUsize::new(1).unwrap()),
+            max_epochs: 2,
+            compression: Some(Arc::new(CopyCompressor)),
+            ..Default::default()
+        };
+        let rotator = LogRotator::new_or_panic(dir.path().to_owned(), rotation.clone());
+        let logger = Logger::new(Arc::new(Mutex::new(rotator)));
+        for n in 0..4 {
+            logger.write(&TestRecord { s: "a", n });
+        }
+        logger
+            .rotator
+            .lock()
+            .unwrap()
+            .wait_for_compression()
+            .unwrap();
+
+        let files = list_log_files(dir.path(), "csv", Some("cp"))
+            .into_iter()
+            .map(|f| f.path.file_name().unwrap().to_str().unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(files, ["3.csv.cp", "4.csv"]);
+        let compressed = dir.path().join("3.csv.cp");
+        assert_eq!(std::fs::read_to_string(compressed).unwrap(), "s,n\na,3\n");
+
+        drop(logger);
+        let rotator = LogRotator::<CsvLogWriter>::new_or_panic(dir.path().to_owned(), rotation);
+        assert!(rotator.startup_report().unexpected_files.is_empty());
+    }
+
+    #[test]
+    fn test_retention_by_mtime_keeps_compressing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy {
+            max_epochs: 2,
+            compression: Some(Arc::new(CopyCompressor)),
+            ..retention_policy(RetentionOrder::Mtime)
+        };
+        let mut rotator = LogRotator::<CsvLogWriter>::new(dir.path().to_owned(), rotation).unwrap();
+        let gate = Arc::new(CompressionGate::default());
+        rotator.set_compression_gate(Arc::clone(&gate));
+        write_uncounted(&mut rotator, 0);
+        rotator.try_rotate_file().unwrap();
+        rotator.wait_for_compression().unwrap();
+        gate.close();
+        write_uncounted(&mut rotator, 1);
+        rotator.flush().unwrap();
+        rotator.try_rotate_file().unwrap();
+        let is_kept = log_file_path(dir.path(), 1, None, "csv").exists();
+        // Dropping the rotator waits for the compression.
+        gate.open();
+        assert!(is_kept);
+        assert!(!dir.path().join("0.csv.cp").exists());
+        rotator.wait_for_compression().unwrap();
+        let compressed = dir.path().join("1.csv.cp");
+        assert_eq!(std::fs::read_to_string(compressed).unwrap(), "s,n\na,1\n");
+    }
+
+    #[test]
+    fn test_retention_by_epoch_keeps_compressing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy {
+            max_epochs: 1,
+            compression: Some(Arc::new(CopyCompressor)),
+            ..retention_policy(RetentionOrder::Epoch)
+        };
+        let mut rotator = LogRotator::<CsvLogWriter>::new(dir.path().to_owned(), rotation).unwrap();
+        let gate = Arc::new(CompressionGate::default());
+        rotator.set_compression_gate(Arc::clone(&gate));
+        write_uncounted(&mut rotator, 0);
+        rotator.try_rotate_file().unwrap();
+        rotator.wait_for_compression().unwrap();
+        gate.close();
+        write_uncounted(&mut rotator, 1);
+        rotator.flush().unwrap();
+        rotator.try_rotate_file().unwrap();
+        let is_kept = log_file_path(dir.path(), 1, None, "csv").exists();
+        let is_reserved = dir.path().join("1.csv.cp").exists();
+        // Dropping the rotator waits for the compression.
+        gate.open();
+        assert!(is_kept);
+        assert!(is_reserved);
+        assert!(!dir.path().join("0.csv.cp").exists());
+        rotator.wait_for_compression().unwrap();
+        let compressed = dir.path().join("1.csv.cp");
+        assert_eq!(std::fs::read_to_string(compressed).unwrap(), "s,n\na,1\n");
+    }
+
+    #[test]
+    fn test_resume() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy {
+            max_records: Some(NonZeroUsize::new(3).unwrap()),
+            resume: true,
+            ..Default::default()
+        };
+        let run = |records: std::ops::Range<usize>| {
+            let rotator = LogRotator::new_or_panic(dir.path().to_owned(), rotation.clone());
+            let logger = Logger::new(Arc::new(Mutex::new(rotator)));
+            for n in records {
+                logger.write(&TestRecord { s: "a", n });
+            }
+            let metrics = logger.rotator.lock().unwrap().metrics();
+            (metrics.epoch, metrics.records_current)
+        };
+        assert_eq!(run(0..2), (0, 2));
+        assert_eq!(run(2..2), (0, 2));
+        assert_eq!(run(2..4), (1, 1));
+        let contents = std::fs::read_to_string(log_file_path(dir.path(), 0, None, "csv"));
+        assert_eq!(contents.unwrap(), "s,n\na,0\na,1\na,2\n");
+        let contents = std::fs::read_to_string(log_file_path(dir.path(), 1, None, "csv"));
+        assert_eq!(contents.unwrap(), "s,n\na,3\n");
+    }
+
+    #[test]
+    fn test_resume_after_crash() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy {
+            max_records: NonZeroUsize::new(4),
+            resume: true,
+            ..Default::default()
+        };
+        let mut rotator =
+            LogRotator::<CsvLogWriter>::new_or_panic(dir.path().to_owned(), rotation.clone());
+        for n in 0..2 {
+            rotator
+                .writer()
+                .serialize(&TestRecord { s: "a", n })
+                .unwrap();
+            rotator.try_incr_record_count().unwrap();
+        }
+        rotator.flush().unwrap();
+        rotator
+            .writer()
+            .serialize(&TestRecord { s: "a", n: 2 })
+            .unwrap();
+        rotator.try_incr_record_count().unwrap();
+        // Dies without flushing the last record.
+        std::mem::forget(rotator);
+
+        let mut rotator = LogRotator::<CsvLogWriter>::new_or_panic(dir.path().to_owned(), rotation);
+        assert_eq!(rotator.metrics().epoch, 0);
+        assert_eq!(rotator.records_written(), 2);
+        let rotated = (2..4)
+            .map(|n| {
+                rotator
+                    .writer()
+                    .serialize(&TestRecord { s: "b", n })
+                    .unwrap();
+                rotator.try_incr_record_count().unwrap()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(rotated, [false, true]);
+        let contents = std::fs::read_to_string(log_file_path(dir.path(), 0, None, "csv"));
+        assert_eq!(contents.unwrap(), "s,n\na,0\na,1\nb,2\nb,3\n");
+    }
+
+    #[test]
+    fn test_on_rotate() {
+        let rotated = |compression: Option<Arc<dyn Compressor>>| {
+            let dir = tempfile::tempdir().unwrap();
+            let rotation = RotationPolicy {
+                max_records: Some(NonZeroUsize::new(1).unwrap()),
+                compression,
+                ..Default::default()
+            };
+            let rotated = Arc::new(Mutex::new(vec![]));
+            let mut rotator = LogRotator::new_or_panic(dir.path().to_owned(), rotation);
+            rotator.set_on_rotate(Box::new({
+                let rotated = Arc::clone(&rotated);
+                move |event| {
+                    let path = &event.old_path;
+                    let contents = std::fs::read_to_string(path).unwrap();
+                    let name = path.file_name().unwrap().to_str().unwrap().to_string();
+                    rotated
+                        .lock()
+                        .unwrap()
+                        .push((name, event.old_epoch, contents));
+                    false
+                }
+            }));
+            let logger = Logger::new(Arc::new(Mutex::new(rotator)));
+            for n in 0..2 {
+                logger.write(&TestRecord { s: "a", n });
+            }
+            let rotator = Arc::into_inner(logger.rotator).unwrap();
+            rotator.into_inner().unwrap().shutdown(true).unwrap();
+            let rotated = rotated.lock().unwrap().clone();
+            rotated
+        };
+        let expected = |ext: &str| {
+            (0..3)
+                .map(|n| {
+                    let contents = if n < 2 {
+                        format!("s,n\na,{n}\n")
+                    } else {
+                        String::new()
+                    };
+                    (format!("{n}.{ext}"), n, contents)
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(rotated(None), expected("csv"));
+        assert_eq!(rotated(Some(Arc::new(CopyCompressor))), expected("csv.cp"));
+    }
+
+    /// Brackets every file with a preamble and a trailer.
+    #[derive(Debug)]
+    struct Bracketed(crate::writers::text::TextLogWriter);
+    impl LogWriter for Bracketed {
+        fn flush(&mut self) -> std::io::Result<()> {
+            LogWriter::flush(&mut self.0)
+        }
+
+        fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+            LogWriter::open(path).map(Self)
+        }
+
+        fn file_extension() -> &'static str {
+            "log"
+        }
+
+        fn on_open(&mut self) -> std::io::Result<usize> {
+            writeln!(self.0, "[")?;
+            Ok(1)
+        }
+
+        fn on_close(&mut self) -> std::io::Result<usize> {
+            writeln!(self.0, "]")?;
+            Ok(1)
+        }
+
+        fn write_footer(&mut self, records_written: usize) -> std::io::Result<()> {
+            writeln!(self.0, "={records_written}")
+        }
+    }
+
+    /// Skips the brackets and the footer.
+    impl crate::reader::LogReaderFor<Bracketed> for usize {
+        fn read_records(reader: impl std::io::Read) -> impl Iterator<Item = std::io::Result<Self>> {
+            std::io::BufRead::lines(std::io::BufReader::new(reader))
+                .filter(|line| {
+                    let line = line.as_deref().unwrap_or_default();
+                    !matches!(line, "[" | "]") && !line.starts_with('=')
+                })
+                .map(|line| {
+                    let line = line?;
+                    line.parse()
+                        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+                })
+        }
+    }
+
+    #[test]
+    fn test_open_close_hooks() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy {
+            max_records: NonZeroUsize::new(1),
+            ..Default::default()
+        };
+        let mut rotator = LogRotator::<Bracketed>::new_or_panic(dir.path().to_owned(), rotation);
+        for n in 0..2 {
+            writeln!(rotator.writer().0, "{n}").unwrap();
+            rotator.try_incr_record_count().unwrap();
+        }
+        rotator.shutdown(true).unwrap();
+        for (epoch, contents) in [(0, "[\n0\n]\n"), (1, "[\n1\n]\n"), (2, "[\n]\n")] {
+            let path = log_file_path(dir.path(), epoch, None, "log");
+            assert_eq!(std::fs::read_to_string(path).unwrap(), contents);
+        }
+    }
+
+    #[test]
+    fn test_schema_change_within_file() {
+        /// Has the schema it is told to have.
+        #[derive(Debug)]
+        struct Shifting(crate::writers::text::TextLogWriter, Option<u64>);
+        impl LogWriter for Shifting {
+            fn flush(&mut self) -> std::io::Result<()> {
+                LogWriter::flush(&mut self.0)
+            }
+
+            fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+                LogWriter::open(path).map(|writer| Self(writer, None))
+            }
+
+            fn file_extension() -> &'static str {
+                "log"
+            }
+
+            fn schema(&self) -> Option<u64> {
+                self.1
+            }
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy {
+            max_records: NonZeroUsize::new(3),
+            ..Default::default()
+        };
+        let mut rotator = LogRotator::<Shifting>::new_or_panic(dir.path().to_owned(), rotation);
+        rotator.writer().1 = Some(1);
+        rotator.try_incr_record_count().unwrap();
+        rotator.writer().1 = Some(2);
+        let e = rotator.try_incr_record_count().unwrap_err();
+        assert_eq!(e.op, RotatorOp::CheckSchema);
+        let e = rotator.try_incr_record_count().unwrap_err();
+        assert_eq!(e.op, RotatorOp::CheckSchema);
+        assert_eq!(rotator.metrics().epoch, 1);
+        rotator.writer().1 = Some(2);
+        rotator.try_incr_record_count().unwrap();
+    }
+
+    #[test]
+    fn test_failed_close() {
+        /// Cannot finish its files.
+        #[derive(Debug)]
+        struct Unclosable(crate::writers::text::TextLogWriter);
+        impl LogWriter for Unclosable {
+            fn flush(&mut self) -> std::io::Result<()> {
+                LogWriter::flush(&mut self.0)
+            }
+
+            fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+                LogWriter::open(path).map(Self)
+            }
+
+            fn file_extension() -> &'static str {
+                "log"
+            }
+
+            fn on_close(&mut self) -> std::io::Result<usize> {
+                Err(std::io::ErrorKind::StorageFull.into())
+            }
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy {
+            max_records: NonZeroUsize::new(1),
+            ..Default::default()
+        };
+        let mut rotator = LogRotator::<Unclosable>::new_or_panic(dir.path().to_owned(), rotation);
+        writeln!(rotator.writer().0, "0").unwrap();
+        let e = rotator.try_incr_record_count().unwrap_err();
+        assert_eq!(e.op, RotatorOp::Close);
+        assert_eq!(rotator.metrics().epoch, 0);
+        let path = log_file_path(dir.path(), 0, None, "log");
+        assert_eq!(rotator.current_path(), path);
+    }
+
+    #[test]
+    fn test_durability() {
+        static SYNCS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        /// Counts the syncs.
+        #[derive(Debug)]
+        struct Syncing(crate::writers::text::TextLogWriter);
+        impl LogWriter for Syncing {
+            fn flush(&mut self) -> std::io::Result<()> {
+                LogWriter::flush(&mut self.0)
+            }
+
+            fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+                LogWriter::open(path).map(Self)
+            }
+
+            fn file_extension() -> &'static str {
+                "log"
+            }
+
+            fn sync(&mut self) -> std::io::Result<()> {
+                SYNCS.fetch_add(1, atomic::Ordering::Relaxed);
+                self.0.sync()
+            }
+        }
+
+        for (durability, expected) in [
+            (Durability::None, 0),
+            (Durability::FsyncOnRotate, 0),
+            (Durability::FsyncOnFlush, 2),
+            (Durability::Both, 2),
+        ] {
+            SYNCS.store(0, atomic::Ordering::Relaxed);
+            let dir = tempfile::tempdir().unwrap();
+            let rotation = RotationPolicy {
+                max_records: NonZeroUsize::new(2),
+                max_epochs: 1,
+                durability,
+                ..Default::default()
+            };
+            let mut rotator = LogRotator::<Syncing>::new_or_panic(dir.path().to_owned(), rotation);
+            for n in 0..3 {
+                writeln!(rotator.writer().0, "{n}").unwrap();
+                rotator.try_incr_record_count().unwrap();
+                rotator.flush().unwrap();
+                rotator.flush().unwrap();
+            }
+            assert_eq!(
+                SYNCS.load(atomic::Ordering::Relaxed),
+                expected,
+                "{durability:?}"
+            );
+            assert_eq!(list_log_files(dir.path(), "log", None).len(), 1);
+        }
+    }
+
+    #[test]
+    fn test_flusher_skips_idle_rotators() {
+        static FLUSHES: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        /// Counts the flushes.
+        #[derive(Debug)]
+        struct Flushing(crate::writers::text::TextLogWriter);
+        impl LogWriter for Flushing {
+            fn flush(&mut self) -> std::io::Result<()> {
+                FLUSHES.fetch_add(1, atomic::Ordering::Relaxed);
+                LogWriter::flush(&mut self.0)
+            }
+
+            fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+                LogWriter::open(path).map(Self)
+            }
+
+            fn file_extension() -> &'static str {
+                "log"
+            }
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy {
+            max_records: NonZeroUsize::new(2),
+            ..Default::default()
+        };
+        let rotators = (0..100)
+            .map(|i| {
+                let output_dir = dir.path().join(i.to_string());
+                let rotator = LogRotator::<Flushing>::new_or_panic(output_dir, rotation.clone());
+                Arc::new(Mutex::new(rotator))
+            })
+            .collect::<Vec<_>>();
+        let mut weak = rotators.iter().map(Arc::downgrade).collect::<Vec<_>>();
+        for _ in 0..10 {
+            assert_eq!(flush_rotators(&mut weak, true), (false, false));
+        }
+        assert_eq!(FLUSHES.load(atomic::Ordering::Relaxed), 0);
+
+        writeln!(rotators[7].lock().unwrap().writer().0, "a").unwrap();
+        rotators[7].lock().unwrap().try_incr_record_count().unwrap();
+        assert_eq!(flush_rotators(&mut weak, true), (true, false));
+        assert_eq!(flush_rotators(&mut weak, true), (false, false));
+        assert_eq!(FLUSHES.load(atomic::Ordering::Relaxed), 1);
+        let path = log_file_path(dir.path().join("7"), 0, None, "log");
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "a\n");
+    }
+
+    #[test]
+    fn test_set_on_rotate() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy {
+            max_records: NonZeroUsize::new(1),
+            max_epochs: 2,
+            ..Default::default()
+        };
+        let mut rotator = LogRotator::new_or_panic(dir.path().to_owned(), rotation);
+        let events = Arc::new(Mutex::new(vec![]));
+        rotator.set_on_rotate(Box::new({
+            let events = Arc::clone(&events);
+            move |event| {
+                assert!(event.old_path.exists());
+                let mut events = events.lock().unwrap();
+                events.push(event.clone());
+                events.len() == 1
+            }
+        }));
+        let logger = Logger::new(Arc::new(Mutex::new(rotator)));
+        for n in 0..4 {
+            logger.write(&TestRecord { s: "a", n });
+            logger.rotator.lock().unwrap().wait_for_on_rotate();
+        }
+        let events = events.lock().unwrap().clone();
+        let expected = (0..4)
+            .map(|epoch| RotationEvent {
+                old_epoch: epoch,
+                old_path: log_file_path(dir.path(), epoch, None, "csv"),
+                new_epoch: epoch + 1,
+                new_path: Some(log_file_path(dir.path(), epoch + 1, None, "csv")),
+                schema_changed: false,
+                dropped: 0,
+                sampled_out: 0,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(events, expected);
+        let epochs = || {
+            let mut epochs = list_log_files(dir.path(), "csv", None)
+                .iter()
+                .map(|f| f.epoch)
+                .collect::<Vec<_>>();
+            epochs.sort_unstable();
+            epochs
+        };
+        assert_eq!(epochs(), [0, 3, 4]);
+
+        logger.rotator.lock().unwrap().release(0);
+        logger.write(&TestRecord { s: "a", n: 4 });
+        assert_eq!(epochs(), [4, 5]);
+    }
 
-        logger.write(&TestRecord { s: "c", n: 2 });
+    #[test]
+    fn test_set_on_rotate_logs() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy {
+            max_records: NonZeroUsize::new(2),
+            ..Default::default()
+        };
+        let rotator = LogRotator::new_or_panic(dir.path().to_owned(), rotation);
+        let rotator = Arc::new(Mutex::new(rotator));
+        let (rotated, events) = std::sync::mpsc::channel();
+        rotator.lock().unwrap().set_on_rotate(Box::new({
+            let rotator = Arc::downgrade(&rotator);
+            move |event| {
+                let n = event.old_epoch;
+                Logger::new(rotator.upgrade().unwrap()).write(&TestRecord { s: "rotated", n });
+                rotated.send(event.clone()).unwrap();
+                false
+            }
+        }));
+        let logger = Logger::new(Arc::clone(&rotator));
+        for n in 0..2 {
+            logger.write(&TestRecord { s: "a", n });
+        }
+        let event = events.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(event.old_epoch, 0);
         logger.flush();
-        let path = log_file_path(dir.path(), 0, "csv");
-        assert!(path.exists());
-        let path = log_file_path(dir.path(), 1, "csv");
-        assert!(path.exists());
-        let path = log_file_path(dir.path(), 2, "csv");
-        assert!(!path.exists());
+        let contents = std::fs::read_to_string(log_file_path(dir.path(), 1, None, "csv"));
+        assert_eq!(contents.unwrap(), "s,n\nrotated,0\n");
+    }
 
-        logger.write(&TestRecord { s: "d", n: 3 });
-        let path = log_file_path(dir.path(), 0, "csv");
-        assert!(!path.exists());
-        let path = log_file_path(dir.path(), 1, "csv");
-        assert!(path.exists());
-        let path = log_file_path(dir.path(), 2, "csv");
+    #[test]
+    fn test_set_on_rotate_timestamped_mtime() {
+        let dir = tempfile::tempdir().unwrap();
+        let start: jiff::Zoned = "2024-01-01T00:00:00[UTC]".parse().unwrap();
+        let clock = Arc::new(crate::clock::MockClock::new(start));
+        let rotation = RotationPolicy {
+            max_records: NonZeroUsize::new(1),
+            max_epochs: 2,
+            retention: RetentionOrder::Mtime,
+            naming: FileNaming::EpochTimestamp,
+            clock: Some(Arc::clone(&clock) as _),
+            ..Default::default()
+        };
+        let mut rotator = LogRotator::new_or_panic(dir.path().to_owned(), rotation);
+        rotator.set_on_rotate(Box::new(|event| event.old_epoch == 0));
+        let logger = Logger::new(Arc::new(Mutex::new(rotator)));
+        for n in 0..4 {
+            clock.advance(jiff::SignedDuration::from_secs(1));
+            logger.write(&TestRecord { s: "a", n });
+            logger.rotator.lock().unwrap().wait_for_on_rotate();
+        }
+        let mut epochs = list_named_log_files(dir.path(), "csv", None, &FileNaming::EpochTimestamp)
+            .iter()
+            .map(|f| f.epoch)
+            .collect::<Vec<_>>();
+        epochs.sort_unstable();
+        assert_eq!(epochs, [0, 3, 4]);
+    }
+
+    #[test]
+    fn test_interrupted_compression() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("0.csv"), "s,n\na,0\n").unwrap();
+        std::fs::write(dir.path().join("0.csv.cp"), "s,n\n").unwrap();
+        std::fs::write(dir.path().join("1.csv"), "s,n\na,1\n").unwrap();
+        write_epoch(dir.path(), None, 1).unwrap();
+        let rotation = RotationPolicy {
+            compression: Some(Arc::new(CopyCompressor)),
+            ..Default::default()
+        };
+        let rotator = LogRotator::<CsvLogWriter>::new_or_panic(dir.path().to_owned(), rotation);
+        assert!(rotator.startup_report().is_consistent());
+        assert!(!dir.path().join("0.csv").exists());
+        let compressed = dir.path().join("0.csv.cp");
+        assert_eq!(std::fs::read_to_string(compressed).unwrap(), "s,n\na,0\n");
+        assert!(dir.path().join("1.csv").exists());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_spawn_flushers_async() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotator = LogRotator::<CsvLogWriter>::new_or_panic(
+            dir.path().to_owned(),
+            RotationPolicy::default(),
+        );
+        let rotator = Arc::new(Mutex::new(rotator));
+        let cancel = tokio_util::sync::CancellationToken::new();
+        let task = spawn_flushers_async(
+            vec![Arc::clone(&rotator)],
+            Duration::from_millis(10),
+            cancel.clone(),
+        );
+        Logger::new(Arc::clone(&rotator)).write(&TestRecord { s: "a", n: 0 });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let path = log_file_path(dir.path(), 0, None, "csv");
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "s,n\na,0\n");
+        drop(rotator);
+        tokio::time::timeout(Duration::from_secs(1), task)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let rotator = LogRotator::<CsvLogWriter>::new_or_panic(
+            dir.path().to_owned(),
+            RotationPolicy::default(),
+        );
+        let rotator = Arc::new(Mutex::new(rotator));
+        let task = spawn_flushers_async(vec![rotator], Duration::from_secs(1), cancel.clone());
+        cancel.cancel();
+        tokio::time::timeout(Duration::from_millis(100), task)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    #[test]
+    fn test_flush_on_drop() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut rotator = LogRotator::<CsvLogWriter>::new_or_panic(
+            dir.path().to_owned(),
+            RotationPolicy::default(),
+        );
+        rotator
+            .writer()
+            .serialize(&TestRecord { s: "a", n: 0 })
+            .unwrap();
+        rotator.try_incr_record_count().unwrap();
+        drop(rotator);
+        let path = log_file_path(dir.path(), 0, None, "csv");
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "s,n\na,0\n");
+    }
+
+    #[test]
+    fn test_shutdown() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy {
+            write_footer: true,
+            compression: Some(Arc::new(CopyCompressor)),
+            ..Default::default()
+        };
+        let mut rotator =
+            LogRotator::<CsvLogWriter>::new_or_panic(dir.path().to_owned(), rotation.clone());
+        rotator
+            .writer()
+            .serialize(&TestRecord { s: "a", n: 0 })
+            .unwrap();
+        rotator.try_incr_record_count().unwrap();
+        rotator.shutdown(true).unwrap();
+        assert!(!dir.path().join("0.csv").exists());
+        let compressed = dir.path().join("0.csv.cp");
+        assert_eq!(
+            std::fs::read_to_string(compressed).unwrap(),
+            "s,n\na,0\n#records=1\n"
+        );
+        assert_eq!(cur_epoch(dir.path(), None).unwrap(), Some(0));
+
+        let rotator = LogRotator::<CsvLogWriter>::new_or_panic(dir.path().to_owned(), rotation);
+        assert!(rotator.startup_report().is_consistent());
+        rotator.shutdown(false).unwrap();
+        assert!(dir.path().join("1.csv").exists());
+    }
+
+    #[test]
+    fn test_timestamp_naming() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy {
+            max_records: Some(NonZeroUsize::new(1).unwrap()),
+            max_epochs: 2,
+            naming: FileNaming::EpochTimestamp,
+            ..Default::default()
+        };
+        let rotator = LogRotator::new_or_panic(dir.path().to_owned(), rotation.clone());
+        let logger = Logger::new(Arc::new(Mutex::new(rotator)));
+        for n in 0..3 {
+            logger.write(&TestRecord { s: "a", n });
+        }
+        logger.flush();
+
+        let files = list_log_files(dir.path(), "csv", None);
+        let epochs = files.iter().map(|f| f.epoch).collect::<Vec<_>>();
+        assert_eq!(epochs, [2, 3]);
+        let file_name = files[0].path.file_name().unwrap().to_str().unwrap();
+        let (epoch, timestamp) = file_name
+            .strip_suffix(".csv")
+            .unwrap()
+            .split_once('_')
+            .unwrap();
+        assert_eq!(epoch, "2");
+        jiff::civil::DateTime::strptime(TIMESTAMP_FORMAT, timestamp).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&files[0].path).unwrap(),
+            "s,n\na,2\n"
+        );
+
+        drop(logger);
+        let rotator = LogRotator::<CsvLogWriter>::new_or_panic(dir.path().to_owned(), rotation);
+        assert!(rotator.startup_report().is_consistent());
+        drop(rotator);
+        let epochs = list_log_files(dir.path(), "csv", None)
+            .iter()
+            .map(|f| f.epoch)
+            .collect::<Vec<_>>();
+        assert_eq!(epochs, [3, 4]);
+    }
+
+    #[test]
+    fn test_cron_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy::builder()
+            .cron(crate::cron::Cron::parse("0 3,15 * * *").unwrap())
+            .build()
+            .unwrap();
+        let mut rotator = LogRotator::<CsvLogWriter>::new_or_panic(dir.path().to_owned(), rotation);
+        let mut poll = |t: &str| {
+            let t: jiff::Zoned = format!("2024-03-01T{t}[UTC]").parse().unwrap();
+            rotator.try_rotate_file_at(t).unwrap();
+            rotator.metrics().rotations
+        };
+        for t in ["02:58:00", "02:59:30", "02:59:59"] {
+            assert_eq!(poll(t), 0);
+        }
+        for t in ["03:00:00", "03:00:01", "03:00:59", "03:01:00", "14:59:59"] {
+            assert_eq!(poll(t), 1);
+        }
+        assert_eq!(poll("15:00:30"), 2);
+        assert_eq!(poll("15:00:31"), 2);
+    }
+
+    #[test]
+    fn test_rotation_outcome() {
+        let dir = tempfile::tempdir().unwrap();
+        let start: jiff::Zoned = "2024-01-01T23:59[UTC]".parse().unwrap();
+        let clock = Arc::new(crate::clock::MockClock::new(start));
+        let rotation = RotationPolicy {
+            max_records: NonZeroUsize::new(2),
+            time: Some(TimePast::new(Arc::new(DailyContains))),
+            clock: Some(Arc::clone(&clock) as _),
+            ..Default::default()
+        };
+        let mut rotator = LogRotator::<CsvLogWriter>::new_or_panic(dir.path().to_owned(), rotation);
+        let rotated = (0..4)
+            .map(|_| rotator.try_incr_record_count().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(rotated, [false, true, false, true]);
+        assert!(!rotator.try_rotate_file().unwrap());
+        clock.advance(jiff::SignedDuration::from_secs(60));
+        assert!(rotator.try_rotate_file().unwrap());
+        assert_eq!(rotator.metrics().epoch, 3);
+    }
+
+    #[test]
+    fn test_mock_clock() {
+        let dir = tempfile::tempdir().unwrap();
+        let start: jiff::Zoned = "2024-01-01T23:59:58[UTC]".parse().unwrap();
+        let clock = Arc::new(crate::clock::MockClock::new(start));
+        let rotation = RotationPolicy::builder()
+            .daily()
+            .naming(FileNaming::EpochTimestamp)
+            .clock(Arc::clone(&clock) as _)
+            .build()
+            .unwrap();
+        let mut rotator = LogRotator::<CsvLogWriter>::new_or_panic(dir.path().to_owned(), rotation);
+        for epoch in [0, 0, 1, 1] {
+            rotator.try_rotate_file().unwrap();
+            assert_eq!(rotator.metrics().epoch, epoch);
+            clock.advance(jiff::SignedDuration::from_secs(1));
+        }
+        let midnight: jiff::Timestamp = "2024-01-02T00:00:00Z".parse().unwrap();
+        assert_eq!(rotator.metrics().last_rotation, Some(midnight));
+        let path = log_file_path(dir.path(), "1_2024-01-02T00-00-00", None, "csv");
         assert!(path.exists());
-        let path = log_file_path(dir.path(), 3, "csv");
-        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_extension() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy::builder()
+            .max_records(1)
+            .max_epochs(2)
+            .extension("log")
+            .current_link(true)
+            .build()
+            .unwrap();
+        let mut rotator =
+            LogRotator::<CsvLogWriter>::new_or_panic(dir.path().to_owned(), rotation.clone());
+        for n in 0..4 {
+            let record = TestRecord { s: "a", n };
+            rotator.writer().serialize(&record).unwrap();
+            rotator.try_incr_record_count().unwrap();
+        }
+        drop(rotator);
+        let files = list_log_files(dir.path(), "log", None);
+        let mut epochs = files.iter().map(|f| f.epoch).collect::<Vec<_>>();
+        epochs.sort_unstable();
+        assert_eq!(epochs, [3, 4]);
+        assert!(list_log_files(dir.path(), "csv", None).is_empty());
+        assert!(dir.path().join("current.log").exists());
+
+        let rotator = LogRotator::<CsvLogWriter>::new_or_panic(dir.path().to_owned(), rotation);
+        assert_eq!(rotator.metrics().epoch, 5);
+    }
+
+    #[test]
+    fn test_clock_time_zone() {
+        let dir = tempfile::tempdir().unwrap();
+        // 14:59:59 in UTC, so only the local date changes on the next second.
+        let start: jiff::Zoned = "2024-01-01T23:59:59+09:00[+09:00]".parse().unwrap();
+        let clock = Arc::new(crate::clock::MockClock::new(start));
+        let (rotator, flusher) = LogRotator::<CsvLogWriter>::builder(dir.path())
+            .daily()
+            .clock(Arc::clone(&clock) as _)
+            .flush_every(Duration::from_secs(60 * 60))
+            .build()
+            .unwrap();
+        flusher.shutdown();
+        let mut rotator = rotator.lock().unwrap();
+        let mut rotations = 0;
+        for _ in 0..3 {
+            rotations += usize::from(rotator.try_rotate_file().unwrap());
+            clock.advance(jiff::SignedDuration::from_secs(1));
+        }
+        assert_eq!(rotations, 1);
+        let midnight: jiff::Timestamp = "2024-01-01T15:00:00Z".parse().unwrap();
+        assert_eq!(rotator.metrics().last_rotation, Some(midnight));
+    }
+
+    #[test]
+    fn test_hourly_cron_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy::builder()
+            .cron(crate::cron::Cron::parse("0 * * * *").unwrap())
+            .build()
+            .unwrap();
+        let mut rotator = LogRotator::<CsvLogWriter>::new_or_panic(dir.path().to_owned(), rotation);
+        let mut poll = |t: &str| {
+            let t: jiff::Zoned = format!("2024-03-01T{t}[UTC]").parse().unwrap();
+            rotator.try_rotate_file_at(t).unwrap();
+            rotator.metrics().epoch
+        };
+        assert_eq!(poll("00:30:00"), 0);
+        assert_eq!(poll("01:00:00"), 1);
+        assert_eq!(poll("01:59:59"), 1);
+        assert_eq!(poll("02:00:10"), 2);
+        // Polls that skip hours rotate once.
+        assert_eq!(poll("05:00:00"), 3);
+        assert!(log_file_path(dir.path(), 3, None, "csv").exists());
+    }
+
+    #[test]
+    fn test_template_naming() {
+        let namer = TemplateFileNamer::new("app-%Y-%m-%d-{epoch}").unwrap();
+        assert_eq!(namer.parse_epoch("app-2024-01-15-3.csv", "csv"), Some(3));
+        assert_eq!(namer.parse_epoch("app-2024-01-15-30.csv", "csv"), Some(30));
+        assert_eq!(namer.parse_epoch("app-2024-01-15-03.csv", "csv"), None);
+        assert_eq!(namer.parse_epoch("app-2024-01-15-3.log", "csv"), None);
+        assert_eq!(namer.parse_epoch("app-x-3.csv", "csv"), None);
+        assert_eq!(namer.parse_epoch("3.csv", "csv"), None);
+        for template in ["app", "{epoch}-{epoch}", "a/{epoch}", "%Q{epoch}"] {
+            assert_eq!(
+                TemplateFileNamer::new(template).unwrap_err(),
+                PolicyError::InvalidTemplate
+            );
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy {
+            max_records: Some(NonZeroUsize::new(1).unwrap()),
+            max_epochs: 2,
+            naming: FileNaming::Custom(Arc::new(namer.clone())),
+            clock: Some(Arc::new(crate::clock::MockClock::new(
+                "2024-01-15T23:00:00[Asia/Tokyo]".parse().unwrap(),
+            ))),
+            ..Default::default()
+        };
+        let rotator = LogRotator::new_or_panic(dir.path().to_owned(), rotation.clone());
+        let logger = Logger::new(Arc::new(Mutex::new(rotator)));
+        for n in 0..3 {
+            logger.write(&TestRecord { s: "a", n });
+        }
+        drop(logger);
+        let files = list_named_log_files(dir.path(), "csv", None, &rotation.naming);
+        let epochs = files.iter().map(|f| f.epoch).collect::<Vec<_>>();
+        assert_eq!(epochs, [2, 3]);
+        let name = files[1].path.file_name().unwrap().to_str().unwrap();
+        assert_eq!(name, "app-2024-01-15-3.csv");
+    }
+
+    #[derive(Debug)]
+    struct PrefixNamer;
+    impl FileNamer for PrefixNamer {
+        fn path(&self, dir: &Path, epoch: usize, extension: &str, _now: &jiff::Zoned) -> PathBuf {
+            dir.join(format!("svc{epoch}.{extension}"))
+        }
+
+        fn parse_epoch(&self, file_name: &str, extension: &str) -> Option<usize> {
+            EpochFileNamer.parse_epoch(file_name.strip_prefix("svc")?, extension)
+        }
+    }
+
+    #[test]
+    fn test_custom_naming() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy {
+            max_records: Some(NonZeroUsize::new(1).unwrap()),
+            max_epochs: 2,
+            compression: Some(Arc::new(CopyCompressor)),
+            naming: FileNaming::Custom(Arc::new(PrefixNamer)),
+            ..Default::default()
+        };
+        let rotator = LogRotator::new_or_panic(dir.path().to_owned(), rotation.clone());
+        let logger = Logger::new(Arc::new(Mutex::new(rotator)));
+        for n in 0..3 {
+            logger.write(&TestRecord { s: "a", n });
+        }
+        logger
+            .rotator
+            .lock()
+            .unwrap()
+            .wait_for_compression()
+            .unwrap();
+        drop(logger);
+        let file_names = || {
+            let naming = FileNaming::Custom(Arc::new(PrefixNamer));
+            list_named_log_files(dir.path(), "csv", Some("cp"), &naming)
+                .into_iter()
+                .map(|f| f.path.file_name().unwrap().to_str().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(file_names(), ["svc2.csv.cp", "svc3.csv"]);
+        assert!(list_log_files(dir.path(), "csv", Some("cp")).is_empty());
+
+        let rotator = LogRotator::<CsvLogWriter>::new_or_panic(dir.path().to_owned(), rotation);
+        assert!(rotator.startup_report().is_consistent());
+        assert_eq!(rotator.startup_report().present_epochs, [2, 3]);
+        drop(rotator);
+        assert_eq!(file_names(), ["svc3.csv", "svc4.csv"]);
+    }
+
+    #[test]
+    fn test_watermark() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotator = LogRotator::new_or_panic(
+            dir.path().to_owned(),
+            RotationPolicy {
+                max_epochs: 4,
+                watermark: Some(TimePast::new(Arc::new(DailyContains))),
+                ..Default::default()
+            },
+        );
+        let rotator = Arc::new(Mutex::new(rotator));
+        let logger = Logger::new(Arc::clone(&rotator));
+        let watermarks = [
+            "2024-03-01T10:00",
+            "2024-03-01T23:00",
+            "2024-03-01T09:00",
+            "2024-03-02T01:00",
+            "2024-03-01T22:00",
+            "2024-03-02T05:00",
+            "2024-03-03T00:00",
+        ];
+        for (n, t) in watermarks.into_iter().enumerate() {
+            let t: jiff::Zoned = format!("{t}[UTC]").parse().unwrap();
+            rotator.lock().unwrap().advance_watermark(t).unwrap();
+            logger.write(&TestRecord { s: "a", n });
+        }
+        logger.flush();
+
+        let read = |epoch| std::fs::read_to_string(log_file_path(dir.path(), epoch, None, "csv"));
+        assert_eq!(read(0).unwrap(), "s,n\na,0\na,1\na,2\n");
+        assert_eq!(read(1).unwrap(), "s,n\na,3\na,4\na,5\n");
+        assert_eq!(read(2).unwrap(), "s,n\na,6\n");
+        let metrics = rotator.lock().unwrap().metrics();
+        assert_eq!(metrics.watermark_regressions, 2);
+        let day_3: jiff::Timestamp = "2024-03-03T00:00Z".parse().unwrap();
+        assert_eq!(metrics.watermark_start, Some(day_3));
+        assert_eq!(metrics.watermark_end, Some(day_3));
+    }
+
+    #[test]
+    fn test_watermark_failed_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy {
+            max_records: None,
+            watermark: Some(TimePast::new(Arc::new(DailyContains))),
+            ..Default::default()
+        };
+        let mut rotator = LogRotator::<CsvLogWriter>::new_or_panic(dir.path().to_owned(), rotation);
+        let t = |t: &str| format!("{t}[UTC]").parse::<jiff::Zoned>().unwrap();
+        assert!(!rotator.advance_watermark(t("2024-03-01T10:00")).unwrap());
+        // The next file cannot be opened where a directory stands.
+        std::fs::create_dir(log_file_path(dir.path(), 1, None, "csv")).unwrap();
+        let e = rotator
+            .advance_watermark(t("2024-03-02T01:00"))
+            .unwrap_err();
+        assert_eq!(e.op, RotatorOp::OpenLogFile);
+        assert_eq!(rotator.metrics().epoch, 0);
+        let day_2: jiff::Timestamp = "2024-03-02T01:00Z".parse().unwrap();
+        assert_eq!(rotator.metrics().watermark_end, Some(day_2));
+    }
+
+    #[test]
+    fn test_flush_interval_bounds() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotator = LogRotator::<CsvLogWriter>::new_or_panic(
+            dir.path().to_owned(),
+            retention_policy(RetentionOrder::Epoch),
+        );
+        let rotator = Arc::new(Mutex::new(rotator));
+        let res = try_spawn_flushers(vec![Arc::clone(&rotator)], Duration::ZERO);
+        assert!(matches!(res, Err(FlushIntervalError::TooShort(_))));
+        let res = try_spawn_flushers(vec![Arc::clone(&rotator)], Duration::MAX);
+        assert!(matches!(res, Err(FlushIntervalError::TooLong(_))));
+
+        let flusher = spawn_flushers(vec![Arc::clone(&rotator)], Duration::ZERO);
+        assert_eq!(
+            flusher.clamped(),
+            Some(FlushIntervalError::TooShort(Duration::ZERO))
+        );
+        let clamped = spawn_flushers(vec![Arc::clone(&rotator)], Duration::MAX);
+        assert_eq!(
+            clamped.clamped(),
+            Some(FlushIntervalError::TooLong(Duration::MAX))
+        );
+        clamped.shutdown();
+        let interval = AdaptiveInterval {
+            base: Duration::from_secs(2),
+            max: Duration::from_secs(1),
+            idle_ticks: 1,
+        };
+        let clamped = spawn_flushers_adaptive(vec![Arc::clone(&rotator)], interval);
+        assert!(matches!(
+            clamped.clamped(),
+            Some(FlushIntervalError::BaseAboveMax { .. })
+        ));
+        clamped.shutdown();
+        let unclamped = spawn_flushers(
+            vec![Arc::clone(&rotator)],
+            crate::flusher::MIN_FLUSH_INTERVAL,
+        );
+        assert_eq!(unclamped.clamped(), None);
+        unclamped.shutdown();
+
+        rotator
+            .lock()
+            .unwrap()
+            .writer()
+            .writer()
+            .serialize(TestRecord { s: "a", n: 0 })
+            .unwrap();
+        std::thread::sleep(Duration::from_millis(200));
+        let path = log_file_path(dir.path(), 0, None, "csv");
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "s,n\na,0\n");
+    }
+
+    #[test]
+    fn test_flusher_add() {
+        let flusher = spawn_flushers::<CsvLogWriter>(vec![], Duration::from_millis(10));
+        std::thread::sleep(Duration::from_millis(50));
+        let dirs = [tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap()];
+        for (n, dir) in dirs.iter().enumerate() {
+            let rotator =
+                LogRotator::new_or_panic(dir.path().to_owned(), RotationPolicy::default());
+            let rotator = Arc::new(Mutex::new(rotator));
+            flusher.add(Arc::clone(&rotator));
+            Logger::new(Arc::clone(&rotator)).write(&TestRecord { s: "a", n });
+            std::thread::sleep(Duration::from_millis(200));
+            let path = log_file_path(dir.path(), 0, None, "csv");
+            let expected = format!("s,n\na,{n}\n");
+            assert_eq!(std::fs::read_to_string(path).unwrap(), expected);
+        }
+        flusher.shutdown();
+    }
+
+    #[test]
+    fn test_flusher_rotates_idle_rotator() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy {
+            time: Some(TimePast::new(Arc::new(
+                crate::time_past::IntervalContains::new(Duration::from_millis(1)),
+            ))),
+            ..Default::default()
+        };
+        let rotator = LogRotator::<CsvLogWriter>::new_or_panic(dir.path().to_owned(), rotation);
+        let rotator = Arc::new(Mutex::new(rotator));
+        let flusher = spawn_flushers(vec![], Duration::from_secs(60 * 60));
+        flusher.add(Arc::clone(&rotator));
+        let start = std::time::Instant::now();
+        while rotator.lock().unwrap().metrics().epoch < 2 {
+            assert!(start.elapsed() < Duration::from_secs(10));
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        assert!(log_file_path(dir.path(), 2, None, "csv").exists());
+        flusher.shutdown();
+    }
+
+    #[test]
+    fn test_flusher_polls_time_without_flushing() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy {
+            time: Some(TimePast::new(Arc::new(DailyContains))),
+            ..Default::default()
+        };
+        let rotator = LogRotator::<CsvLogWriter>::new_or_panic(dir.path().to_owned(), rotation);
+        let rotator = Arc::new(Mutex::new(rotator));
+        let flusher = spawn_flushers(vec![Arc::clone(&rotator)], Duration::from_secs(60 * 60));
+        Logger::new(Arc::clone(&rotator)).write(&TestRecord { s: "a", n: 0 });
+        std::thread::sleep(crate::flusher::TIME_TRIGGER_POLL_INTERVAL + Duration::from_millis(500));
+        let path = log_file_path(dir.path(), 0, None, "csv");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+        flusher.shutdown();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "s,n\na,0\n");
+    }
+
+    #[test]
+    fn test_builder() {
+        let dir = tempfile::tempdir().unwrap();
+        let output_dir = dir.path().join("logs");
+        let (rotator, flusher) = LogRotator::<CsvLogWriter>::builder(&output_dir)
+            .max_records(2)
+            .daily()
+            .max_epochs(7)
+            .policy(|p| p.write_footer(true))
+            .flush_every(Duration::from_millis(10))
+            .build()
+            .unwrap();
+        let logger = Logger::new(Arc::clone(&rotator));
+        for n in 0..3 {
+            logger.write(&TestRecord { s: "a", n });
+        }
+        let path = log_file_path(&output_dir, 1, None, "csv");
+        let start = std::time::Instant::now();
+        while std::fs::read_to_string(&path).unwrap() != "s,n\na,2\n" {
+            assert!(start.elapsed() < Duration::from_secs(10));
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let first = std::fs::read_to_string(log_file_path(&output_dir, 0, None, "csv")).unwrap();
+        assert_eq!(first, "s,n\na,0\na,1\n#records=2\n");
+        flusher.shutdown();
+
+        let err = |builder: LogRotatorBuilder<CsvLogWriter>| builder.build().unwrap_err();
+        let builder = || LogRotator::<CsvLogWriter>::builder(&output_dir);
+        assert!(matches!(
+            err(builder()),
+            BuildError::Policy(PolicyError::NoTrigger)
+        ));
+        assert!(matches!(
+            err(builder().daily().flush_every(Duration::ZERO)),
+            BuildError::FlushInterval(FlushIntervalError::TooShort(_))
+        ));
+        assert!(matches!(
+            err(builder().daily().flush_every(Duration::MAX)),
+            BuildError::FlushInterval(FlushIntervalError::TooLong(_))
+        ));
+        let file = dir.path().join("file");
+        std::fs::write(&file, "").unwrap();
+        let builder = LogRotator::<CsvLogWriter>::builder(file.join("logs"));
+        match err(builder.daily()) {
+            BuildError::Rotator(e) => assert_eq!(e.op, RotatorOp::CreateDir),
+            e => panic!("{e}"),
+        }
+    }
+
+    #[test]
+    fn test_flusher_shutdown() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotator = LogRotator::<CsvLogWriter>::new_or_panic(
+            dir.path().to_owned(),
+            RotationPolicy::default(),
+        );
+        let rotator = Arc::new(Mutex::new(rotator));
+        let flusher = spawn_flushers(vec![Arc::clone(&rotator)], Duration::from_secs(60 * 60));
+        Logger::new(Arc::clone(&rotator)).write(&TestRecord { s: "a", n: 0 });
+        let start = std::time::Instant::now();
+        flusher.shutdown();
+        assert!(start.elapsed() < Duration::from_secs(1));
+        let path = log_file_path(dir.path(), 0, None, "csv");
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "s,n\na,0\n");
+    }
+
+    #[test]
+    fn test_max_bytes() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy {
+            max_bytes: NonZeroU64::new(12),
+            max_epochs: 8,
+            ..Default::default()
+        };
+        let mut rotator = LogRotator::<CsvLogWriter>::new_or_panic(dir.path().to_owned(), rotation);
+        #[derive(Serialize)]
+        struct Record<'a> {
+            s: &'a str,
+            n: usize,
+        }
+        let mut write = |s: &str, n| {
+            rotator
+                .writer()
+                .writer()
+                .serialize(Record { s, n })
+                .unwrap();
+            rotator.try_incr_record_count().unwrap();
+            rotator.table.epoch()
+        };
+        // `s,n\n` plus two `a,0\n` reach the 12 bytes.
+        let epochs = (0..5).map(|n| write("a", n)).collect::<Vec<_>>();
+        assert_eq!(epochs, [0, 1, 1, 2, 2]);
+        let long = "a".repeat(100);
+        assert_eq!(write(&long, 5), 3);
+        assert_eq!(write(&long, 6), 4);
+        rotator.try_rotate_file().unwrap();
+        rotator.flush().unwrap();
+        assert_eq!(rotator.table.epoch(), 4);
+
+        let read = |epoch| std::fs::read_to_string(log_file_path(dir.path(), epoch, None, "csv"));
+        assert_eq!(read(0).unwrap(), "s,n\na,0\na,1\n");
+        assert_eq!(read(2).unwrap(), format!("s,n\na,4\n{long},5\n"));
+        assert_eq!(read(3).unwrap(), format!("s,n\n{long},6\n"));
+        assert_eq!(read(4).unwrap(), "");
+    }
+
+    #[test]
+    fn test_try_new_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("file");
+        std::fs::write(&file, "").unwrap();
+        let output_dir = file.join("logs");
+        let err = LogRotator::<CsvLogWriter>::new(output_dir.clone(), Default::default())
+            .err()
+            .unwrap();
+        assert_eq!(err.op, RotatorOp::CreateDir);
+        assert_eq!(err.path, output_dir);
+    }
+
+    #[test]
+    fn test_max_bytes_waits_for_record() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy {
+            max_bytes: NonZeroU64::new(12),
+            ..Default::default()
+        };
+        let mut rotator = LogRotator::<CsvLogWriter>::new_or_panic(dir.path().to_owned(), rotation);
+        for n in 0..2 {
+            let record = TestRecord { s: "a", n };
+            rotator.writer().writer().serialize(record).unwrap();
+            if n == 0 {
+                rotator.try_incr_record_count().unwrap();
+            }
+        }
+        rotator.flush().unwrap();
+        rotator.try_rotate_file().unwrap();
+        assert_eq!(rotator.table.epoch(), 0);
+        rotator.try_incr_record_count().unwrap();
+        assert_eq!(rotator.table.epoch(), 1);
+        let path = log_file_path(dir.path(), 0, None, "csv");
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "s,n\na,0\na,1\n");
+    }
+
+    fn write_uncounted(rotator: &mut LogRotator<CsvLogWriter>, n: usize) {
+        let record = TestRecord { s: "a", n };
+        rotator.writer().writer().serialize(record).unwrap();
+        rotator.table.incr_record_count();
+    }
+
+    #[test]
+    fn test_output_dir_deleted() {
+        let dir = tempfile::tempdir().unwrap();
+        let output_dir = dir.path().join("logs");
+        let rotation = RotationPolicy {
+            max_records: NonZeroUsize::new(1),
+            ..Default::default()
+        };
+        let mut rotator = LogRotator::<CsvLogWriter>::new(output_dir.clone(), rotation).unwrap();
+        write_uncounted(&mut rotator, 0);
+        std::fs::remove_dir_all(&output_dir).unwrap();
+        rotator.try_rotate_file().unwrap();
+        assert!(log_file_path(&output_dir, 1, None, "csv").exists());
+
+        std::fs::remove_dir_all(&output_dir).unwrap();
+        std::fs::write(&output_dir, "").unwrap();
+        write_uncounted(&mut rotator, 1);
+        rotator.flush().unwrap();
+        let err = rotator.try_rotate_file().err().unwrap();
+        assert_eq!(err.op, RotatorOp::CreateDir);
+        assert_eq!(rotator.table.epoch(), 1);
+
+        std::fs::remove_file(&output_dir).unwrap();
+        rotator.try_rotate_file().unwrap();
+        assert_eq!(rotator.table.epoch(), 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_read_only_output_dir() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy {
+            max_records: NonZeroUsize::new(1),
+            ..Default::default()
+        };
+        let mut rotator =
+            LogRotator::<CsvLogWriter>::new(dir.path().to_owned(), rotation.clone()).unwrap();
+        let set_mode = |mode| {
+            let permissions = std::fs::Permissions::from_mode(mode);
+            std::fs::set_permissions(dir.path(), permissions).unwrap();
+        };
+        set_mode(0o555);
+        // Permissions do not bind privileged users.
+        if std::fs::File::create(dir.path().join("probe")).is_ok() {
+            set_mode(0o755);
+            return;
+        }
+        let err = LogRotator::<CsvLogWriter>::new(dir.path().to_owned(), rotation)
+            .err()
+            .unwrap();
+        assert_eq!(err.op, RotatorOp::OpenLogFile);
+        write_uncounted(&mut rotator, 0);
+        let err = rotator.try_rotate_file().err().unwrap();
+        assert_eq!(err.op, RotatorOp::OpenLogFile);
+        rotator.flush().unwrap();
+        set_mode(0o755);
+        rotator.try_rotate_file().unwrap();
+        let path = log_file_path(dir.path(), 0, None, "csv");
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "s,n\na,0\n");
+    }
+
+    #[derive(Debug)]
+    struct SlowCompressor;
+    impl Compressor for SlowCompressor {
+        fn compress(&self, src: &Path, dst: &Path) -> std::io::Result<()> {
+            std::thread::sleep(Duration::from_millis(200));
+            CopyCompressor.compress(src, dst)
+        }
+
+        fn extension(&self) -> &str {
+            CopyCompressor.extension()
+        }
+    }
+
+    #[test]
+    fn test_compression_in_background() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy {
+            max_records: NonZeroUsize::new(1),
+            compression: Some(Arc::new(SlowCompressor)),
+            ..Default::default()
+        };
+        let mut rotator = LogRotator::<CsvLogWriter>::new(dir.path().to_owned(), rotation).unwrap();
+        let start = std::time::Instant::now();
+        write_uncounted(&mut rotator, 0);
+        rotator.try_rotate_file().unwrap();
+        assert!(start.elapsed() < Duration::from_millis(200));
+        assert!(log_file_path(dir.path(), 0, None, "csv").exists());
+
+        rotator.wait_for_compression().unwrap();
+        assert!(!log_file_path(dir.path(), 0, None, "csv").exists());
+        let compressed = dir.path().join("0.csv.cp");
+        assert_eq!(std::fs::read_to_string(compressed).unwrap(), "s,n\na,0\n");
+    }
+
+    #[derive(Debug)]
+    struct FailingCompressor;
+    impl Compressor for FailingCompressor {
+        fn compress(&self, _src: &Path, _dst: &Path) -> std::io::Result<()> {
+            Err(std::io::Error::other("No space left"))
+        }
+
+        fn extension(&self) -> &str {
+            "cp"
+        }
+    }
+
+    #[test]
+    fn test_compression_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy {
+            max_records: NonZeroUsize::new(1),
+            max_epochs: usize::MAX,
+            compression: Some(Arc::new(FailingCompressor)),
+            ..Default::default()
+        };
+        let mut rotator = LogRotator::<CsvLogWriter>::new(dir.path().to_owned(), rotation).unwrap();
+        for n in 0..2 {
+            write_uncounted(&mut rotator, n);
+            rotator.try_rotate_file().unwrap();
+        }
+        let e = rotator.wait_for_compression().unwrap_err();
+        assert_eq!(e.op, RotatorOp::Compress);
+        assert_eq!(rotator.metrics().compression_failures, 2);
+        rotator.wait_for_compression().unwrap();
+        assert!(log_file_path(dir.path(), 0, None, "csv").exists());
+    }
+
+    #[test]
+    fn test_policy_builder() {
+        let rotation = RotationPolicy::builder()
+            .max_records(2)
+            .daily()
+            .max_epochs(3)
+            .build()
+            .unwrap();
+        assert_eq!(rotation.max_records, NonZeroUsize::new(2));
+        assert!(rotation.time.is_some());
+        assert_eq!(rotation.max_epochs, 3);
+
+        let rotation = RotationPolicy::daily_keep(5).unwrap();
+        assert!(rotation.time.is_some());
+        assert_eq!(rotation.max_epochs, 5);
+    }
+
+    #[test]
+    fn test_policy_builder_error() {
+        let err = |builder: RotationPolicyBuilder| builder.build().unwrap_err();
+        assert_eq!(err(RotationPolicy::builder()), PolicyError::NoTrigger);
+        assert_eq!(
+            err(RotationPolicy::builder().max_records(0)),
+            PolicyError::ZeroMaxRecords
+        );
+        assert_eq!(
+            err(RotationPolicy::builder().max_bytes(0)),
+            PolicyError::ZeroMaxBytes
+        );
+        assert_eq!(
+            err(RotationPolicy::builder().daily().max_total_bytes(0)),
+            PolicyError::ZeroMaxTotalBytes
+        );
+        assert_eq!(
+            RotationPolicy::daily_keep(0).unwrap_err(),
+            PolicyError::ZeroMaxEpochs
+        );
+        let hourly = TimePast::new(Arc::new(crate::time_past::HourlyContains));
+        assert_eq!(
+            err(RotationPolicy::builder().daily().watermark(hourly)),
+            PolicyError::TimeAndWatermark
+        );
+        assert_eq!(
+            err(RotationPolicy::builder().daily().max_age(jiff::Span::new())),
+            PolicyError::NonPositiveMaxAge
+        );
+        let naming = FileNaming::Custom(Arc::new(EpochFileNamer));
+        assert_eq!(
+            err(RotationPolicy::builder()
+                .daily()
+                .instance("a")
+                .naming(naming)),
+            PolicyError::InstanceWithCustomNaming
+        );
     }
 }
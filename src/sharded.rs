@@ -0,0 +1,313 @@
+//! A write path where producers only contend on a channel.
+//!
+//! Each [`LogSender`] is a shard: its producer serializes records into the buffer of the shard,
+//! which goes over a channel to a single consumer thread once full. The consumer writes the
+//! records to the rotator in batches, so the rotator lock is taken once per batch instead of once
+//! per record, and the serialization happens on the producer threads.
+
+use std::{
+    io::Write,
+    sync::{mpsc, Arc, Mutex, Weak},
+    thread::JoinHandle,
+};
+
+use crate::{
+    drain::{CommitFn, Drain, Message, Records, MAX_BATCH},
+    rotator::{LogRotator, RotatorError},
+    LogWriter,
+};
+
+/// Most records buffered by a shard before they are sent to the consumer.
+const SHARD_CAPACITY: usize = 256;
+
+/// Records serialized back to back by the producer of a shard.
+#[derive(Debug, Default)]
+struct Serialized {
+    bytes: Vec<u8>,
+    /// End of each record in `bytes`.
+    ends: Vec<usize>,
+}
+impl<W> Records<W> for Serialized
+where
+    W: LogWriter + Write,
+{
+    fn write(self, rotator: &mut LogRotator<W>, commit: &mut CommitFn<'_, W>) {
+        let mut start = 0;
+        for end in self.ends {
+            let written = rotator.writer().write_all(&self.bytes[start..end]);
+            start = end;
+            commit(rotator, written);
+        }
+    }
+}
+
+struct Shared {
+    sender: mpsc::Sender<Message<Serialized>>,
+    /// Buffers of the live shards, for flushes to send.
+    shards: Mutex<Vec<Weak<Mutex<Serialized>>>>,
+}
+impl Shared {
+    /// Sends the records buffered by `shard`, if any; under the lock of the shard, so that the
+    /// records of a shard reach the consumer in order.
+    fn send(&self, shard: &mut Serialized) {
+        if shard.ends.is_empty() {
+            return;
+        }
+        let records = std::mem::take(shard);
+        self.sender
+            .send(Message::Write(records))
+            .expect("The draining thread panicked");
+    }
+}
+
+/// Owns the consumer thread, which runs until the logger and all its senders are dropped.
+///
+/// Dropping the logger detaches the consumer; see [`Self::shutdown`] to wait for it.
+#[derive(Debug)]
+pub struct ShardedLogger {
+    shared: Arc<Shared>,
+    consumer: JoinHandle<Result<(), RotatorError>>,
+}
+impl ShardedLogger {
+    pub fn spawn<W>(rotator: Arc<Mutex<LogRotator<W>>>) -> Self
+    where
+        W: LogWriter + Write + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel::<Message<Serialized>>();
+        let consumer = std::thread::Builder::new()
+            .name("ShardedLogger::drain()".to_string())
+            .spawn(move || {
+                let mut drain = Drain::default();
+                while let Ok(message) = receiver.recv() {
+                    let mut rotator = rotator.lock().unwrap();
+                    let batch = std::iter::once(message)
+                        .chain(std::iter::from_fn(|| receiver.try_recv().ok()))
+                        .take(MAX_BATCH);
+                    drain.write_batch(&mut rotator, batch);
+                }
+                drain.finish(&mut rotator.lock().unwrap())
+            })
+            .expect("Failed to spawn the draining thread");
+        let shared = Arc::new(Shared {
+            sender,
+            shards: Mutex::new(vec![]),
+        });
+        Self { shared, consumer }
+    }
+
+    /// A new shard for one producer, whose records are written in the order they are sent.
+    pub fn sender(&self) -> LogSender {
+        let shard = Arc::new(Mutex::new(Serialized::default()));
+        let mut shards = self.shared.shards.lock().unwrap();
+        shards.retain(|shard| shard.strong_count() != 0);
+        shards.push(Arc::downgrade(&shard));
+        LogSender {
+            shared: Arc::clone(&self.shared),
+            shard,
+        }
+    }
+
+    /// Like [`LogSender::flush`].
+    pub fn flush(&self) -> Result<(), RotatorError> {
+        flush(&self.shared)
+    }
+
+    /// Waits for the consumer to write the records of every shard, which it does once the
+    /// senders are dropped too, and to flush.
+    ///
+    /// Fails like [`Self::flush`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the consumer thread has panicked.
+    pub fn shutdown(self) -> Result<(), RotatorError> {
+        drop(self.shared);
+        self.consumer.join().expect("The draining thread panicked")
+    }
+}
+
+/// A shard, which sends its records to the consumer once it holds 256 of them,
+/// on flush and on drop.
+#[derive(Debug)]
+pub struct LogSender {
+    shared: Arc<Shared>,
+    shard: Arc<Mutex<Serialized>>,
+}
+impl LogSender {
+    /// Buffers the record that `serialize` writes, counting one record.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the consumer thread has panicked.
+    pub fn write(&self, serialize: impl FnOnce(&mut Vec<u8>)) {
+        let mut shard = self.shard.lock().unwrap();
+        serialize(&mut shard.bytes);
+        let end = shard.bytes.len();
+        shard.ends.push(end);
+        if SHARD_CAPACITY <= shard.ends.len() {
+            self.shared.send(&mut shard);
+        }
+    }
+
+    /// Sends the records buffered by every shard and waits for every record sent so far to be
+    /// written, then flushes the rotator.
+    ///
+    /// Fails with the first write or rotation that failed since the previous flush, if any.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the consumer thread has panicked.
+    pub fn flush(&self) -> Result<(), RotatorError> {
+        flush(&self.shared)
+    }
+}
+impl Drop for LogSender {
+    fn drop(&mut self) {
+        let mut shard = self
+            .shard
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        // The records are lost along with the consumer if it has panicked.
+        if !shard.ends.is_empty() {
+            let records = std::mem::take(&mut *shard);
+            let _ = self.shared.sender.send(Message::Write(records));
+        }
+    }
+}
+
+fn flush(shared: &Shared) -> Result<(), RotatorError> {
+    let shards = shared.shards.lock().unwrap().clone();
+    for shard in shards.iter().filter_map(Weak::upgrade) {
+        shared.send(&mut shard.lock().unwrap());
+    }
+    let (done, flushed) = mpsc::sync_channel(1);
+    shared
+        .sender
+        .send(Message::Flush(done))
+        .expect("The draining thread panicked");
+    flushed.recv().expect("The draining thread panicked")
+}
+
+impl core::fmt::Debug for Shared {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Shared").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, num::NonZeroUsize};
+
+    use crate::{
+        rotator::{list_log_files, RotationPolicy, RotatorOp},
+        writers::text::TextLogWriter,
+    };
+
+    use super::*;
+
+    fn serialize(producer: usize, n: usize) -> impl FnOnce(&mut Vec<u8>) {
+        move |buf| writeln!(buf, "{producer},{n}").unwrap()
+    }
+
+    #[test]
+    fn test_no_lost_records() {
+        const PRODUCERS: usize = 16;
+        const RECORDS: usize = 500;
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy {
+            max_records: NonZeroUsize::new(97),
+            max_epochs: usize::MAX,
+            ..Default::default()
+        };
+        let rotator = LogRotator::<TextLogWriter>::new_or_panic(dir.path().to_owned(), rotation);
+        let rotator = Arc::new(Mutex::new(rotator));
+        let logger = ShardedLogger::spawn(Arc::clone(&rotator));
+        std::thread::scope(|s| {
+            for producer in 0..PRODUCERS {
+                let sender = logger.sender();
+                s.spawn(move || {
+                    for n in 0..RECORDS {
+                        sender.write(serialize(producer, n));
+                    }
+                });
+            }
+        });
+        logger.shutdown().unwrap();
+
+        let mut next = HashMap::new();
+        let files = list_log_files(dir.path(), "log", None);
+        let mut epochs = files.iter().map(|f| f.epoch).collect::<Vec<_>>();
+        epochs.sort_unstable();
+        for epoch in epochs {
+            let path = files.iter().find(|f| f.epoch == epoch).unwrap();
+            let contents = std::fs::read_to_string(&path.path).unwrap();
+            let mut count = 0;
+            for line in contents.lines() {
+                let (producer, n) = line.split_once(',').unwrap();
+                let n = n.parse::<usize>().unwrap();
+                let next = next.entry(producer.parse::<usize>().unwrap()).or_insert(0);
+                assert_eq!(n, *next);
+                *next += 1;
+                count += 1;
+            }
+            assert!(count <= 97);
+        }
+        assert_eq!(next.len(), PRODUCERS);
+        assert!(next.values().all(|&n| n == RECORDS));
+        assert_eq!(
+            rotator.lock().unwrap().metrics().records_total,
+            (PRODUCERS * RECORDS) as u64
+        );
+    }
+
+    #[test]
+    fn test_flush_drains_shards() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotator = LogRotator::<TextLogWriter>::new_or_panic(
+            dir.path().to_owned(),
+            RotationPolicy::default(),
+        );
+        let rotator = Arc::new(Mutex::new(rotator));
+        let logger = ShardedLogger::spawn(Arc::clone(&rotator));
+        let senders = [logger.sender(), logger.sender()];
+        for (producer, sender) in senders.iter().enumerate() {
+            sender.write(serialize(producer, 0));
+        }
+        let path = dir.path().join("0.log");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+        logger.flush().unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "0,0\n1,0\n");
+        assert_eq!(rotator.lock().unwrap().metrics().records_current, 2);
+        drop(senders);
+        logger.shutdown().unwrap();
+    }
+
+    #[test]
+    fn test_failed_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy {
+            max_records: NonZeroUsize::new(1),
+            ..Default::default()
+        };
+        let rotator = LogRotator::<TextLogWriter>::new_or_panic(dir.path().to_owned(), rotation);
+        let rotator = Arc::new(Mutex::new(rotator));
+        let logger = ShardedLogger::spawn(Arc::clone(&rotator));
+        // The next file cannot be opened where a directory stands.
+        let poisoned = dir.path().join("1.log");
+        std::fs::create_dir(&poisoned).unwrap();
+        let sender = logger.sender();
+        for n in 0..2 {
+            sender.write(serialize(0, n));
+        }
+        let e = logger.flush().unwrap_err();
+        assert_eq!(e.op, RotatorOp::OpenLogFile);
+        logger.flush().unwrap();
+        std::fs::remove_dir(&poisoned).unwrap();
+        sender.write(serialize(0, 2));
+        logger.flush().unwrap();
+
+        let rotator = rotator.lock().unwrap();
+        assert_eq!(rotator.metrics().epoch, 1);
+        assert_eq!(rotator.metrics().records_total, 3);
+    }
+}
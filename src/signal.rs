@@ -0,0 +1,156 @@
+//! Rotation on `SIGHUP`, which logrotate-style tools send to have a daemon reopen its files.
+//!
+//! The signal handler only writes a byte to a pipe. A dedicated thread reads it and runs the
+//! registered rotations, so that no lock is taken and no file IO happens in the handler.
+//!
+//! The handler stays installed for the rest of the process once the first rotation is
+//! registered, so a `SIGHUP` with no rotations left is ignored instead of terminating the
+//! process.
+
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicI32, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+};
+
+#[cfg(target_os = "aix")]
+use libc::_Errno as errno_location;
+#[cfg(any(target_os = "solaris", target_os = "illumos"))]
+use libc::___errno as errno_location;
+#[cfg(any(target_os = "android", target_os = "openbsd", target_os = "netbsd"))]
+use libc::__errno as errno_location;
+#[cfg(any(
+    target_os = "linux",
+    target_os = "emscripten",
+    target_os = "redox",
+    target_os = "dragonfly",
+    target_os = "hurd"
+))]
+use libc::__errno_location as errno_location;
+#[cfg(any(target_vendor = "apple", target_os = "freebsd"))]
+use libc::__error as errno_location;
+#[cfg(target_os = "nto")]
+use libc::__get_errno_ptr as errno_location;
+#[cfg(target_os = "haiku")]
+use libc::_errnop as errno_location;
+
+/// Where `libc` does not reach `errno`, the handler leaves it as its write sets it.
+#[cfg(not(any(
+    target_os = "android",
+    target_os = "openbsd",
+    target_os = "netbsd",
+    target_os = "linux",
+    target_os = "emscripten",
+    target_os = "redox",
+    target_os = "dragonfly",
+    target_os = "hurd",
+    target_vendor = "apple",
+    target_os = "freebsd",
+    target_os = "solaris",
+    target_os = "illumos",
+    target_os = "aix",
+    target_os = "haiku",
+    target_os = "nto"
+)))]
+unsafe fn errno_location() -> *mut libc::c_int {
+    std::ptr::null_mut()
+}
+
+type Listener = Box<dyn FnMut() + Send>;
+type SharedListener = Arc<Mutex<Listener>>;
+
+/// Write end of the pipe the handler writes to.
+static PIPE: AtomicI32 = AtomicI32::new(-1);
+static LISTENERS: Mutex<BTreeMap<u64, SharedListener>> = Mutex::new(BTreeMap::new());
+static INSTALLED: OnceLock<std::io::Result<()>> = OnceLock::new();
+
+/// Stops the rotations it was returned for on drop.
+///
+/// Dropping the last handle leaves the handler installed; see the [module docs](self).
+#[derive(Debug)]
+pub struct SighupHandle {
+    id: u64,
+}
+impl SighupHandle {
+    /// Like dropping the handle.
+    pub fn unregister(self) {}
+}
+impl Drop for SighupHandle {
+    fn drop(&mut self) {
+        LISTENERS.lock().unwrap().remove(&self.id);
+    }
+}
+
+/// Calls `listener` on the signal thread on every `SIGHUP`, installing the handler on first use.
+pub(crate) fn register(listener: Listener) -> std::io::Result<SighupHandle> {
+    if let Err(e) = INSTALLED.get_or_init(install) {
+        return Err(std::io::Error::new(e.kind(), e.to_string()));
+    }
+    let mut listeners = LISTENERS.lock().unwrap();
+    let id = listeners.last_key_value().map_or(0, |(id, _)| id + 1);
+    listeners.insert(id, Arc::new(Mutex::new(listener)));
+    Ok(SighupHandle { id })
+}
+
+fn install() -> std::io::Result<()> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let [read, write] = fds;
+    for fd in fds {
+        unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) };
+    }
+    // A full pipe must not block the handler.
+    unsafe { libc::fcntl(write, libc::F_SETFL, libc::O_NONBLOCK) };
+    PIPE.store(write, Ordering::Relaxed);
+    std::thread::Builder::new()
+        .name("rotate_on_sighup()".to_string())
+        .spawn(move || {
+            let mut buf = [0_u8; 64];
+            loop {
+                let n = unsafe { libc::read(read, buf.as_mut_ptr().cast(), buf.len()) };
+                if n < 0
+                    && std::io::Error::last_os_error().kind() == std::io::ErrorKind::Interrupted
+                {
+                    continue;
+                }
+                if n <= 0 {
+                    return;
+                }
+                // Called outside the lock so that a listener may drop a handle.
+                let listeners = LISTENERS
+                    .lock()
+                    .unwrap()
+                    .values()
+                    .cloned()
+                    .collect::<Vec<_>>();
+                for listener in listeners {
+                    (listener.lock().unwrap())();
+                }
+            }
+        })?;
+    let handler: extern "C" fn(libc::c_int) = on_sighup;
+    let mut action: libc::sigaction = unsafe { std::mem::zeroed() };
+    action.sa_sigaction = handler as libc::sighandler_t;
+    action.sa_flags = libc::SA_RESTART;
+    unsafe { libc::sigemptyset(&mut action.sa_mask) };
+    if unsafe { libc::sigaction(libc::SIGHUP, &action, std::ptr::null_mut()) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+extern "C" fn on_sighup(_: libc::c_int) {
+    let fd = PIPE.load(Ordering::Relaxed);
+    // The interrupted code may be about to read `errno`.
+    let errno = unsafe { errno_location() };
+    let saved = (!errno.is_null()).then(|| unsafe { *errno });
+    // A full pipe already has a wakeup pending.
+    unsafe { libc::write(fd, [0_u8].as_ptr().cast(), 1) };
+    if let Some(saved) = saved {
+        unsafe { *errno = saved };
+    }
+}
@@ -3,8 +3,17 @@ use crate::LogWriter;
 #[derive(Debug)]
 pub struct Table<W> {
     records_written: usize,
+    /// Records counted since the table was created, across every file.
+    lifetime_records: u64,
+    aux_records: usize,
+    /// Auxiliary rows counted since the table was created, across every file.
+    lifetime_aux_records: u64,
     epoch: usize,
     writer: W,
+    dirty: bool,
+    /// [`LogWriter::schema`] of the file, pinned by the file it resumed or else by its first
+    /// record.
+    schema: Option<u64>,
 }
 impl<W> Table<W>
 where
@@ -13,27 +22,97 @@ where
     pub fn new(writer: W, epoch: usize) -> Self {
         Self {
             records_written: 0,
+            lifetime_records: 0,
+            aux_records: 0,
+            lifetime_aux_records: 0,
             epoch,
             writer,
+            dirty: false,
+            schema: None,
         }
     }
 
-    pub fn replace(&mut self, writer: W) {
+    /// Continues a file already holding `records_written` records.
+    pub fn resume(writer: W, epoch: usize, records_written: usize) -> Self {
+        let schema = writer.schema();
+        Self {
+            records_written,
+            schema,
+            ..Self::new(writer, epoch)
+        }
+    }
+
+    pub fn replace(&mut self, writer: W, epoch: usize) {
         self.writer = writer;
-        self.epoch += 1;
+        self.epoch = epoch;
         self.records_written = 0;
+        self.aux_records = 0;
+        self.dirty = false;
+        self.schema = None;
     }
 
     pub fn writer(&mut self) -> &mut W {
+        self.dirty = true;
         &mut self.writer
     }
 
+    /// Unlike [`Self::writer`], leaves the table clean.
+    pub fn writer_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Unlike [`Self::writer`], leaves the table clean.
+    pub fn bytes_written(&mut self) -> Option<u64> {
+        self.writer.bytes_written()
+    }
+
     pub fn incr_record_count(&mut self) {
+        self.dirty = true;
         self.records_written += 1;
+        self.lifetime_records += 1;
+    }
+
+    /// Pins the schema of the file on its first known value, failing if the writer has another
+    /// one since.
+    pub fn check_schema(&mut self) -> std::io::Result<()> {
+        match (self.schema, self.writer.schema()) {
+            (Some(pinned), Some(schema)) if pinned != schema => {
+                let msg =
+                    format!("The schema {schema:016x} is not {pinned:016x}, that of the file");
+                Err(std::io::Error::new(std::io::ErrorKind::InvalidData, msg))
+            }
+            (None, schema) => {
+                self.schema = schema;
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Counts a row that is not a data record, e.g. a banner or a footer.
+    pub fn incr_aux_record_count(&mut self) {
+        self.add_aux_records(1);
     }
 
-    pub fn flush(&mut self) {
-        self.writer.flush();
+    pub fn add_aux_records(&mut self, aux_records: usize) {
+        self.aux_records += aux_records;
+        self.lifetime_aux_records += aux_records as u64;
+    }
+
+    /// Stays dirty if the flush fails.
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    pub fn sync(&mut self) -> std::io::Result<()> {
+        self.writer.sync()
+    }
+
+    /// Whether the writer has been handed out since the last flush.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
     }
 
     pub fn epoch(&self) -> usize {
@@ -43,4 +122,16 @@ where
     pub fn records_written(&self) -> usize {
         self.records_written
     }
+
+    pub fn lifetime_records(&self) -> u64 {
+        self.lifetime_records
+    }
+
+    pub fn aux_records(&self) -> usize {
+        self.aux_records
+    }
+
+    pub fn lifetime_aux_records(&self) -> u64 {
+        self.lifetime_aux_records
+    }
 }
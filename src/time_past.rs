@@ -1,7 +1,50 @@
-use std::sync::Arc;
+use std::{num::NonZeroU64, sync::Arc, time::Duration};
 
 pub trait TimeContains: core::fmt::Debug + Sync + Send {
     fn matches(&self, interval: Interval) -> bool;
+
+    /// The built-in trigger this is, if any, so that a policy using it can be written out.
+    ///
+    /// [`None`] by default.
+    fn trigger(&self) -> Option<TimeTrigger> {
+        None
+    }
+}
+
+/// A clock trigger of [`crate::rotator::RotationPolicy`] by name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)
+)]
+pub enum TimeTrigger {
+    /// [`HourlyContains`]
+    Hourly,
+    /// [`DailyContains`]
+    Daily,
+    /// [`MonthlyContains`]
+    Monthly,
+    /// [`IntervalContains`] of this many minutes.
+    Every { minutes: NonZeroU64 },
+    /// [`crate::rotator::RotationPolicy::cron`] parsed from the expression.
+    Cron { expr: String },
+}
+impl TimeTrigger {
+    /// The time past of the trigger, or [`None`] for [`Self::Cron`].
+    pub fn time_past(&self) -> Option<TimePast> {
+        let time_contains: Arc<dyn TimeContains> = match self {
+            Self::Hourly => Arc::new(HourlyContains),
+            Self::Daily => Arc::new(DailyContains),
+            Self::Monthly => Arc::new(MonthlyContains),
+            Self::Every { minutes } => {
+                let period = Duration::from_secs(minutes.get().saturating_mul(60));
+                Arc::new(IntervalContains::new(period))
+            }
+            Self::Cron { .. } => return None,
+        };
+        Some(TimePast::new(time_contains))
+    }
 }
 #[derive(Debug, Clone)]
 pub struct DailyContains;
@@ -14,28 +57,167 @@ impl TimeContains for DailyContains {
         let end_date = interval.inclusive_end.date();
         start_date != end_date
     }
+
+    fn trigger(&self) -> Option<TimeTrigger> {
+        Some(TimeTrigger::Daily)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HourlyContains;
+impl TimeContains for HourlyContains {
+    fn matches(&self, interval: Interval) -> bool {
+        let Some(exclusive_start) = interval.exclusive_start else {
+            return false;
+        };
+        let hour = |t: &jiff::Zoned| (t.date(), t.hour());
+        hour(&exclusive_start) != hour(&interval.inclusive_end)
+    }
+
+    fn trigger(&self) -> Option<TimeTrigger> {
+        Some(TimeTrigger::Hourly)
+    }
+}
+
+/// Matches once the interval crosses into a week starting on `start_of_week`.
+///
+/// Weeks are the seven days since the last `start_of_week`, not ISO weeks, so a week spanning the
+/// new year is still one week.
+#[derive(Debug, Clone)]
+pub struct WeeklyContains {
+    pub start_of_week: jiff::civil::Weekday,
+}
+impl Default for WeeklyContains {
+    fn default() -> Self {
+        Self {
+            start_of_week: jiff::civil::Weekday::Monday,
+        }
+    }
+}
+impl TimeContains for WeeklyContains {
+    fn matches(&self, interval: Interval) -> bool {
+        let Some(exclusive_start) = interval.exclusive_start else {
+            return false;
+        };
+        let week = |t: &jiff::Zoned| {
+            let days = t.weekday().since(self.start_of_week);
+            t.date().checked_sub(jiff::Span::new().days(days)).ok()
+        };
+        week(&exclusive_start) != week(&interval.inclusive_end)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MonthlyContains;
+impl TimeContains for MonthlyContains {
+    fn matches(&self, interval: Interval) -> bool {
+        let Some(exclusive_start) = interval.exclusive_start else {
+            return false;
+        };
+        let month = |t: &jiff::Zoned| (t.year(), t.month());
+        month(&exclusive_start) != month(&interval.inclusive_end)
+    }
+
+    fn trigger(&self) -> Option<TimeTrigger> {
+        Some(TimeTrigger::Monthly)
+    }
+}
+
+/// Matches once `period` has elapsed since [`Interval::last_match`], whatever the calendar.
+#[derive(Debug, Clone)]
+pub struct IntervalContains {
+    period: Duration,
+}
+impl IntervalContains {
+    /// # Panics
+    ///
+    /// Panics if `period` is zero.
+    pub fn new(period: Duration) -> Self {
+        assert!(!period.is_zero(), "The period is zero");
+        Self { period }
+    }
+}
+impl TimeContains for IntervalContains {
+    fn matches(&self, interval: Interval) -> bool {
+        let elapsed = interval.inclusive_end.duration_since(&interval.last_match);
+        !elapsed.is_negative() && self.period <= elapsed.unsigned_abs()
+    }
+
+    fn trigger(&self) -> Option<TimeTrigger> {
+        if self.period.subsec_nanos() != 0 || !self.period.as_secs().is_multiple_of(60) {
+            return None;
+        }
+        let minutes = NonZeroU64::new(self.period.as_secs() / 60)?;
+        Some(TimeTrigger::Every { minutes })
+    }
+}
+
+/// Matches once the interval crosses a multiple of `period` since midnight, so that hosts
+/// rotating every 15 minutes all start files at `:00`, `:15`, `:30` and `:45`.
+///
+/// A period that does not divide the day leaves a shorter last period before midnight.
+#[derive(Debug, Clone)]
+pub struct AlignedIntervalContains {
+    period: Duration,
+}
+impl AlignedIntervalContains {
+    /// # Panics
+    ///
+    /// Panics if `period` is zero.
+    pub fn new(period: Duration) -> Self {
+        assert!(!period.is_zero(), "The period is zero");
+        Self { period }
+    }
+
+    fn slot(&self, t: &jiff::Zoned) -> Option<(jiff::civil::Date, i128)> {
+        let since_midnight = t.duration_since(&t.start_of_day().ok()?);
+        Some((
+            t.date(),
+            since_midnight.as_nanos() / self.period.as_nanos() as i128,
+        ))
+    }
+}
+impl TimeContains for AlignedIntervalContains {
+    fn matches(&self, interval: Interval) -> bool {
+        let Some(exclusive_start) = interval.exclusive_start else {
+            return false;
+        };
+        self.slot(&exclusive_start) != self.slot(&interval.inclusive_end)
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct TimePast {
     prev: Option<jiff::Zoned>,
+    last_match: Option<jiff::Zoned>,
     time_contains: Arc<dyn TimeContains>,
 }
 impl TimePast {
     pub fn new(time_contains: Arc<dyn TimeContains>) -> Self {
         Self {
             prev: None,
+            last_match: None,
             time_contains,
         }
     }
 
+    /// Like [`TimeContains::trigger`].
+    pub fn trigger(&self) -> Option<TimeTrigger> {
+        self.time_contains.trigger()
+    }
+
     pub fn poll(&mut self, now: jiff::Zoned) -> bool {
         let interval = Interval {
             exclusive_start: self.prev.clone(),
             inclusive_end: now.clone(),
+            last_match: self.last_match.get_or_insert_with(|| now.clone()).clone(),
         };
+        let matches = self.time_contains.matches(interval);
+        if matches {
+            self.last_match = Some(now.clone());
+        }
         self.prev = Some(now);
-        self.time_contains.matches(interval)
+        matches
     }
 }
 
@@ -43,4 +225,113 @@ impl TimePast {
 pub struct Interval {
     pub exclusive_start: Option<jiff::Zoned>,
     pub inclusive_end: jiff::Zoned,
+    /// The last end that matched, or the first one polled.
+    pub last_match: jiff::Zoned,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(time_contains: impl TimeContains + 'static, start: &str, end: &str) -> bool {
+        let zoned = |t: &str| format!("{t}[UTC]").parse::<jiff::Zoned>().unwrap();
+        let mut time_past = TimePast::new(Arc::new(time_contains));
+        assert!(!time_past.poll(zoned(start)));
+        time_past.poll(zoned(end))
+    }
+
+    #[test]
+    fn test_hourly() {
+        assert!(!matches(
+            HourlyContains,
+            "2024-01-01T10:00",
+            "2024-01-01T10:59:59"
+        ));
+        assert!(matches(
+            HourlyContains,
+            "2024-01-01T10:59:59",
+            "2024-01-01T11:00"
+        ));
+        assert!(matches(
+            HourlyContains,
+            "2024-01-01T10:00",
+            "2024-01-02T10:00"
+        ));
+    }
+
+    #[test]
+    fn test_weekly() {
+        let monday = WeeklyContains::default;
+        // 2024-01-07 is a Sunday
+        assert!(!matches(monday(), "2024-01-01T00:00", "2024-01-07T23:59"));
+        assert!(matches(monday(), "2024-01-07T23:59", "2024-01-08T00:00"));
+        assert!(matches(monday(), "2024-01-01T12:00", "2024-01-08T11:00"));
+        let sunday = || WeeklyContains {
+            start_of_week: jiff::civil::Weekday::Sunday,
+        };
+        assert!(matches(sunday(), "2024-01-06T23:59", "2024-01-07T00:00"));
+        assert!(!matches(sunday(), "2024-01-07T23:59", "2024-01-08T00:00"));
+        // 2024-12-30 is a Monday
+        assert!(!matches(monday(), "2024-12-30T00:00", "2025-01-05T23:59"));
+        assert!(matches(monday(), "2024-12-29T23:59", "2024-12-30T00:00"));
+        assert!(matches(monday(), "2024-12-01T00:00", "2025-01-01T00:00"));
+    }
+
+    #[test]
+    fn test_monthly() {
+        assert!(!matches(
+            MonthlyContains,
+            "2024-02-01T00:00",
+            "2024-02-29T23:59"
+        ));
+        assert!(matches(
+            MonthlyContains,
+            "2024-02-29T23:59",
+            "2024-03-01T00:00"
+        ));
+        assert!(matches(
+            MonthlyContains,
+            "2024-03-15T00:00",
+            "2025-03-15T00:00"
+        ));
+    }
+
+    #[test]
+    fn test_interval() {
+        let zoned = |t: &str| {
+            format!("2024-01-01T{t}[UTC]")
+                .parse::<jiff::Zoned>()
+                .unwrap()
+        };
+        let mut time_past = TimePast::new(Arc::new(IntervalContains::new(Duration::from_secs(
+            15 * 60,
+        ))));
+        let fired = [
+            "10:07", "10:21", "10:22", "10:23", "10:40", "11:30", "11:44",
+        ]
+        .into_iter()
+        .filter(|t| time_past.poll(zoned(t)))
+        .collect::<Vec<_>>();
+        assert_eq!(fired, ["10:22", "10:40", "11:30"]);
+    }
+
+    #[test]
+    fn test_aligned_interval() {
+        let quarter = || AlignedIntervalContains::new(Duration::from_secs(15 * 60));
+        assert!(!matches(
+            quarter(),
+            "2024-01-01T10:00",
+            "2024-01-01T10:14:59"
+        ));
+        assert!(matches(
+            quarter(),
+            "2024-01-01T10:14:59",
+            "2024-01-01T10:15"
+        ));
+        assert!(matches(quarter(), "2024-01-01T10:07", "2024-01-01T11:07"));
+        assert!(matches(quarter(), "2024-01-01T23:59", "2024-01-02T00:00"));
+        let seven = || AlignedIntervalContains::new(Duration::from_secs(7 * 60 * 60));
+        assert!(matches(seven(), "2024-01-01T20:59", "2024-01-01T21:00"));
+        assert!(!matches(seven(), "2024-01-01T21:00", "2024-01-01T23:59"));
+    }
 }
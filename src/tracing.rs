@@ -0,0 +1,154 @@
+use std::{
+    io::Write,
+    sync::{Arc, Mutex},
+};
+
+use tracing_subscriber::fmt::MakeWriter;
+
+use crate::{
+    rotator::{LogRotator, RotatorError, RotatorOp},
+    LogWriter,
+};
+
+/// Hands the formatted events of [`tracing_subscriber::fmt()`] to a rotator, one record per event.
+///
+/// [`crate::writers::text::TextLogWriter`] fits as the log writer.
+#[derive(Debug)]
+pub struct RotatingMakeWriter<W>
+where
+    W: LogWriter,
+{
+    rotator: Arc<Mutex<LogRotator<W>>>,
+}
+impl<W> RotatingMakeWriter<W>
+where
+    W: LogWriter,
+{
+    pub fn new(rotator: Arc<Mutex<LogRotator<W>>>) -> Self {
+        Self { rotator }
+    }
+}
+impl<'a, W> MakeWriter<'a> for RotatingMakeWriter<W>
+where
+    W: LogWriter + Write + 'static,
+{
+    type Writer = RotatingWriter<'a, W>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        RotatingWriter {
+            rotator: &self.rotator,
+            line: vec![],
+        }
+    }
+}
+
+/// Buffers a formatted event and commits it to the rotator once it ends with a newline.
+///
+/// Anything left in the buffer is committed on flush and on drop.
+#[derive(Debug)]
+pub struct RotatingWriter<'a, W>
+where
+    W: LogWriter + Write,
+{
+    rotator: &'a Mutex<LogRotator<W>>,
+    line: Vec<u8>,
+}
+impl<W> RotatingWriter<'_, W>
+where
+    W: LogWriter + Write,
+{
+    fn commit(&mut self) -> Result<(), RotatorError> {
+        if self.line.is_empty() {
+            return Ok(());
+        }
+        let mut rotator = self.rotator.lock().unwrap();
+        if let Err(e) = rotator.writer().write_all(&self.line) {
+            return Err(RotatorError::new(
+                RotatorOp::Write,
+                rotator.current_path(),
+                e,
+            ));
+        }
+        self.line.clear();
+        rotator.try_incr_record_count()?;
+        Ok(())
+    }
+
+    fn try_commit(&mut self) -> std::io::Result<()> {
+        self.commit()
+            .map_err(|e| std::io::Error::new(e.source.kind(), e))
+    }
+}
+impl<W> Write for RotatingWriter<'_, W>
+where
+    W: LogWriter + Write,
+{
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.line.extend_from_slice(buf);
+        if self.line.ends_with(b"\n") {
+            self.try_commit()?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.try_commit()
+    }
+}
+impl<W> Drop for RotatingWriter<'_, W>
+where
+    W: LogWriter + Write,
+{
+    /// Keeps a failure for the next [`LogRotator::flush`].
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            return;
+        }
+        if let Err(e) = self.commit() {
+            self.rotator.lock().unwrap().defer_error(e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroUsize;
+
+    use crate::{
+        rotator::{list_log_files, RotationPolicy},
+        writers::text::TextLogWriter,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotator = LogRotator::<TextLogWriter>::new_or_panic(
+            dir.path().to_owned(),
+            RotationPolicy {
+                max_records: Some(NonZeroUsize::new(2).unwrap()),
+                ..Default::default()
+            },
+        );
+        let rotator = Arc::new(Mutex::new(rotator));
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(RotatingMakeWriter::new(Arc::clone(&rotator)))
+            .without_time()
+            .finish();
+        ::tracing::subscriber::with_default(subscriber, || {
+            for n in 0..5 {
+                ::tracing::info!(n, "event");
+            }
+        });
+        rotator.lock().unwrap().flush().unwrap();
+
+        let lines = list_log_files(dir.path(), "log", None)
+            .into_iter()
+            .map(|f| std::fs::read_to_string(f.path).unwrap().lines().count())
+            .collect::<Vec<_>>();
+        assert_eq!(lines, [2, 2, 1]);
+        let last = std::fs::read_to_string(dir.path().join("2.log")).unwrap();
+        assert!(last.contains("event n=4"));
+    }
+}
@@ -0,0 +1,187 @@
+use std::{io::Write, path::Path};
+
+use crate::LogWriter;
+
+/// Buffers up to `CAPACITY` bytes in front of a writer that goes straight to its file.
+///
+/// The bytes are passed on to the writer once the buffer is full, on flush, before the footer
+/// and on drop.
+#[derive(Debug)]
+pub struct Buffered<W, const CAPACITY: usize = 8192>
+where
+    W: Write,
+{
+    inner: W,
+    buf: Vec<u8>,
+}
+impl<W, const CAPACITY: usize> Buffered<W, CAPACITY>
+where
+    W: Write,
+{
+    pub fn inner(&self) -> &W {
+        &self.inner
+    }
+
+    fn new(inner: W) -> Self {
+        Self {
+            inner,
+            buf: Vec::with_capacity(CAPACITY),
+        }
+    }
+
+    fn flush_buf(&mut self) -> std::io::Result<()> {
+        self.inner.write_all(&self.buf)?;
+        self.buf.clear();
+        Ok(())
+    }
+}
+impl<W, const CAPACITY: usize> Write for Buffered<W, CAPACITY>
+where
+    W: Write,
+{
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if CAPACITY < self.buf.len() + buf.len() {
+            self.flush_buf()?;
+        }
+        if CAPACITY <= buf.len() {
+            return self.inner.write(buf);
+        }
+        self.buf.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.flush_buf()?;
+        self.inner.flush()
+    }
+}
+impl<W, const CAPACITY: usize> LogWriter for Buffered<W, CAPACITY>
+where
+    W: LogWriter + Write,
+{
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.flush_buf()?;
+        LogWriter::flush(&mut self.inner)
+    }
+
+    fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        W::open(path).map(Self::new)
+    }
+
+    fn open_for(
+        path: impl AsRef<Path>,
+        rotation: &crate::rotator::RotationPolicy,
+    ) -> std::io::Result<Self> {
+        W::open_for(path, rotation).map(Self::new)
+    }
+
+    fn try_open_append(path: impl AsRef<Path>) -> std::io::Result<(Self, usize)> {
+        let (inner, records) = W::try_open_append(path)?;
+        Ok((Self::new(inner), records))
+    }
+
+    fn file_extension() -> &'static str {
+        W::file_extension()
+    }
+
+    fn write_footer(&mut self, records_written: usize) -> std::io::Result<()> {
+        self.flush_buf()?;
+        self.inner.write_footer(records_written)
+    }
+
+    fn on_open(&mut self) -> std::io::Result<usize> {
+        self.inner.on_open()
+    }
+
+    fn on_close(&mut self) -> std::io::Result<usize> {
+        self.flush_buf()?;
+        self.inner.on_close()
+    }
+
+    /// Leaves the buffered bytes out, like the file buffers of other writers.
+    fn sync(&mut self) -> std::io::Result<()> {
+        self.inner.sync()
+    }
+
+    fn bytes_written(&mut self) -> Option<u64> {
+        let bytes = self.inner.bytes_written()?;
+        Some(bytes + self.buf.len() as u64)
+    }
+
+    fn schema(&self) -> Option<u64> {
+        self.inner.schema()
+    }
+
+    fn schema_text(&self) -> Option<String> {
+        self.inner.schema_text()
+    }
+}
+impl<W, const CAPACITY: usize> Drop for Buffered<W, CAPACITY>
+where
+    W: Write,
+{
+    /// Failures are ignored, like those of [`std::io::BufWriter`] on drop; flush beforehand to
+    /// get them.
+    fn drop(&mut self) {
+        let _ = self.flush_buf();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use super::*;
+
+    /// Writes to its file without a buffer, counting the writes.
+    #[derive(Debug)]
+    struct Unbuffered {
+        file: File,
+        writes: usize,
+    }
+    impl Write for Unbuffered {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.writes += 1;
+            self.file.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            self.file.flush()
+        }
+    }
+    impl LogWriter for Unbuffered {
+        fn flush(&mut self) -> std::io::Result<()> {
+            Write::flush(self)
+        }
+
+        fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+            let file = File::create(path)?;
+            Ok(Self { file, writes: 0 })
+        }
+
+        fn file_extension() -> &'static str {
+            "log"
+        }
+    }
+
+    #[test]
+    fn test_buffered() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("0.log");
+        let mut w = Buffered::<Unbuffered, 64>::open(&path).unwrap();
+        assert_eq!(<Buffered<Unbuffered, 64>>::file_extension(), "log");
+        for n in 0..100 {
+            writeln!(w, "{n:07}").unwrap();
+        }
+        assert_eq!(w.inner().writes, 12);
+        LogWriter::flush(&mut w).unwrap();
+        assert_eq!(w.inner().writes, 13);
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(contents.lines().count(), 100);
+
+        writeln!(w, "{}", "a".repeat(64)).unwrap();
+        drop(w);
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(contents.lines().last().unwrap(), "a".repeat(64));
+    }
+}
@@ -0,0 +1,416 @@
+use std::{
+    fs::File,
+    hash::{DefaultHasher, Hash, Hasher},
+    io::{BufWriter, Write},
+    path::Path,
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+};
+
+pub use csv;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    reader::{LogReader, LogReaderFor},
+    LogWriter,
+};
+
+/// Writes one CSV file per epoch.
+///
+/// Records go through [`Self::serialize`] or [`Self::writer`]; the header row is written along
+/// with the first record of each file, which is truncated on open.
+#[derive(Debug)]
+pub struct CsvLogWriter {
+    writer: csv::Writer<CountingFile>,
+    header: Option<csv::StringRecord>,
+    /// Hash of [`Self::header`].
+    schema: Option<u64>,
+    /// [`Self::header`] is that of the file, which later records must match.
+    is_header_pinned: bool,
+}
+impl CsvLogWriter {
+    pub fn writer(&mut self) -> &mut csv::Writer<CountingFile> {
+        &mut self.writer
+    }
+
+    /// Fails without writing if the columns of `record` are not those of [`Self::header`].
+    ///
+    /// Records without named fields, such as tuples, are not checked.
+    /// Neither are the rows written through [`Self::writer`], which never set the header either.
+    pub fn serialize<R: Serialize>(&mut self, record: &R) -> csv::Result<()> {
+        let header = header_of(record)?;
+        match (&self.header, header) {
+            (Some(expected), Some(header)) if *expected != header => {
+                let e = std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("The record has columns {header:?} instead of {expected:?}"),
+                );
+                return Err(e.into());
+            }
+            (_, header) if !self.is_header_pinned => {
+                self.schema = header.as_ref().map(schema_of);
+                self.header = header;
+                self.is_header_pinned = true;
+            }
+            _ => {}
+        }
+        self.writer.serialize(record)
+    }
+
+    /// The header row of the file, read from it if reopened by [`LogWriter::try_open_append`]
+    /// or else taken from the first record given to [`Self::serialize`].
+    pub fn header(&self) -> Option<&csv::StringRecord> {
+        self.header.as_ref()
+    }
+
+    /// Drains the CSV buffer, flushing the file buffer too only if `flush_file`.
+    fn flush_csv(&mut self, flush_file: bool) -> std::io::Result<()> {
+        let file = self.writer.get_ref();
+        file.flush_file.store(flush_file, Ordering::Relaxed);
+        self.writer.flush()
+    }
+}
+impl LogWriter for CsvLogWriter {
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.flush_csv(true)
+    }
+
+    fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let file = File::options()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        let file = CountingFile {
+            file: BufWriter::new(file),
+            bytes: AtomicU64::new(0),
+            flush_file: AtomicBool::new(false),
+        };
+        let writer = csv::Writer::from_writer(file);
+        Ok(Self {
+            writer,
+            header: None,
+            schema: None,
+            is_header_pinned: false,
+        })
+    }
+
+    /// Counts the rows past the header, skipping `#` comments such as footers, and writes the
+    /// header again only if the file is empty.
+    fn try_open_append(path: impl AsRef<Path>) -> std::io::Result<(Self, usize)> {
+        let path = path.as_ref();
+        let (file, bytes) = super::open_append(path)?;
+        let mut records = 0;
+        let mut reader = csv::ReaderBuilder::new()
+            .comment(Some(b'#'))
+            .flexible(true)
+            .from_path(path)?;
+        let header = match bytes {
+            0 => None,
+            _ => Some(reader.headers()?.clone()),
+        };
+        for record in reader.records() {
+            record?;
+            records += 1;
+        }
+        let file = CountingFile {
+            file: BufWriter::new(file),
+            bytes: AtomicU64::new(bytes),
+            flush_file: AtomicBool::new(false),
+        };
+        let writer = csv::WriterBuilder::new()
+            .has_headers(bytes == 0)
+            .from_writer(file);
+        let writer = Self {
+            writer,
+            schema: header.as_ref().map(schema_of),
+            header,
+            is_header_pinned: bytes != 0,
+        };
+        Ok((writer, records))
+    }
+
+    fn file_extension() -> &'static str {
+        "csv"
+    }
+
+    /// Writes `#records=<n>` past the CSV writer so that the column count is not checked, still
+    /// counting it in [`LogWriter::bytes_written`].
+    fn write_footer(&mut self, records_written: usize) -> std::io::Result<()> {
+        LogWriter::flush(self)?;
+        let footer = format!("#records={records_written}\n");
+        let counting = self.writer.get_ref();
+        let mut file = counting.file.get_ref();
+        file.write_all(footer.as_bytes())?;
+        counting
+            .bytes
+            .fetch_add(footer.len() as u64, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn sync(&mut self) -> std::io::Result<()> {
+        self.writer.get_ref().file.get_ref().sync_data()
+    }
+
+    /// Moves the rows buffered by the CSV writer into the file buffer to count them.
+    fn bytes_written(&mut self) -> Option<u64> {
+        self.flush_csv(false).ok()?;
+        Some(self.writer.get_ref().bytes.load(Ordering::Relaxed))
+    }
+
+    /// Hashes [`CsvLogWriter::header`].
+    fn schema(&self) -> Option<u64> {
+        self.schema
+    }
+
+    /// [`CsvLogWriter::header`] as a CSV row.
+    fn schema_text(&self) -> Option<String> {
+        let mut writer = csv::Writer::from_writer(vec![]);
+        writer.write_record(self.header.as_ref()?).ok()?;
+        let row = String::from_utf8(writer.into_inner().ok()?).ok()?;
+        row.strip_suffix('\n').map(str::to_string)
+    }
+}
+
+fn schema_of(header: &csv::StringRecord) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    header.iter().for_each(|column| column.hash(&mut hasher));
+    hasher.finish()
+}
+
+/// The header row that `record` would start a file with, if it has named fields.
+fn header_of<R: Serialize>(record: &R) -> csv::Result<Option<csv::StringRecord>> {
+    let mut writer = csv::Writer::from_writer(vec![]);
+    writer.serialize(record)?;
+    let rows = writer.into_inner().map_err(|e| e.into_error())?;
+    let mut rows = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .from_reader(rows.as_slice())
+        .into_records()
+        .collect::<csv::Result<Vec<_>>>()?;
+    Ok(match rows.len() {
+        2 => Some(rows.swap_remove(0)),
+        _ => None,
+    })
+}
+
+/// Buffered log file counting the bytes handed to it.
+///
+/// Flushing reaches the file only when [`CsvLogWriter`] flushes, so that the rows buffered by the
+/// CSV writer can be counted without a system call.
+#[derive(Debug)]
+pub struct CountingFile {
+    file: BufWriter<File>,
+    /// Atomic like `flush_file`, as the CSV writer only lends the file out shared.
+    bytes: AtomicU64,
+    flush_file: AtomicBool,
+}
+impl Write for CountingFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.file.write(buf)?;
+        *self.bytes.get_mut() += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        if !*self.flush_file.get_mut() {
+            return Ok(());
+        }
+        self.file.flush()
+    }
+}
+
+/// Skips the header row and the footer.
+impl<R> LogReaderFor<CsvLogWriter> for R
+where
+    R: DeserializeOwned,
+{
+    fn read_records(reader: impl std::io::Read) -> impl Iterator<Item = std::io::Result<Self>> {
+        let reader = csv::ReaderBuilder::new()
+            .comment(Some(b'#'))
+            .from_reader(reader);
+        reader.into_deserialize().map(|r| {
+            r.map_err(|e| match e.kind() {
+                csv::ErrorKind::Io(io) => std::io::Error::new(io.kind(), e),
+                _ => e.into(),
+            })
+        })
+    }
+}
+
+impl LogReader<CsvLogWriter> {
+    /// The header row of each file by epoch, in the order of [`Self::files`], e.g. to decode
+    /// the files written before a column was added.
+    pub fn headers(&self) -> impl Iterator<Item = (usize, csv::Result<csv::StringRecord>)> + '_ {
+        self.files().iter().map(|file| {
+            let header = csv::ReaderBuilder::new()
+                .comment(Some(b'#'))
+                .from_path(&file.path)
+                .and_then(|mut reader| reader.headers().cloned());
+            (file.epoch, header)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        num::NonZeroUsize,
+        sync::{Arc, Mutex},
+    };
+
+    use crate::rotator::{LogRotator, RotationPolicy};
+
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Record {
+        a: u32,
+        b: &'static str,
+    }
+
+    #[derive(Serialize)]
+    struct Narrow {
+        a: u32,
+    }
+
+    #[test]
+    fn test_serialize() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.csv");
+        let mut w = CsvLogWriter::open(&path).unwrap();
+        w.serialize(&Record { a: 1, b: "x" }).unwrap();
+        w.serialize(&Record { a: 2, b: "y" }).unwrap();
+        assert_eq!(w.bytes_written(), Some(12));
+        w.write_footer(2).unwrap();
+        assert_eq!(w.bytes_written(), Some(23));
+        w.flush().unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(content, "a,b\n1,x\n2,y\n#records=2\n");
+    }
+
+    #[test]
+    fn test_open_append() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.csv");
+        let (mut w, records) = CsvLogWriter::try_open_append(&path).unwrap();
+        assert_eq!(records, 0);
+        w.serialize(&Record { a: 1, b: "x" }).unwrap();
+        w.write_footer(1).unwrap();
+        drop(w);
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"2,y")
+            .unwrap();
+
+        let (mut w, records) = CsvLogWriter::try_open_append(&path).unwrap();
+        assert_eq!(records, 2);
+        w.serialize(&Record { a: 3, b: "z" }).unwrap();
+        w.flush().unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(content, "a,b\n1,x\n#records=1\n2,y\n3,z\n");
+    }
+
+    #[test]
+    fn test_schema_across_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy {
+            max_records: NonZeroUsize::new(2),
+            max_epochs: usize::MAX,
+            write_metadata: true,
+            ..Default::default()
+        };
+        let mut rotator = LogRotator::<CsvLogWriter>::new_or_panic(dir.path().to_owned(), rotation);
+        let changes = Arc::new(Mutex::new(vec![]));
+        rotator.set_on_rotate(Box::new({
+            let changes = Arc::clone(&changes);
+            move |event| {
+                changes.lock().unwrap().push(event.schema_changed);
+                false
+            }
+        }));
+        for a in 0..2 {
+            rotator.writer().serialize(&Narrow { a }).unwrap();
+            rotator.try_incr_record_count().unwrap();
+        }
+        for a in 2..6 {
+            rotator.writer().serialize(&Record { a, b: "x" }).unwrap();
+            rotator.try_incr_record_count().unwrap();
+        }
+        rotator.wait_for_on_rotate();
+        assert_eq!(*changes.lock().unwrap(), [false, true, false]);
+
+        let reader = LogReader::<CsvLogWriter>::new(dir.path());
+        let headers = reader
+            .headers()
+            .take(3)
+            .map(|(epoch, header)| (epoch, header.unwrap()))
+            .collect::<Vec<_>>();
+        let narrow = csv::StringRecord::from(vec!["a"]);
+        let wide = csv::StringRecord::from(vec!["a", "b"]);
+        let schemas = reader
+            .metadata()
+            .take(3)
+            .map(|(epoch, metadata)| {
+                let metadata = metadata.unwrap();
+                (epoch, metadata.schema, metadata.schema_text)
+            })
+            .collect::<Vec<_>>();
+        let (narrow_schema, wide_schema) = (schema_of(&narrow), schema_of(&wide));
+        assert_eq!(headers, [(0, narrow), (1, wide.clone()), (2, wide)]);
+        assert_eq!(
+            schemas,
+            [
+                (0, Some(narrow_schema), Some("a".to_string())),
+                (1, Some(wide_schema), Some("a,b".to_string())),
+                (2, Some(wide_schema), Some("a,b".to_string()))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_schema_change_within_epoch() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy {
+            max_records: NonZeroUsize::new(10),
+            resume: true,
+            ..Default::default()
+        };
+        let mut rotator =
+            LogRotator::<CsvLogWriter>::new_or_panic(dir.path().to_owned(), rotation.clone());
+        rotator.writer().serialize(&Narrow { a: 0 }).unwrap();
+        rotator.try_incr_record_count().unwrap();
+        let e = rotator.writer().serialize(&Record { a: 1, b: "x" });
+        assert!(e.unwrap_err().to_string().contains("instead of"));
+        rotator.writer().serialize(&Narrow { a: 2 }).unwrap();
+        rotator.try_incr_record_count().unwrap();
+        drop(rotator);
+
+        let mut rotator = LogRotator::<CsvLogWriter>::new_or_panic(dir.path().to_owned(), rotation);
+        let e = rotator.writer().serialize(&Record { a: 3, b: "y" });
+        assert!(e.unwrap_err().to_string().contains("instead of"));
+        rotator.writer().serialize(&Narrow { a: 4 }).unwrap();
+        rotator.try_incr_record_count().unwrap();
+        rotator.flush_or_panic();
+        let content = std::fs::read_to_string(dir.path().join("0.csv")).unwrap();
+        assert_eq!(content, "a\n0\n2\n4\n");
+    }
+
+    #[test]
+    fn test_resume_mismatch() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.csv");
+        std::fs::write(&path, "a,b\n1,x\n").unwrap();
+        let (mut w, records) = CsvLogWriter::try_open_append(&path).unwrap();
+        assert_eq!(records, 1);
+        let header = csv::StringRecord::from(vec!["a", "b"]);
+        assert_eq!(w.header(), Some(&header));
+        let e = w.serialize(&Narrow { a: 2 }).unwrap_err();
+        assert!(e.to_string().contains("instead of"), "{e}");
+        w.serialize(&Record { a: 2, b: "y" }).unwrap();
+        w.flush().unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(content, "a,b\n1,x\n2,y\n");
+    }
+}
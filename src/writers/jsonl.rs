@@ -0,0 +1,167 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{reader::LogReaderFor, LogWriter};
+
+/// Writes one [JSON Lines](https://jsonlines.org/) file per epoch.
+///
+/// Each record is a compact JSON object on its own line, so every file can be parsed independently.
+#[derive(Debug)]
+pub struct JsonLinesLogWriter {
+    file: BufWriter<File>,
+    bytes: u64,
+}
+impl JsonLinesLogWriter {
+    pub fn write<R: Serialize>(&mut self, record: &R) -> serde_json::Result<()> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+        self.file.write_all(&line).map_err(serde_json::Error::io)?;
+        self.bytes += line.len() as u64;
+        Ok(())
+    }
+}
+impl LogWriter for JsonLinesLogWriter {
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+
+    fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let file = File::options()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        Ok(Self {
+            file: BufWriter::new(file),
+            bytes: 0,
+        })
+    }
+
+    fn try_open_append(path: impl AsRef<Path>) -> std::io::Result<(Self, usize)> {
+        let path = path.as_ref();
+        let (file, bytes) = super::open_append(path)?;
+        let records = super::count_lines(path)?;
+        let file = BufWriter::new(file);
+        Ok((Self { file, bytes }, records))
+    }
+
+    fn file_extension() -> &'static str {
+        "jsonl"
+    }
+
+    fn sync(&mut self) -> std::io::Result<()> {
+        self.file.get_ref().sync_data()
+    }
+
+    fn bytes_written(&mut self) -> Option<u64> {
+        Some(self.bytes)
+    }
+}
+
+impl<R> LogReaderFor<JsonLinesLogWriter> for R
+where
+    R: DeserializeOwned,
+{
+    fn read_records(reader: impl std::io::Read) -> impl Iterator<Item = std::io::Result<Self>> {
+        BufReader::new(reader)
+            .lines()
+            .filter(|line| line.as_ref().map_or(true, |line| !line.is_empty()))
+            .map(|line| Ok(serde_json::from_str(&line?)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, num::NonZeroUsize};
+
+    use crate::rotator::{list_log_files, LogRotator, RotationPolicy};
+
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Event {
+        n: usize,
+        tags: BTreeMap<&'static str, BTreeMap<&'static str, usize>>,
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.jsonl");
+        let mut w = JsonLinesLogWriter::open(&path).unwrap();
+        let events = (0..3)
+            .map(|n| {
+                let tags = BTreeMap::from_iter([("a", BTreeMap::from_iter([("b", n)]))]);
+                Event { n, tags }
+            })
+            .collect::<Vec<_>>();
+        events.iter().for_each(|e| w.write(e).unwrap());
+        w.flush().unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(w.bytes_written(), Some(content.len() as u64));
+        let lines = content.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), events.len());
+        for (line, event) in lines.iter().zip(&events) {
+            assert_eq!(*line, serde_json::to_string(event).unwrap());
+        }
+        assert_eq!(lines[0], r#"{"n":0,"tags":{"a":{"b":0}}}"#);
+
+        let records = <serde_json::Value as LogReaderFor<JsonLinesLogWriter>>::read_records(
+            File::open(&path).unwrap(),
+        );
+        let n = records.map(|r| r.unwrap()["n"].as_u64().unwrap());
+        assert_eq!(n.collect::<Vec<_>>(), [0, 1, 2]);
+    }
+
+    #[test]
+    fn test_open_append() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.jsonl");
+        std::fs::write(&path, "{\"n\":0}\n{\"n\":1}").unwrap();
+        let (mut w, records) = JsonLinesLogWriter::try_open_append(&path).unwrap();
+        assert_eq!(records, 2);
+        w.write(&BTreeMap::from_iter([("n", 2)])).unwrap();
+        w.flush().unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(content, "{\"n\":0}\n{\"n\":1}\n{\"n\":2}\n");
+        assert_eq!(w.bytes_written(), Some(content.len() as u64));
+    }
+
+    #[test]
+    fn test_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut rotator = LogRotator::<JsonLinesLogWriter>::new_or_panic(
+            dir.path().to_owned(),
+            RotationPolicy {
+                max_records: Some(NonZeroUsize::new(2).unwrap()),
+                max_epochs: 3,
+                ..Default::default()
+            },
+        );
+        for n in 0..5 {
+            let tags = BTreeMap::from_iter([("a", BTreeMap::from_iter([("b", n)]))]);
+            rotator.writer().write(&Event { n, tags }).unwrap();
+            rotator.try_incr_record_count().unwrap();
+        }
+        rotator.flush().unwrap();
+
+        let files = list_log_files(dir.path(), "jsonl", None);
+        assert_eq!(files.len(), 3);
+        let mut n = 0;
+        for file in files {
+            let content = std::fs::read_to_string(&file.path).unwrap();
+            for line in content.lines() {
+                let value: serde_json::Value = serde_json::from_str(line).unwrap();
+                assert_eq!(value["n"], n);
+                assert_eq!(value["tags"]["a"]["b"], n);
+                n += 1;
+            }
+        }
+        assert_eq!(n, 5);
+    }
+}
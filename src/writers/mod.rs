@@ -0,0 +1,49 @@
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+pub mod buffered;
+#[cfg(feature = "csv")]
+pub mod csv;
+#[cfg(feature = "jsonl")]
+pub mod jsonl;
+pub mod text;
+
+/// Opens the file for appending and returns it along with its length.
+///
+/// A last line cut short, e.g. by a crash, is terminated first so that appended lines start on
+/// their own.
+fn open_append(path: &Path) -> std::io::Result<(File, u64)> {
+    let mut file = File::options()
+        .read(true)
+        .append(true)
+        .create(true)
+        .open(path)?;
+    let mut len = file.metadata()?.len();
+    if len != 0 {
+        let mut last = [0];
+        file.seek(SeekFrom::End(-1))?;
+        file.read_exact(&mut last)?;
+        if last[0] != b'\n' {
+            file.write_all(b"\n")?;
+            len += 1;
+        }
+    }
+    Ok((file, len))
+}
+
+/// Counts the newline-terminated lines of the file.
+fn count_lines(path: &Path) -> std::io::Result<usize> {
+    let mut file = File::open(path)?;
+    let mut buf = [0; 8 * 1024];
+    let mut lines = 0;
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            return Ok(lines);
+        }
+        lines += buf[..n].iter().filter(|&&b| b == b'\n').count();
+    }
+}
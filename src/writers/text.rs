@@ -0,0 +1,100 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use crate::LogWriter;
+
+/// Writes one plain text file per epoch through [`Write`].
+///
+/// Callers are expected to write whole lines and count each of them as a record.
+#[derive(Debug)]
+pub struct TextLogWriter {
+    file: BufWriter<File>,
+    bytes: u64,
+}
+impl TextLogWriter {
+    /// Writes `line` and a newline.
+    pub fn write_line(&mut self, line: &str) -> std::io::Result<()> {
+        self.write_all(line.as_bytes())?;
+        self.write_all(b"\n")
+    }
+}
+impl Write for TextLogWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.file.write(buf)?;
+        self.bytes += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+impl LogWriter for TextLogWriter {
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+
+    fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let file = File::options()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        Ok(Self {
+            file: BufWriter::new(file),
+            bytes: 0,
+        })
+    }
+
+    /// Counts every line as a record.
+    fn try_open_append(path: impl AsRef<Path>) -> std::io::Result<(Self, usize)> {
+        let path = path.as_ref();
+        let (file, bytes) = super::open_append(path)?;
+        let records = super::count_lines(path)?;
+        let file = BufWriter::new(file);
+        Ok((Self { file, bytes }, records))
+    }
+
+    fn file_extension() -> &'static str {
+        "log"
+    }
+
+    fn sync(&mut self) -> std::io::Result<()> {
+        self.file.get_ref().sync_data()
+    }
+
+    fn bytes_written(&mut self) -> Option<u64> {
+        Some(self.bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroUsize;
+
+    use crate::rotator::{LogRotator, RotationPolicy};
+
+    use super::*;
+
+    #[test]
+    fn test_write_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = RotationPolicy {
+            max_records: NonZeroUsize::new(2),
+            ..Default::default()
+        };
+        let mut rotator =
+            LogRotator::<TextLogWriter>::new_or_panic(dir.path().to_owned(), rotation);
+        for line in ["a", "b", "c"] {
+            rotator.writer().write_line(line).unwrap();
+            rotator.try_incr_record_count().unwrap();
+        }
+        rotator.flush().unwrap();
+        let read = |epoch: usize| std::fs::read_to_string(dir.path().join(format!("{epoch}.log")));
+        assert_eq!(read(0).unwrap(), "a\nb\n");
+        assert_eq!(read(1).unwrap(), "c\n");
+    }
+}
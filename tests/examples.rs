@@ -0,0 +1,132 @@
+use std::{collections::BTreeSet, num::NonZeroUsize, path::Path};
+
+#[allow(dead_code)]
+#[path = "../examples/crash_recovery.rs"]
+mod crash_recovery;
+#[allow(dead_code)]
+#[path = "../examples/csv_service.rs"]
+mod csv_service;
+#[allow(dead_code)]
+#[path = "../examples/merge_finished.rs"]
+mod merge_finished;
+#[allow(dead_code)]
+#[path = "../examples/multi_table.rs"]
+mod multi_table;
+
+fn tree(dir: &Path) -> BTreeSet<String> {
+    let mut entries = BTreeSet::new();
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        let name = path.file_name().unwrap().to_str().unwrap().to_string();
+        if path.is_dir() {
+            entries.extend(tree(&path).into_iter().map(|e| format!("{name}/{e}")));
+        } else {
+            entries.insert(name);
+        }
+    }
+    entries
+}
+
+fn read(path: impl AsRef<Path>) -> String {
+    std::fs::read_to_string(path).unwrap()
+}
+
+#[test]
+fn test_csv_service() {
+    let dir = tempfile::tempdir().unwrap();
+    csv_service::run(csv_service::Args {
+        output_dir: dir.path().to_owned(),
+        requests: 10,
+        max_records: NonZeroUsize::new(3),
+        max_epochs: 2,
+    })
+    .unwrap();
+    assert_eq!(
+        tree(dir.path()),
+        BTreeSet::from(["2.csv", "3.csv", "epoch"].map(String::from))
+    );
+    assert_eq!(
+        read(dir.path().join("2.csv")),
+        "id,path,status\n6,/health,200\n7,/api,500\n8,/api,200\n"
+    );
+    assert_eq!(
+        read(dir.path().join("3.csv")),
+        "id,path,status\n9,/health,200\n"
+    );
+}
+
+#[test]
+fn test_multi_table() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = dir.path().join("config.json");
+    let output_dir = dir.path().join("logs");
+    let json = serde_json::json!({
+        "output_dir": output_dir,
+        "max_records": 2,
+        "max_epochs": 2,
+        "tables": { "requests": 5, "errors": 1 },
+    });
+    std::fs::write(&config, json.to_string()).unwrap();
+    multi_table::run(multi_table::Args { config }).unwrap();
+    let expected = [
+        "errors/0.csv",
+        "errors/epoch",
+        "requests/1.csv",
+        "requests/2.csv",
+        "requests/epoch",
+    ];
+    assert_eq!(
+        tree(&output_dir),
+        BTreeSet::from(expected.map(String::from))
+    );
+    assert_eq!(
+        read(output_dir.join("requests/2.csv")),
+        "table,n\nrequests,4\n"
+    );
+}
+
+#[test]
+fn test_crash_recovery() {
+    let dir = tempfile::tempdir().unwrap();
+    crash_recovery::run(crash_recovery::Args {
+        output_dir: dir.path().to_owned(),
+        flushed: 2,
+        lost: 2,
+        after_restart: 1,
+        max_records: NonZeroUsize::new(100).unwrap(),
+    })
+    .unwrap();
+    assert_eq!(
+        tree(dir.path()),
+        BTreeSet::from(["0.csv", "1.csv", "epoch"].map(String::from))
+    );
+    assert_eq!(
+        read(dir.path().join("0.csv")),
+        "phase,n\nbefore,0\nbefore,1\n"
+    );
+    assert_eq!(read(dir.path().join("1.csv")), "phase,n\nafter,0\n");
+    assert_eq!(read(dir.path().join("epoch")), "1");
+}
+
+#[test]
+fn test_merge_finished() {
+    let dir = tempfile::tempdir().unwrap();
+    let input_dir = dir.path().join("logs");
+    csv_service::run(csv_service::Args {
+        output_dir: input_dir.clone(),
+        requests: 5,
+        max_records: NonZeroUsize::new(2),
+        max_epochs: 7,
+    })
+    .unwrap();
+    let args = merge_finished::Args {
+        input_dir: input_dir.clone(),
+        output: dir.path().join("merged.csv"),
+        watch: false,
+    };
+    let mut merged = BTreeSet::new();
+    assert_eq!(merge_finished::merge_once(&args, &mut merged), 4);
+    assert_eq!(merge_finished::merge_once(&args, &mut merged), 0);
+    let expected = "id,path,status\n0,/health,500\n1,/api,200\n2,/api,200\n3,/health,200\n";
+    assert_eq!(read(&args.output), expected);
+}